@@ -1,13 +1,18 @@
 package dataloader
 
 import (
+	"bytes"
 	"context"
 	"encoding/json"
 	"errors"
 	"fmt"
 	"io"
 	"log"
+	"math"
 	"net/http"
+	"os"
+	"strconv"
+	"strings"
 	"time"
 
 	"github.com/MichalKalita/ote/storage"
@@ -42,6 +47,41 @@ var (
 // httptest.Server.
 var BaseURL = "https://www.ote-cr.cz/en/short-term-markets/electricity/day-ahead-market/@@chart-data"
 
+// AllowPartialDays makes FetchData accept a price series shorter than the
+// day's quarter count, which OTE occasionally serves mid-publish. When false
+// (the default) any length mismatch is reported as *InvalidDataSizeError.
+var AllowPartialDays = false
+
+// MaxRetries is how many times FetchData retries after a network error or a
+// 5xx status. Other failures are returned immediately.
+var MaxRetries = 3
+
+// RetryBackoff is the delay before the first retry; it doubles on each
+// further attempt. Tests shorten it.
+var RetryBackoff = 500 * time.Millisecond
+
+// networkError marks transport failures as retryable without changing their
+// message.
+type networkError struct {
+	err error
+}
+
+func (e *networkError) Error() string { return fmt.Sprintf("Network error: %v", e.err) }
+func (e *networkError) Unwrap() error { return e.err }
+
+// ExhaustedRetriesError is returned when every attempt failed with a
+// retryable error. Last is the error of the final attempt.
+type ExhaustedRetriesError struct {
+	Attempts int
+	Last     error
+}
+
+func (e *ExhaustedRetriesError) Error() string {
+	return fmt.Sprintf("giving up after %d attempts: %v", e.Attempts, e.Last)
+}
+
+func (e *ExhaustedRetriesError) Unwrap() error { return e.Last }
+
 type UnexpectedStatusError struct {
 	Status int
 }
@@ -50,34 +90,231 @@ func (e *UnexpectedStatusError) Error() string {
 	return fmt.Sprintf("Unexpected response status: %d", e.Status)
 }
 
-// FetchData fetches day-ahead 15-minute electricity prices for the given
-// Prague-local date. The returned slice has one entry per quarter-hour;
-// timestamps are in UTC. On DST days the slice has 92 or 100 entries.
-func FetchData(date time.Time) ([]storage.Quarter, error) {
+// UnexpectedContentTypeError is returned when OTE answers with a success status
+// but an HTML body (typically a maintenance or error page) instead of JSON.
+type UnexpectedContentTypeError struct {
+	ContentType string
+}
+
+func (e *UnexpectedContentTypeError) Error() string {
+	return fmt.Sprintf("Unexpected content type %q: expected JSON", e.ContentType)
+}
+
+// PriceLineNotFoundError is returned when no dataLine title matches
+// PriceLineTitles. It lists the titles OTE did send and matches
+// ErrPriceDataNotFound with errors.Is.
+type PriceLineNotFoundError struct {
+	Titles []string
+}
+
+func (e *PriceLineNotFoundError) Error() string {
+	return fmt.Sprintf("%v, titles present: %q", ErrPriceDataNotFound, e.Titles)
+}
+
+func (e *PriceLineNotFoundError) Unwrap() error { return ErrPriceDataNotFound }
+
+// PriceLineTitles are the accepted titles of the 15-minute price series in
+// order of preference, compared after lowercasing and collapsing whitespace.
+// "price (eur/mwh)" is the older label.
+var PriceLineTitles = []string{
+	"15min price (eur/mwh)",
+	"15 min price (eur/mwh)",
+	"15-min price (eur/mwh)",
+	"price (eur/mwh)",
+}
+
+func normalizeTitle(title string) string {
+	return strings.ToLower(strings.Join(strings.Fields(title), " "))
+}
+
+// findPriceLine picks the price series from lines by PriceLineTitles.
+func findPriceLine(lines []dataLine) (dataLine, error) {
+	for _, want := range PriceLineTitles {
+		for _, line := range lines {
+			if normalizeTitle(line.Title) == want {
+				if line.Title != "15min price (EUR/MWh)" {
+					log.Printf("Using price data line %q", line.Title)
+				}
+				return line, nil
+			}
+		}
+	}
+	titles := make([]string, len(lines))
+	for i, line := range lines {
+		titles[i] = line.Title
+	}
+	return dataLine{}, &PriceLineNotFoundError{Titles: titles}
+}
+
+// InvalidDataSizeError is returned when the price series does not have one
+// point per quarter-hour of the requested day.
+type InvalidDataSizeError struct {
+	Got  int
+	Want int
+}
+
+func (e *InvalidDataSizeError) Error() string {
+	return fmt.Sprintf("Invalid data size: got %d points, expected %d", e.Got, e.Want)
+}
+
+// prague is Europe/Prague, the zone OTE days are in, loaded once. It is UTC
+// when the zone database is missing.
+var prague = func() *time.Location {
 	loc, err := time.LoadLocation("Europe/Prague")
 	if err != nil {
-		loc = time.UTC
+		return time.UTC
+	}
+	return loc
+}()
+
+// QuartersInDay returns the number of 15-minute intervals in the Prague-local
+// date: 96 normally, 92 on the spring DST switch and 100 in autumn.
+func QuartersInDay(date time.Time) int {
+	dayStart := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, prague)
+	return int(dayStart.AddDate(0, 0, 1).Sub(dayStart) / (15 * time.Minute))
+}
+
+// DefaultHTTPTimeout bounds a single OTE request, including reading the body,
+// unless OTE_HTTP_TIMEOUT_SECS overrides it.
+const DefaultHTTPTimeout = 10 * time.Second
+
+// NewClient returns the HTTP client FetchData callers should create once and
+// share, so consecutive fetches reuse pooled connections instead of paying a
+// new TLS handshake each time. The request timeout is read from
+// OTE_HTTP_TIMEOUT_SECS (fractions allowed); an invalid value is logged and
+// the default is used.
+func NewClient() *http.Client {
+	timeout := DefaultHTTPTimeout
+	if v := os.Getenv("OTE_HTTP_TIMEOUT_SECS"); v != "" {
+		secs, err := strconv.ParseFloat(v, 64)
+		if err != nil || secs <= 0 || math.IsInf(secs, 0) {
+			log.Printf("Ignoring invalid OTE_HTTP_TIMEOUT_SECS %q, using %v", v, timeout)
+		} else {
+			timeout = time.Duration(secs * float64(time.Second))
+		}
 	}
-	dayStart := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, loc)
-	if dayStart.Before(time.Date(2025, 10, 1, 0, 0, 0, 0, loc)) {
+	return &http.Client{Timeout: timeout}
+}
+
+// FetchData fetches day-ahead 15-minute electricity prices for the given
+// Prague-local date. The returned slice has one entry per quarter-hour;
+// timestamps are in UTC. On DST days the slice has 92 or 100 entries. With
+// AllowPartialDays a shorter series is returned as-is.
+//
+// Network errors and 5xx responses are retried up to MaxRetries times with
+// exponential backoff; if all attempts fail the result is
+// *ExhaustedRetriesError wrapping the last error.
+func FetchData(client *http.Client, date time.Time) ([]storage.Quarter, error) {
+	dayStart := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, prague)
+	if dayStart.Before(time.Date(2025, 10, 1, 0, 0, 0, 0, prague)) {
 		return nil, ErrDateBeforeQuarterHourly
 	}
+
+	return withRetries(dayStart, func() ([]storage.Quarter, error) {
+		return fetchOnce(client, dayStart)
+	})
+}
+
+// FetchDataLines fetches every series of the OTE chart-data response for the
+// Prague-local date, keyed by dataLine title, e.g. "15min price (EUR/MWh)".
+// Values are passed through as published, without the length checks
+// FetchData applies to prices. Retries work as in FetchData.
+func FetchDataLines(client *http.Client, date time.Time) (map[string][]float32, error) {
+	dayStart := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, prague)
+
+	return withRetries(dayStart, func() (map[string][]float32, error) {
+		respJSON, err := fetchResponse(client, dayStart)
+		if err != nil {
+			return nil, err
+		}
+		lines := make(map[string][]float32, len(respJSON.Data.DataLine))
+		for _, line := range respJSON.Data.DataLine {
+			values := make([]float32, len(line.Point))
+			for i, p := range line.Point {
+				values[i] = p.Y
+			}
+			lines[line.Title] = values
+		}
+		return lines, nil
+	})
+}
+
+// withRetries runs fetch, retrying network errors and 5xx responses up to
+// MaxRetries times with exponential backoff.
+func withRetries[T any](dayStart time.Time, fetch func() (T, error)) (T, error) {
+	var zero T
+	backoff := RetryBackoff
+	for attempt := 1; ; attempt++ {
+		v, err := fetch()
+		if err == nil || !retryable(err) {
+			return v, err
+		}
+		if attempt > MaxRetries {
+			if attempt == 1 {
+				return zero, err // retries disabled
+			}
+			return zero, &ExhaustedRetriesError{Attempts: attempt, Last: err}
+		}
+		log.Printf("Attempt %d for %s failed (%v), retrying in %v", attempt, dayStart.Format("2006-01-02"), err, backoff)
+		time.Sleep(backoff)
+		backoff *= 2
+	}
+}
+
+func retryable(err error) bool {
+	var netErr *networkError
+	if errors.As(err, &netErr) {
+		return true
+	}
+	var statusErr *UnexpectedStatusError
+	return errors.As(err, &statusErr) && statusErr.Status >= 500
+}
+
+// fetchOnce performs a single request for the Prague-local day starting at
+// dayStart and extracts the 15-minute price series.
+func fetchOnce(client *http.Client, dayStart time.Time) ([]storage.Quarter, error) {
+	respJSON, err := fetchResponse(client, dayStart)
+	if err != nil {
+		return nil, err
+	}
+	dateStr := dayStart.Format("2006-01-02")
+
+	line, err := findPriceLine(respJSON.Data.DataLine)
+	if err != nil {
+		log.Printf("Price data not found in the response for %s: %v", dateStr, err)
+		return nil, err
+	}
+	want := QuartersInDay(dayStart)
+	if n := len(line.Point); n == 0 || n > want || (n < want && !AllowPartialDays) {
+		log.Printf("Unexpected number of price points for %s: got %d, expected %d", dateStr, n, want)
+		return nil, &InvalidDataSizeError{Got: n, Want: want}
+	}
+	out := make([]storage.Quarter, len(line.Point))
+	for i, p := range line.Point {
+		ts := dayStart.Add(time.Duration(i) * 15 * time.Minute).UTC()
+		out[i] = storage.Quarter{Ts: ts, Price: p.Y}
+	}
+	return out, nil
+}
+
+// fetchResponse performs a single request for the Prague-local day starting
+// at dayStart and decodes the chart-data JSON.
+func fetchResponse(client *http.Client, dayStart time.Time) (response, error) {
 	dateStr := dayStart.Format("2006-01-02")
 	url := fmt.Sprintf("%s?report_date=%s", BaseURL, dateStr)
 	log.Printf("Fetching data for date %s", dateStr)
 
 	start := time.Now()
 
-	client := &http.Client{}
 	req, err := http.NewRequestWithContext(context.Background(), http.MethodGet, url, nil)
 	if err != nil {
-		return nil, fmt.Errorf("Network error: %w", err)
+		return response{}, fmt.Errorf("Network error: %w", err)
 	}
 
 	resp, err := client.Do(req)
 	if err != nil {
 		log.Printf("Request failed %s in %v error %v", dateStr, time.Since(start), err)
-		return nil, fmt.Errorf("Network error: %w", err)
+		return response{}, &networkError{err: err}
 	}
 	defer resp.Body.Close()
 
@@ -85,30 +322,24 @@ func FetchData(date time.Time) ([]storage.Quarter, error) {
 
 	if resp.StatusCode < 200 || resp.StatusCode >= 300 {
 		log.Printf("Failed to fetch data. Status: %d", resp.StatusCode)
-		return nil, &UnexpectedStatusError{Status: resp.StatusCode}
+		return response{}, &UnexpectedStatusError{Status: resp.StatusCode}
 	}
 
 	body, err := io.ReadAll(resp.Body)
 	if err != nil {
-		return nil, fmt.Errorf("Network error: %w", err)
+		return response{}, &networkError{err: err}
 	}
 
-	var respJSON response
-	if err := json.Unmarshal(body, &respJSON); err != nil {
-		return nil, fmt.Errorf("JSON parsing error: %w", err)
+	contentType := resp.Header.Get("Content-Type")
+	if strings.Contains(contentType, "html") || bytes.HasPrefix(bytes.TrimSpace(body), []byte("<")) {
+		snippet := body[:min(len(body), 200)]
+		log.Printf("Non-JSON response for %s (Content-Type %q): %q", dateStr, contentType, snippet)
+		return response{}, &UnexpectedContentTypeError{ContentType: contentType}
 	}
 
-	for _, line := range respJSON.Data.DataLine {
-		if line.Title == "15min price (EUR/MWh)" {
-			out := make([]storage.Quarter, len(line.Point))
-			for i, p := range line.Point {
-				ts := dayStart.Add(time.Duration(i) * 15 * time.Minute).UTC()
-				out[i] = storage.Quarter{Ts: ts, Price: p.Y}
-			}
-			return out, nil
-		}
+	var respJSON response
+	if err := json.Unmarshal(body, &respJSON); err != nil {
+		return response{}, fmt.Errorf("JSON parsing error: %w", err)
 	}
-
-	log.Printf("Price data not found in the response.")
-	return nil, ErrPriceDataNotFound
+	return respJSON, nil
 }
@@ -2,7 +2,9 @@ package dataloader
 
 import (
 	"encoding/json"
+	"errors"
 	"fmt"
+	"net"
 	"net/http"
 	"net/http/httptest"
 	"strings"
@@ -14,23 +16,15 @@ import (
 // returns. The 15-minute price series is the only dataLine consumed; we include
 // a decoy line to confirm the parser picks the right one by title.
 func otePayload(prices []float32, includeTarget bool) []byte {
-	type pt struct {
-		Y float32 `json:"y"`
-	}
 	type line struct {
 		Title string `json:"title"`
 		Point []pt   `json:"point"`
 	}
-	points := make([]pt, len(prices))
-	for i, p := range prices {
-		points[i] = pt{Y: p}
-	}
-
 	lines := []line{
 		{Title: "Hourly average (EUR/MWh)", Point: []pt{{Y: 1}, {Y: 2}}}, // decoy
 	}
 	if includeTarget {
-		lines = append(lines, line{Title: "15min price (EUR/MWh)", Point: points})
+		lines = append(lines, line{Title: "15min price (EUR/MWh)", Point: otePoints(prices)})
 	}
 
 	body, _ := json.Marshal(map[string]any{
@@ -39,6 +33,19 @@ func otePayload(prices []float32, includeTarget bool) []byte {
 	return body
 }
 
+type pt struct {
+	Y float32 `json:"y"`
+}
+
+// otePoints wraps prices as the point list of a dataLine.
+func otePoints(prices []float32) []pt {
+	points := make([]pt, len(prices))
+	for i, p := range prices {
+		points[i] = pt{Y: p}
+	}
+	return points
+}
+
 // startOTEServer returns a httptest.Server whose handler is `handler` and rewires
 // dataloader.BaseURL to point at it. Original BaseURL is restored on cleanup.
 func startOTEServer(t *testing.T, handler http.HandlerFunc) *httptest.Server {
@@ -53,6 +60,9 @@ func startOTEServer(t *testing.T, handler http.HandlerFunc) *httptest.Server {
 	return srv
 }
 
+// testClient is shared like the server shares its client.
+var testClient = NewClient()
+
 func mustPragueLoc(t *testing.T) *time.Location {
 	t.Helper()
 	loc, err := time.LoadLocation("Europe/Prague")
@@ -77,7 +87,7 @@ func TestFetchData_NormalDay96Quarters(t *testing.T) {
 	})
 
 	date := time.Date(2026, 5, 10, 0, 0, 0, 0, loc)
-	got, err := FetchData(date)
+	got, err := FetchData(testClient, date)
 	if err != nil {
 		t.Fatalf("FetchData: %v", err)
 	}
@@ -120,7 +130,7 @@ func TestFetchData_DSTSpringDay_Returns92(t *testing.T) {
 	})
 
 	date := time.Date(2026, 3, 29, 0, 0, 0, 0, loc)
-	got, err := FetchData(date)
+	got, err := FetchData(testClient, date)
 	if err != nil {
 		t.Fatalf("FetchData: %v", err)
 	}
@@ -137,7 +147,7 @@ func TestFetchData_DSTAutumnDay_Returns100(t *testing.T) {
 	})
 
 	date := time.Date(2025, 10, 26, 0, 0, 0, 0, loc)
-	got, err := FetchData(date)
+	got, err := FetchData(testClient, date)
 	if err != nil {
 		t.Fatalf("FetchData: %v", err)
 	}
@@ -150,7 +160,7 @@ func TestFetchData_404ReturnsUnexpectedStatus(t *testing.T) {
 	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
 		http.NotFound(w, &http.Request{})
 	})
-	_, err := FetchData(time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
 	if err == nil {
 		t.Fatal("expected error on 404")
 	}
@@ -167,9 +177,68 @@ func TestFetchData_MissingPriceLineReturnsErrPriceDataNotFound(t *testing.T) {
 	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
 		w.Write(otePayload(nil, false)) // payload without the "15min price" line
 	})
-	_, err := FetchData(time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
-	if err != ErrPriceDataNotFound {
-		t.Errorf("got %v, want ErrPriceDataNotFound", err)
+	_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	if !errors.Is(err, ErrPriceDataNotFound) {
+		t.Fatalf("got %v, want ErrPriceDataNotFound", err)
+	}
+	// The error lists what OTE sent instead, to spot a renamed series.
+	var notFound *PriceLineNotFoundError
+	if !errors.As(err, &notFound) || len(notFound.Titles) != 1 || notFound.Titles[0] != "Hourly average (EUR/MWh)" {
+		t.Errorf("titles: got %#v", err)
+	}
+	if !strings.Contains(err.Error(), `"Hourly average (EUR/MWh)"`) {
+		t.Errorf("message should list the titles: %v", err)
+	}
+}
+
+func TestFetchData_AcceptsPriceLineTitleVariants(t *testing.T) {
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = float32(i)
+	}
+	for _, title := range []string{
+		"15min price (EUR/MWh)",
+		"  15MIN Price  (eur/MWh) ",
+		"15 min price (EUR/MWh)",
+		"15-min price (EUR/MWh)",
+		"Price (EUR/MWh)",
+	} {
+		t.Run(title, func(t *testing.T) {
+			startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+				body, _ := json.Marshal(map[string]any{"data": map[string]any{"dataLine": []map[string]any{
+					{"title": "Volume (MWh)", "point": []map[string]float32{{"y": 1}}},
+					{"title": title, "point": otePoints(prices)},
+				}}})
+				w.Write(body)
+			})
+			got, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+			if err != nil {
+				t.Fatalf("FetchData: %v", err)
+			}
+			if len(got) != 96 || got[95].Price != 95 {
+				t.Errorf("got %d quarters", len(got))
+			}
+		})
+	}
+}
+
+func TestFetchDataLines_ReturnsEveryLineByTitle(t *testing.T) {
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		w.Write(otePayload([]float32{10, 20, 30}, true))
+	})
+	got, err := FetchDataLines(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	if err != nil {
+		t.Fatalf("FetchDataLines: %v", err)
+	}
+	if len(got) != 2 {
+		t.Fatalf("want 2 lines, got %v", got)
+	}
+	// The short price series is passed through, unlike FetchData.
+	if p := got["15min price (EUR/MWh)"]; len(p) != 3 || p[2] != 30 {
+		t.Errorf("price line: got %v", p)
+	}
+	if h := got["Hourly average (EUR/MWh)"]; len(h) != 2 || h[0] != 1 || h[1] != 2 {
+		t.Errorf("decoy line: got %v", h)
 	}
 }
 
@@ -177,7 +246,7 @@ func TestFetchData_InvalidJSONReturnsError(t *testing.T) {
 	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
 		fmt.Fprint(w, "this is not JSON {{{")
 	})
-	_, err := FetchData(time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
 	if err == nil {
 		t.Fatal("expected JSON parse error")
 	}
@@ -195,7 +264,7 @@ func TestFetchData_BeforeCutoffReturnsErrorAndSkipsRequest(t *testing.T) {
 	})
 
 	// 2025-09-30 Prague is the last day before OTE's 15-minute series.
-	_, err := FetchData(time.Date(2025, 9, 30, 0, 0, 0, 0, loc))
+	_, err := FetchData(testClient, time.Date(2025, 9, 30, 0, 0, 0, 0, loc))
 	if err != ErrDateBeforeQuarterHourly {
 		t.Fatalf("got %v, want ErrDateBeforeQuarterHourly", err)
 	}
@@ -211,7 +280,7 @@ func TestFetchData_CutoffDayAllowed(t *testing.T) {
 		w.Write(otePayload(prices, true))
 	})
 	// 2025-10-01 Prague — the first allowed day.
-	_, err := FetchData(time.Date(2025, 10, 1, 0, 0, 0, 0, loc))
+	_, err := FetchData(testClient, time.Date(2025, 10, 1, 0, 0, 0, 0, loc))
 	if err != nil {
 		t.Fatalf("cutoff day must be allowed, got %v", err)
 	}
@@ -225,7 +294,7 @@ func TestFetchData_TimestampsAcrossPragueMidnightInWinter(t *testing.T) {
 	})
 	// January (CET, UTC+1). Prague midnight = 23:00 UTC previous day.
 	date := time.Date(2026, 1, 15, 0, 0, 0, 0, loc)
-	got, err := FetchData(date)
+	got, err := FetchData(testClient, date)
 	if err != nil {
 		t.Fatalf("FetchData: %v", err)
 	}
@@ -234,3 +303,214 @@ func TestFetchData_TimestampsAcrossPragueMidnightInWinter(t *testing.T) {
 		t.Errorf("winter first ts: got %v, want %v", got[0].Ts, want)
 	}
 }
+
+func TestFetchData_ShortSeriesStrictReturnsInvalidDataSize(t *testing.T) {
+	loc := mustPragueLoc(t)
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		w.Write(otePayload(make([]float32, 20), true))
+	})
+	_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, loc))
+	serr, ok := err.(*InvalidDataSizeError)
+	if !ok {
+		t.Fatalf("want *InvalidDataSizeError, got %T: %v", err, err)
+	}
+	if serr.Got != 20 || serr.Want != 96 {
+		t.Errorf("got %+v, want Got=20 Want=96", *serr)
+	}
+}
+
+func TestFetchData_ShortSeriesLenientReturnsAvailablePoints(t *testing.T) {
+	loc := mustPragueLoc(t)
+	AllowPartialDays = true
+	t.Cleanup(func() { AllowPartialDays = false })
+
+	prices := make([]float32, 20)
+	for i := range prices {
+		prices[i] = float32(i)
+	}
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		w.Write(otePayload(prices, true))
+	})
+	got, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, loc))
+	if err != nil {
+		t.Fatalf("FetchData: %v", err)
+	}
+	if len(got) != 20 {
+		t.Fatalf("expected the 20 published quarters, got %d", len(got))
+	}
+	if got[19].Price != 19 {
+		t.Errorf("last price: got %v, want 19", got[19].Price)
+	}
+}
+
+func TestFetchData_LongSeriesRejectedEvenWhenLenient(t *testing.T) {
+	loc := mustPragueLoc(t)
+	AllowPartialDays = true
+	t.Cleanup(func() { AllowPartialDays = false })
+
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		w.Write(otePayload(make([]float32, 100), true))
+	})
+	// 2026-05-10 has 96 quarters; 100 points cannot belong to it.
+	if _, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, loc)); err == nil {
+		t.Fatal("expected oversize series to be rejected")
+	}
+}
+
+func TestFetchData_HTMLErrorPageWith200ReturnsUnexpectedContentType(t *testing.T) {
+	cases := []struct {
+		name        string
+		contentType string
+	}{
+		{"declared html", "text/html; charset=utf-8"},
+		{"mislabelled as json", "application/json"},
+	}
+	for _, c := range cases {
+		t.Run(c.name, func(t *testing.T) {
+			startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+				w.Header().Set("Content-Type", c.contentType)
+				fmt.Fprint(w, "\n<!DOCTYPE html><html><body>Service temporarily unavailable</body></html>")
+			})
+			_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+			cerr, ok := err.(*UnexpectedContentTypeError)
+			if !ok {
+				t.Fatalf("want *UnexpectedContentTypeError, got %T: %v", err, err)
+			}
+			if cerr.ContentType != c.contentType {
+				t.Errorf("ContentType: got %q, want %q", cerr.ContentType, c.contentType)
+			}
+		})
+	}
+}
+
+func TestFetchData_SharedClientReusesConnection(t *testing.T) {
+	var newConns int
+	srv := httptest.NewUnstartedServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		w.Write(otePayload(make([]float32, 96), true))
+	}))
+	srv.Config.ConnState = func(_ net.Conn, state http.ConnState) {
+		if state == http.StateNew {
+			newConns++
+		}
+	}
+	srv.Start()
+	prev := BaseURL
+	BaseURL = srv.URL
+	defer func() {
+		BaseURL = prev
+		srv.Close()
+	}()
+
+	client := NewClient()
+	for _, day := range []int{10, 11, 12} {
+		if _, err := FetchData(client, time.Date(2026, 5, day, 0, 0, 0, 0, time.UTC)); err != nil {
+			t.Fatalf("FetchData(%d): %v", day, err)
+		}
+	}
+	if newConns != 1 {
+		t.Errorf("opened %d connections for 3 sequential fetches, want 1", newConns)
+	}
+}
+
+// fastRetries shortens the backoff for the duration of a test.
+func fastRetries(t *testing.T) {
+	t.Helper()
+	prev := RetryBackoff
+	RetryBackoff = time.Millisecond
+	t.Cleanup(func() { RetryBackoff = prev })
+}
+
+func TestFetchData_RetriesTransient5xx(t *testing.T) {
+	fastRetries(t)
+	var hits int
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		hits++
+		if hits <= 2 {
+			http.Error(w, "busy", http.StatusServiceUnavailable)
+			return
+		}
+		w.Write(otePayload(make([]float32, 96), true))
+	})
+
+	got, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	if err != nil {
+		t.Fatalf("FetchData: %v", err)
+	}
+	if len(got) != 96 || hits != 3 {
+		t.Errorf("got %d quarters after %d requests, want 96 after 3", len(got), hits)
+	}
+}
+
+func TestFetchData_ExhaustedRetriesWrapsLastError(t *testing.T) {
+	fastRetries(t)
+	var hits int
+	startOTEServer(t, func(w http.ResponseWriter, _ *http.Request) {
+		hits++
+		http.Error(w, "down", http.StatusBadGateway)
+	})
+
+	_, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	var exhausted *ExhaustedRetriesError
+	if !errors.As(err, &exhausted) {
+		t.Fatalf("want *ExhaustedRetriesError, got %T: %v", err, err)
+	}
+	if exhausted.Attempts != MaxRetries+1 || hits != MaxRetries+1 {
+		t.Errorf("attempts %d, requests %d, want %d", exhausted.Attempts, hits, MaxRetries+1)
+	}
+	var statusErr *UnexpectedStatusError
+	if !errors.As(err, &statusErr) || statusErr.Status != http.StatusBadGateway {
+		t.Errorf("last error not wrapped: %v", err)
+	}
+}
+
+func TestFetchData_DoesNotRetryClientErrorsOrBadData(t *testing.T) {
+	fastRetries(t)
+	for name, handler := range map[string]http.HandlerFunc{
+		"4xx": func(w http.ResponseWriter, _ *http.Request) { http.Error(w, "gone", http.StatusGone) },
+		"size": func(w http.ResponseWriter, _ *http.Request) {
+			w.Write(otePayload(make([]float32, 7), true))
+		},
+	} {
+		t.Run(name, func(t *testing.T) {
+			var hits int
+			startOTEServer(t, func(w http.ResponseWriter, r *http.Request) {
+				hits++
+				handler(w, r)
+			})
+			if _, err := FetchData(testClient, time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC)); err == nil {
+				t.Fatal("expected an error")
+			}
+			if hits != 1 {
+				t.Errorf("requests: got %d, want 1", hits)
+			}
+		})
+	}
+}
+
+func TestFetchData_TimesOutOnSlowEndpoint(t *testing.T) {
+	prevRetries := MaxRetries
+	MaxRetries = 0
+	defer func() { MaxRetries = prevRetries }()
+	t.Setenv("OTE_HTTP_TIMEOUT_SECS", "0.2")
+
+	startOTEServer(t, func(w http.ResponseWriter, r *http.Request) {
+		select {
+		case <-r.Context().Done():
+		case <-time.After(5 * time.Second):
+		}
+	})
+
+	start := time.Now()
+	_, err := FetchData(NewClient(), time.Date(2026, 5, 10, 0, 0, 0, 0, time.UTC))
+	elapsed := time.Since(start)
+	if err == nil {
+		t.Fatal("expected a timeout error")
+	}
+	var netErr *networkError
+	if !errors.As(err, &netErr) {
+		t.Errorf("want a network error, got %T: %v", err, err)
+	}
+	if elapsed > 2*time.Second {
+		t.Errorf("call took %v, want about 200ms", elapsed)
+	}
+}
@@ -1,12 +1,16 @@
 package main
 
 import (
+	"encoding/csv"
+	"encoding/json"
 	"flag"
 	"fmt"
+	"io"
 	"log"
 	"math"
 	"os"
 	"path/filepath"
+	"strconv"
 	"time"
 
 	"github.com/MichalKalita/ote/dataloader"
@@ -16,10 +20,39 @@ import (
 
 func main() {
 	cli := flag.Bool("cli", false, "Print prices to stdout instead of starting the web server")
-	czk := flag.Bool("czk", false, "Use CZK currency (CLI mode only)")
+	czk := flag.Bool("czk", false, "Use CZK currency (CLI mode only), same as -currency czk")
+	currencyName := flag.String("currency", "", "Currency in CLI mode: eur (EUR/MWh) or czk (CZK/kWh)")
+	format := flag.String("format", "table", "Output format in CLI mode: table, json or csv")
+	date := flag.String("date", "", "Day to print in CLI mode as YYYY-MM-DD (default today in -tz)")
+	withDist := flag.Bool("with-distribution", false, "Also print totals including the distribution tariff in CLI mode")
+	tz := flag.String("tz", "", "Time zone deciding today and how times are shown, e.g. UTC (default $OTE_TZ or Europe/Prague)")
 	flag.Parse()
 
 	log.SetFlags(log.LstdFlags)
+	dataloader.AllowPartialDays = os.Getenv("OTE_ALLOW_PARTIAL") == "true"
+	if v := os.Getenv("OTE_FETCH_RETRIES"); v != "" {
+		n, err := strconv.Atoi(v)
+		if err != nil || n < 0 {
+			log.Fatalf("OTE_FETCH_RETRIES must be a non-negative integer, got %q", v)
+		}
+		dataloader.MaxRetries = n
+	}
+	if v := os.Getenv("OTE_CZK_RATE"); v != "" {
+		rate, err := strconv.ParseFloat(v, 32)
+		if err != nil || !(rate > 0) || math.IsInf(rate, 0) {
+			log.Fatalf("OTE_CZK_RATE must be a positive number, got %q", v)
+		}
+		webserver.CurrencyRate = float32(rate)
+	}
+
+	if *tz == "" {
+		*tz = os.Getenv("OTE_TZ")
+	}
+	if *tz != "" {
+		if err := webserver.SetTimeZone(*tz); err != nil {
+			log.Fatalf("unknown time zone %q: %v", *tz, err)
+		}
+	}
 
 	if !*cli {
 		dbPath := os.Getenv("DB_PATH")
@@ -42,57 +75,170 @@ func main() {
 	if *czk {
 		currency = webserver.CurrencyCzk
 	}
-	printPrices(currency)
+	if *currencyName != "" {
+		c, err := webserver.ParseCurrency(*currencyName)
+		if err != nil {
+			fmt.Printf("Error: %v\n", err)
+			os.Exit(1)
+		}
+		currency = c
+	}
+	day, err := cliDate(*date, webserver.SystemClock{})
+	if err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+	var dist *webserver.Distribution
+	if *withDist {
+		d, err := webserver.DistributionFromEnv(os.Getenv)
+		if err != nil {
+			fmt.Printf("Error: %v\n", err)
+			os.Exit(1)
+		}
+		dist = &d
+	}
+	if err := printPrices(os.Stdout, *format, currency, day, dist); err != nil {
+		fmt.Printf("Error: %v\n", err)
+		os.Exit(1)
+	}
+}
+
+// cliPrice is one quarter in the JSON output.
+type cliPrice struct {
+	Time  time.Time `json:"time"`
+	Price float32   `json:"price"`
+	Total *float32  `json:"total,omitempty"`
+	Min   bool      `json:"min"`
+	Max   bool      `json:"max"`
 }
 
-func printPrices(currency webserver.Currency) {
-	loc, err := time.LoadLocation("Europe/Prague")
+// cliDate parses the -date flag into Prague midnight of that day. An empty
+// value means today as the web UI has it: the calendar date clock shows in
+// webserver.Location (-tz), taken as the Prague market day of that date. Near
+// midnight this is deliberately not Prague's date, e.g. at 23:30 UTC with
+// -tz UTC today is still the UTC date although Prague is past midnight.
+func cliDate(s string, clock webserver.Clock) (time.Time, error) {
+	loc := webserver.PragueLocation()
+	if s == "" {
+		now := clock.Now().In(webserver.Location)
+		return time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, loc), nil
+	}
+	day, err := time.ParseInLocation("2006-01-02", s, loc)
 	if err != nil {
-		loc = time.UTC
+		return time.Time{}, fmt.Errorf("invalid -date %q, expected YYYY-MM-DD", s)
+	}
+	return day, nil
+}
+
+// printPrices writes the prices of day (Prague midnight) to w as a table of
+// hours by quarters, a JSON array or CSV rows. Prices are in currency per MWh.
+// A non-nil dist adds totals including distribution, and min/max are then
+// marked by the totals.
+func printPrices(w io.Writer, format string, currency webserver.Currency, day time.Time, dist *webserver.Distribution) error {
+	if format != "table" && format != "json" && format != "csv" {
+		return fmt.Errorf("unknown format %q, expected table, json or csv", format)
 	}
-	today := time.Now().In(loc)
-	today = time.Date(today.Year(), today.Month(), today.Day(), 0, 0, 0, 0, loc)
+	loc := webserver.PragueLocation()
 
-	quarters, err := dataloader.FetchData(today)
+	quarters, err := dataloader.FetchData(dataloader.NewClient(), day)
 	if err != nil {
-		fmt.Printf("Error: %v\n", err)
-		return
+		return err
+	}
+
+	prices := make([]float32, len(quarters))
+	for i, q := range quarters {
+		prices[i] = q.Price
+	}
+	var totals []float32
+	marked := prices
+	if dist != nil {
+		dp := webserver.DayPrices{Prices: prices}
+		totals = dp.TotalPrices(dist)
+		marked = totals
 	}
 
-	fmt.Println("Prices:")
 	minPrice := float32(math.Inf(1))
 	maxPrice := float32(math.Inf(-1))
-	for _, q := range quarters {
-		if q.Price < minPrice {
-			minPrice = q.Price
+	for _, p := range marked {
+		if p < minPrice {
+			minPrice = p
 		}
-		if q.Price > maxPrice {
-			maxPrice = q.Price
+		if p > maxPrice {
+			maxPrice = p
 		}
 	}
 
-	for hour := 0; hour < 24; hour++ {
-		base := hour * 4
-		if base >= len(quarters) {
-			break
-		}
-		fmt.Printf("%2d:00", hour)
-		for q := 0; q < 4; q++ {
-			idx := base + q
-			if idx >= len(quarters) {
-				break
+	switch format {
+	case "json":
+		out := make([]cliPrice, len(quarters))
+		for i, q := range quarters {
+			out[i] = cliPrice{
+				Time:  q.Ts.In(loc),
+				Price: currency.Convert(q.Price),
+				Min:   marked[i] == minPrice,
+				Max:   marked[i] == maxPrice,
 			}
-			price := quarters[idx].Price
-			dp := currency.Convert(price)
-			marker := "  "
-			switch price {
-			case minPrice:
-				marker = " *"
-			case maxPrice:
-				marker = " **"
+			if totals != nil {
+				total := currency.Convert(totals[i])
+				out[i].Total = &total
 			}
-			fmt.Printf("   %8.4f%s", dp, marker)
 		}
-		fmt.Printf("   %s\n", currency.ShortLabel())
+		enc := json.NewEncoder(w)
+		enc.SetIndent("", "  ")
+		return enc.Encode(out)
+	case "csv":
+		cw := csv.NewWriter(w)
+		header := []string{"time", "price"}
+		if totals != nil {
+			header = append(header, "total")
+		}
+		_ = cw.Write(header)
+		for i, q := range quarters {
+			row := []string{
+				q.Ts.In(loc).Format(time.RFC3339),
+				strconv.FormatFloat(float64(currency.Convert(q.Price)), 'f', 4, 32),
+			}
+			if totals != nil {
+				row = append(row, strconv.FormatFloat(float64(currency.Convert(totals[i])), 'f', 4, 32))
+			}
+			_ = cw.Write(row)
+		}
+		cw.Flush()
+		return cw.Error()
+	}
+
+	if totals != nil {
+		fmt.Fprintln(w, "Prices (market / with distribution):")
+	} else {
+		fmt.Fprintln(w, "Prices:")
+	}
+	// One row per wall-clock hour: the hour DST skips has no row and the
+	// repeated one has two.
+	slots := dataloader.QuartersInDay(day)
+	for idx := range quarters {
+		hour, minute := webserver.SlotClock(idx, slots)
+		if minute == 0 {
+			if idx > 0 {
+				fmt.Fprintf(w, "   %s\n", currency.ShortLabel())
+			}
+			fmt.Fprintf(w, "%2d:00", hour)
+		}
+		marker := "  "
+		switch marked[idx] {
+		case minPrice:
+			marker = " *"
+		case maxPrice:
+			marker = " **"
+		}
+		dp := currency.Convert(quarters[idx].Price)
+		if totals != nil {
+			fmt.Fprintf(w, "   %8.4f / %8.4f%s", dp, currency.Convert(totals[idx]), marker)
+		} else {
+			fmt.Fprintf(w, "   %8.4f%s", dp, marker)
+		}
+	}
+	if len(quarters) > 0 {
+		fmt.Fprintf(w, "   %s\n", currency.ShortLabel())
 	}
+	return nil
 }
@@ -0,0 +1,195 @@
+package main
+
+import (
+	"bytes"
+	"encoding/csv"
+	"encoding/json"
+	"net/http"
+	"net/http/httptest"
+	"strings"
+	"testing"
+	"time"
+
+	"github.com/MichalKalita/ote/dataloader"
+	"github.com/MichalKalita/ote/webserver"
+)
+
+// startOTE serves every quarter of the requested day priced 0, 1, 2, ...
+// EUR/MWh: 96 on a normal day, 92 or 100 on DST days.
+func startOTE(t *testing.T) {
+	t.Helper()
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		type pt struct {
+			Y float32 `json:"y"`
+		}
+		day, err := time.ParseInLocation("2006-01-02", r.URL.Query().Get("report_date"), webserver.PragueLocation())
+		if err != nil {
+			http.Error(w, err.Error(), http.StatusBadRequest)
+			return
+		}
+		points := make([]pt, dataloader.QuartersInDay(day))
+		for i := range points {
+			points[i] = pt{Y: float32(i)}
+		}
+		json.NewEncoder(w).Encode(map[string]any{
+			"data": map[string]any{
+				"dataLine": []map[string]any{
+					{"title": "15min price (EUR/MWh)", "point": points},
+				},
+			},
+		})
+	}))
+	prev := dataloader.BaseURL
+	dataloader.BaseURL = srv.URL
+	t.Cleanup(func() {
+		dataloader.BaseURL = prev
+		srv.Close()
+	})
+}
+
+// testDay is 2026-05-10, the day the fixture tests print.
+func testDay(t *testing.T) time.Time {
+	t.Helper()
+	day, err := cliDate("2026-05-10", webserver.SystemClock{})
+	if err != nil {
+		t.Fatalf("cliDate: %v", err)
+	}
+	return day
+}
+
+func TestCLIDate(t *testing.T) {
+	loc := webserver.PragueLocation()
+	clock := webserver.FixedClock{T: time.Date(2026, 5, 10, 23, 30, 0, 0, loc)}
+
+	today, err := cliDate("", clock)
+	if err != nil || !today.Equal(time.Date(2026, 5, 10, 0, 0, 0, 0, loc)) {
+		t.Errorf("default: got %v, %v", today, err)
+	}
+	day, err := cliDate("2026-03-29", clock)
+	if err != nil || !day.Equal(time.Date(2026, 3, 29, 0, 0, 0, 0, loc)) {
+		t.Errorf("explicit: got %v, %v", day, err)
+	}
+	for _, bad := range []string{"2026-13-01", "10.5.2026", "2026-5-1", "tomorrow"} {
+		if _, err := cliDate(bad, clock); err == nil || !strings.Contains(err.Error(), "YYYY-MM-DD") {
+			t.Errorf("%q: got %v", bad, err)
+		}
+	}
+}
+
+func TestCLIDate_TodayIsTheDateInTZ(t *testing.T) {
+	prev := webserver.Location
+	t.Cleanup(func() { webserver.Location = prev })
+	// 23:30 UTC on May 10 is already 01:30 on May 11 in Prague.
+	clock := webserver.FixedClock{T: time.Date(2026, 5, 10, 23, 30, 0, 0, time.UTC)}
+	for _, c := range []struct {
+		tz   string
+		want string
+	}{{"Europe/Prague", "2026-05-11"}, {"UTC", "2026-05-10"}} {
+		if err := webserver.SetTimeZone(c.tz); err != nil {
+			t.Fatalf("SetTimeZone: %v", err)
+		}
+		day, err := cliDate("", clock)
+		if err != nil || day.Format("2006-01-02") != c.want || day.Location() != webserver.PragueLocation() {
+			t.Errorf("-tz %s: got %v, %v, want Prague midnight of %s", c.tz, day, err, c.want)
+		}
+	}
+}
+
+func TestPrintPrices_JSON(t *testing.T) {
+	startOTE(t)
+	var buf bytes.Buffer
+	if err := printPrices(&buf, "json", webserver.CurrencyEur, testDay(t), nil); err != nil {
+		t.Fatalf("printPrices: %v", err)
+	}
+	var got []cliPrice
+	if err := json.Unmarshal(buf.Bytes(), &got); err != nil {
+		t.Fatalf("output is not JSON: %v\n%s", err, buf.String())
+	}
+	if len(got) != 96 {
+		t.Fatalf("got %d entries, want 96", len(got))
+	}
+	if !got[0].Min || got[0].Max || !got[95].Max || got[40].Min {
+		t.Errorf("min/max flags: first %+v, last %+v", got[0], got[95])
+	}
+	if got[40].Price != 40 || got[40].Time.Format("15:04") != "10:00" {
+		t.Errorf("10:00 entry: got %+v", got[40])
+	}
+}
+
+func TestPrintPrices_CSV(t *testing.T) {
+	startOTE(t)
+	var buf bytes.Buffer
+	if err := printPrices(&buf, "csv", webserver.CurrencyEur, testDay(t), nil); err != nil {
+		t.Fatalf("printPrices: %v", err)
+	}
+	rows, err := csv.NewReader(&buf).ReadAll()
+	if err != nil {
+		t.Fatalf("csv: %v", err)
+	}
+	if len(rows) != 1+96 || strings.Join(rows[0], ",") != "time,price" {
+		t.Fatalf("got %d rows, header %v", len(rows), rows[0])
+	}
+	if got := strings.Join(rows[1+40], ","); got != "2026-05-10T10:00:00+02:00,40.0000" {
+		t.Errorf("10:00 row: got %s", got)
+	}
+}
+
+func TestPrintPrices_RejectsUnknownFormat(t *testing.T) {
+	if err := printPrices(&bytes.Buffer{}, "xml", webserver.CurrencyEur, testDay(t), nil); err == nil {
+		t.Error("expected an error for format xml")
+	}
+}
+
+func TestPrintPrices_WithDistribution(t *testing.T) {
+	startOTE(t)
+	// Hour 0 is high so totals peak at 03:45 and bottom out at 01:00.
+	dist := &webserver.Distribution{HighHours: []byte{0}, HighPrice: 200, LowPrice: 1}
+	var buf bytes.Buffer
+	if err := printPrices(&buf, "json", webserver.CurrencyEur, testDay(t), dist); err != nil {
+		t.Fatalf("printPrices: %v", err)
+	}
+	var got []cliPrice
+	if err := json.Unmarshal(buf.Bytes(), &got); err != nil {
+		t.Fatalf("output is not JSON: %v\n%s", err, buf.String())
+	}
+	if got[3].Total == nil || *got[3].Total != 203 || got[3].Price != 3 {
+		t.Errorf("03:45 entry: got %+v", got[3])
+	}
+	if got[40].Total == nil || *got[40].Total != 41 {
+		t.Errorf("10:00 entry: got %+v", got[40])
+	}
+	if !got[3].Max || got[95].Max || !got[4].Min || got[0].Min {
+		t.Errorf("min/max should follow totals: 03:45 %+v, 23:45 %+v, 01:00 %+v", got[3], got[95], got[4])
+	}
+
+	buf.Reset()
+	if err := printPrices(&buf, "table", webserver.CurrencyEur, testDay(t), dist); err != nil {
+		t.Fatalf("printPrices: %v", err)
+	}
+	if !strings.Contains(buf.String(), " 3.0000 / 203.0000 **") {
+		t.Errorf("table should show market and total:\n%s", buf.String())
+	}
+}
+
+func TestPrintPrices_TableRowsFollowDSTWallClock(t *testing.T) {
+	startOTE(t)
+	day, err := cliDate("2026-10-25", webserver.SystemClock{})
+	if err != nil {
+		t.Fatalf("cliDate: %v", err)
+	}
+	var buf bytes.Buffer
+	if err := printPrices(&buf, "table", webserver.CurrencyEur, day, nil); err != nil {
+		t.Fatalf("printPrices: %v", err)
+	}
+	rows := strings.Split(strings.TrimSpace(buf.String()), "\n")[1:]
+	if len(rows) != 25 {
+		t.Fatalf("got %d hour rows, want 25:\n%s", len(rows), buf.String())
+	}
+	// 02:00 repeats: quarters 8..11 and 12..15 each get their own row.
+	if !strings.HasPrefix(rows[2], " 2:00     8.0000") || !strings.HasPrefix(rows[3], " 2:00    12.0000") {
+		t.Errorf("repeated hour rows: %q, %q", rows[2], rows[3])
+	}
+	if !strings.HasPrefix(rows[24], "23:00    96.0000") || !strings.Contains(rows[24], "99.0000") {
+		t.Errorf("last row: %q", rows[24])
+	}
+}
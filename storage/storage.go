@@ -27,6 +27,10 @@ CREATE TABLE IF NOT EXISTS prices (
   price       REAL    NOT NULL
 );
 CREATE INDEX IF NOT EXISTS idx_prices_prague_date ON prices(prague_date);
+CREATE TABLE IF NOT EXISTS fetched_days (
+  prague_date TEXT    NOT NULL PRIMARY KEY,
+  fetched_at  INTEGER NOT NULL
+);
 `
 
 // Open opens (or creates) the SQLite database at path and ensures the schema.
@@ -92,6 +96,12 @@ func (db *DB) GetDay(pragueDate string) ([]Quarter, error) {
 // SaveQuarters writes all quarters in a single transaction. Existing rows for
 // the same timestamp are replaced.
 func (db *DB) SaveQuarters(quarters []Quarter) error {
+	return db.SaveQuartersAt(quarters, time.Now())
+}
+
+// SaveQuartersAt is SaveQuarters recording fetchedAt as the fetch time of
+// every day the quarters belong to.
+func (db *DB) SaveQuartersAt(quarters []Quarter, fetchedAt time.Time) error {
 	if len(quarters) == 0 {
 		return nil
 	}
@@ -106,8 +116,18 @@ func (db *DB) SaveQuarters(quarters []Quarter) error {
 	}
 	defer stmt.Close()
 
+	days := make(map[string]bool)
 	for _, q := range quarters {
-		if _, err := stmt.Exec(q.Ts.Unix(), db.PragueDate(q.Ts), q.Price); err != nil {
+		date := db.PragueDate(q.Ts)
+		if _, err := stmt.Exec(q.Ts.Unix(), date, q.Price); err != nil {
+			tx.Rollback()
+			return err
+		}
+		days[date] = true
+	}
+	for date := range days {
+		if _, err := tx.Exec(`INSERT OR REPLACE INTO fetched_days(prague_date, fetched_at) VALUES (?, ?)`,
+			date, fetchedAt.Unix()); err != nil {
 			tx.Rollback()
 			return err
 		}
@@ -115,6 +135,27 @@ func (db *DB) SaveQuarters(quarters []Quarter) error {
 	return tx.Commit()
 }
 
+// FetchedAt returns when the day was last saved. Days stored before fetch
+// times were recorded report false.
+func (db *DB) FetchedAt(pragueDate string) (time.Time, bool, error) {
+	var unix int64
+	err := db.sql.QueryRow(`SELECT fetched_at FROM fetched_days WHERE prague_date = ?`, pragueDate).Scan(&unix)
+	if err == sql.ErrNoRows {
+		return time.Time{}, false, nil
+	}
+	if err != nil {
+		return time.Time{}, false, err
+	}
+	return time.Unix(unix, 0).UTC(), true, nil
+}
+
+// CountDays returns how many Prague-local dates have stored prices.
+func (db *DB) CountDays() (int, error) {
+	var n int
+	err := db.sql.QueryRow(`SELECT COUNT(DISTINCT prague_date) FROM prices`).Scan(&n)
+	return n, err
+}
+
 // MonthAverages returns the raw EUR average price for each Prague-local date in
 // the inclusive range. Days with no rows are absent from the map.
 func (db *DB) MonthAverages(pragueDateFrom, pragueDateTo string) (map[string]float32, error) {
@@ -282,3 +282,25 @@ func TestSaveQuarters_Empty(t *testing.T) {
 		t.Errorf("SaveQuarters([]): %v", err)
 	}
 }
+
+func TestFetchedAt_RecordedPerDay(t *testing.T) {
+	db := openTestDB(t)
+	loc := mustLoadPrague(t)
+
+	if _, ok, err := db.FetchedAt("2026-05-10"); err != nil || ok {
+		t.Fatalf("unsaved day: ok=%v err=%v", ok, err)
+	}
+
+	at := time.Date(2026, 5, 9, 14, 5, 0, 0, time.UTC)
+	start := time.Date(2026, 5, 10, 0, 0, 0, 0, loc)
+	if err := db.SaveQuartersAt(makeQuarters(start, 96, func(int) float32 { return 1 }), at); err != nil {
+		t.Fatalf("SaveQuartersAt: %v", err)
+	}
+	got, ok, err := db.FetchedAt("2026-05-10")
+	if err != nil || !ok || !got.Equal(at) {
+		t.Fatalf("FetchedAt: got %v ok=%v err=%v, want %v", got, ok, err, at)
+	}
+	if _, ok, _ := db.FetchedAt("2026-05-11"); ok {
+		t.Error("neighbouring day should have no fetch time")
+	}
+}
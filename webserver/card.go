@@ -0,0 +1,114 @@
+package webserver
+
+import (
+	"bytes"
+	"fmt"
+	"image"
+	"image/color"
+	"image/draw"
+	"image/png"
+	"strings"
+	"time"
+)
+
+// Social card size recommended by OpenGraph and Twitter.
+const (
+	CardWidth  = 1200
+	CardHeight = 630
+)
+
+// cardGlyphs is a 3x5 bitmap font, one row per byte with the leftmost pixel
+// in bit 2. It covers what a card prints: digits, upper-case letters and a
+// little punctuation. Anything else renders as a space.
+var cardGlyphs = map[rune][5]byte{
+	'0': {7, 5, 5, 5, 7}, '1': {2, 6, 2, 2, 7}, '2': {7, 1, 7, 4, 7}, '3': {7, 1, 7, 1, 7},
+	'4': {5, 5, 7, 1, 1}, '5': {7, 4, 7, 1, 7}, '6': {7, 4, 7, 5, 7}, '7': {7, 1, 1, 1, 1},
+	'8': {7, 5, 7, 5, 7}, '9': {7, 5, 7, 1, 7},
+	'A': {2, 5, 7, 5, 5}, 'B': {6, 5, 6, 5, 6}, 'C': {3, 4, 4, 4, 3}, 'D': {6, 5, 5, 5, 6},
+	'E': {7, 4, 6, 4, 7}, 'F': {7, 4, 6, 4, 4}, 'G': {3, 4, 5, 5, 3}, 'H': {5, 5, 7, 5, 5},
+	'I': {7, 2, 2, 2, 7}, 'J': {1, 1, 1, 5, 2}, 'K': {5, 5, 6, 5, 5}, 'L': {4, 4, 4, 4, 7},
+	'M': {5, 7, 7, 5, 5}, 'N': {6, 5, 5, 5, 5}, 'O': {2, 5, 5, 5, 2}, 'P': {6, 5, 6, 4, 4},
+	'Q': {2, 5, 5, 6, 3}, 'R': {6, 5, 6, 5, 5}, 'S': {3, 4, 2, 1, 6}, 'T': {7, 2, 2, 2, 2},
+	'U': {5, 5, 5, 5, 7}, 'V': {5, 5, 5, 5, 2}, 'W': {5, 5, 7, 7, 5}, 'X': {5, 5, 2, 5, 5},
+	'Y': {5, 5, 2, 2, 2}, 'Z': {7, 1, 2, 4, 7},
+	'-': {0, 0, 7, 0, 0}, '.': {0, 0, 0, 0, 2}, ':': {0, 2, 0, 2, 0}, '/': {1, 1, 2, 4, 4},
+}
+
+// drawCardText draws s upper-cased at (x, y) with each font pixel px wide.
+func drawCardText(img draw.Image, x, y, px int, s string, c color.Color) {
+	src := image.NewUniform(c)
+	for _, r := range strings.ToUpper(s) {
+		glyph := cardGlyphs[r]
+		for row, bits := range glyph {
+			for col := 0; col < 3; col++ {
+				if bits&(4>>col) != 0 {
+					rect := image.Rect(x+col*px, y+row*px, x+(col+1)*px, y+(row+1)*px)
+					draw.Draw(img, rect, src, image.Point{}, draw.Src)
+				}
+			}
+		}
+		x += 4 * px
+	}
+}
+
+// drawSparkline plots prices as a thick polyline inside bounds.
+func drawSparkline(img draw.Image, bounds image.Rectangle, prices []float32, thickness int, c color.Color) {
+	_, lo, ok := CheapestHour(prices)
+	if !ok {
+		return
+	}
+	_, hi, _ := ExpensiveHour(prices)
+	span := hi - lo
+	if span == 0 {
+		span = 1
+	}
+	point := func(i int) (int, int) {
+		x := bounds.Min.X
+		if len(prices) > 1 {
+			x += i * (bounds.Dx() - thickness) / (len(prices) - 1)
+		}
+		y := bounds.Max.Y - thickness - int((prices[i]-lo)/span*float32(bounds.Dy()-thickness))
+		return x, y
+	}
+	src := image.NewUniform(c)
+	x0, y0 := point(0)
+	for i := range prices {
+		x1, y1 := point(i)
+		steps := max(abs(x1-x0), abs(y1-y0), 1)
+		for s := 0; s <= steps; s++ {
+			x := x0 + (x1-x0)*s/steps
+			y := y0 + (y1-y0)*s/steps
+			draw.Draw(img, image.Rect(x, y, x+thickness, y+thickness), src, image.Point{}, draw.Src)
+		}
+		x0, y0 = x1, y1
+	}
+}
+
+func abs(n int) int {
+	if n < 0 {
+		return -n
+	}
+	return n
+}
+
+// RenderCardPNG draws the social preview card for a day: title, date, the
+// min and max price in currency and a sparkline of the day's prices.
+func RenderCardPNG(date time.Time, prices []float32, currency Currency) ([]byte, error) {
+	img := image.NewRGBA(image.Rect(0, 0, CardWidth, CardHeight))
+	draw.Draw(img, img.Bounds(), image.White, image.Point{}, draw.Src)
+
+	drawCardText(img, 60, 50, 16, "OTE prices", fillColor("fill-neutral-900"))
+	drawCardText(img, 60, 160, 10, date.Format("2006-01-02"), fillColor("fill-neutral-500"))
+	if _, minPrice, ok := CheapestHour(prices); ok {
+		_, maxPrice, _ := ExpensiveHour(prices)
+		drawCardText(img, 60, 240, 8, fmt.Sprintf("min %.2f %s", currency.Convert(minPrice), currency.ShortLabel()), fillColor("fill-green-600"))
+		drawCardText(img, 60, 300, 8, fmt.Sprintf("max %.2f %s", currency.Convert(maxPrice), currency.ShortLabel()), fillColor("fill-red-600"))
+	}
+	drawSparkline(img, image.Rect(60, 380, CardWidth-60, CardHeight-40), prices, 6, fillColor("fill-blue-600"))
+
+	var buf bytes.Buffer
+	if err := png.Encode(&buf, img); err != nil {
+		return nil, err
+	}
+	return buf.Bytes(), nil
+}
@@ -0,0 +1,110 @@
+package webserver
+
+import (
+	"bytes"
+	"image"
+	"image/color"
+	"image/draw"
+	"image/png"
+	"math"
+	"strconv"
+	"strings"
+	"sync"
+)
+
+// MaxPNGScale caps the ?scale= multiplier for rasterized charts.
+const MaxPNGScale = 4
+
+// MaxCachedPNGs bounds how many rendered past-day charts pngCache keeps.
+const MaxCachedPNGs = 128
+
+// pngCache keeps rendered chart.png and card.png bodies of past days, whose
+// prices never change, by pngCacheKey or cardCacheKey. When full it drops an
+// arbitrary entry.
+type pngCache struct {
+	mu      sync.Mutex
+	entries map[string][]byte
+}
+
+func pngCacheKey(date string, scale int) string {
+	return date + "@" + strconv.Itoa(scale)
+}
+
+// cardCacheKey is the pngCache key of a social card. The prefix keeps it
+// apart from the chart keys.
+func cardCacheKey(date string, currency Currency) string {
+	return "card:" + date + "@" + currency.String()
+}
+
+func (c *pngCache) get(key string) ([]byte, bool) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	body, ok := c.entries[key]
+	return body, ok
+}
+
+func (c *pngCache) put(key string, body []byte) {
+	c.mu.Lock()
+	defer c.mu.Unlock()
+	if c.entries == nil {
+		c.entries = make(map[string][]byte)
+	}
+	if _, ok := c.entries[key]; !ok && len(c.entries) >= MaxCachedPNGs {
+		for k := range c.entries {
+			delete(c.entries, k)
+			break
+		}
+	}
+	c.entries[key] = body
+}
+
+// RenderPNG rasterizes the same bar geometry as Render into a PNG, scaled by
+// scale. Bars are filled with the palette color behind each fill-* class that
+// barColor returns. Text labels are omitted: the standard library has no font
+// rasterizer.
+func (cs ChartSettings) RenderPNG(prices []float32, barColor func(index int, price float32) string, scale int) ([]byte, error) {
+	metrics := cs.calculateMetrics(prices)
+	s := float64(scale)
+
+	width := int(float64(metrics.svgWidth) * s)
+	height := int(math.Ceil(float64(metrics.svgHeight) * s))
+	img := image.NewRGBA(image.Rect(0, 0, width, height))
+	draw.Draw(img, img.Bounds(), image.White, image.Point{}, draw.Src)
+
+	for i, price := range prices {
+		y := cs.calculateBarY(price, metrics)
+		h := cs.calculateBarHeight(price, metrics)
+		rect := image.Rect(
+			int(float64(cs.calculateBarX(i))*s),
+			int(math.Round(float64(y)*s)),
+			int(float64(cs.calculateBarX(i)+cs.BarWidth)*s),
+			int(math.Round(float64(y+h)*s)),
+		)
+		draw.Draw(img, rect, image.NewUniform(fillColor(barColor(i, price))), image.Point{}, draw.Src)
+	}
+
+	var buf bytes.Buffer
+	if err := png.Encode(&buf, img); err != nil {
+		return nil, err
+	}
+	return buf.Bytes(), nil
+}
+
+// fillColor resolves a fill-* utility class to its palette color, falling back
+// to black for classes the CSS generator does not know.
+func fillColor(class string) color.RGBA {
+	black := color.RGBA{A: 0xff}
+	hex, ok := parseColor(strings.TrimPrefix(class, "fill-"))
+	if !ok {
+		return black
+	}
+	hex = strings.TrimPrefix(hex, "#")
+	if len(hex) == 3 {
+		hex = string([]byte{hex[0], hex[0], hex[1], hex[1], hex[2], hex[2]})
+	}
+	v, err := strconv.ParseUint(hex, 16, 32)
+	if err != nil {
+		return black
+	}
+	return color.RGBA{R: uint8(v >> 16), G: uint8(v >> 8), B: uint8(v), A: 0xff}
+}
@@ -0,0 +1,79 @@
+package webserver
+
+import "time"
+
+// Clock supplies the current time. Handlers and AppState read time through it
+// so tests can pin "now", e.g. either side of NextDayPricesHour.
+type Clock interface {
+	Now() time.Time
+}
+
+// SystemClock reads the wall clock.
+type SystemClock struct{}
+
+func (SystemClock) Now() time.Time { return time.Now() }
+
+// FixedClock always returns T.
+type FixedClock struct {
+	T time.Time
+}
+
+func (c FixedClock) Now() time.Time { return c.T }
+
+// DefaultTimeZone is the zone "now" is read in unless overridden.
+const DefaultTimeZone = "Europe/Prague"
+
+// Location is the zone that decides which day is today and how "now" is
+// displayed. main sets it from -tz or OTE_TZ. Prices stay keyed by Prague
+// days, the OTE market day, so the current quarter and the publication hour
+// are Prague ones.
+var Location = PragueLocation()
+
+// SetTimeZone points Location at the IANA zone name, e.g. "UTC".
+func SetTimeZone(name string) error {
+	loc, err := time.LoadLocation(name)
+	if err != nil {
+		return err
+	}
+	Location = loc
+	return nil
+}
+
+// prague is Europe/Prague, loaded once. It is UTC when the zone database is
+// missing.
+var prague = func() *time.Location {
+	loc, err := time.LoadLocation(DefaultTimeZone)
+	if err != nil {
+		return time.UTC
+	}
+	return loc
+}()
+
+// PragueLocation is the zone prices are keyed by. Use it instead of loading
+// Europe/Prague again.
+func PragueLocation() *time.Location {
+	return prague
+}
+
+// localToday reads clock in Location. today is the Prague midnight of now's
+// calendar date, so Location only decides which day is today. slot is the
+// quarter of the real instant counted from that Prague midnight, so it
+// indexes today's prices; it falls outside them when the instant belongs to a
+// neighbouring Prague day, and DST days keep 92 or 100 quarters.
+func localToday(clock Clock) (now, today time.Time, slot int) {
+	now = clock.Now().In(Location)
+	today = time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, PragueLocation())
+	const quarter = 15 * time.Minute
+	elapsed := now.Sub(today)
+	slot = int(elapsed / quarter)
+	if elapsed%quarter < 0 {
+		slot--
+	}
+	return now, today, slot
+}
+
+// nextDayPublished reports whether the day after today is out at now. OTE
+// publishes it at NextDayPricesHour Prague time, whatever Location is.
+func nextDayPublished(now, today time.Time) bool {
+	return !now.Before(time.Date(today.Year(), today.Month(), today.Day(), NextDayPricesHour, 0, 0, 0, PragueLocation()))
+}
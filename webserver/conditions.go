@@ -2,8 +2,11 @@ package webserver
 
 import (
 	"encoding/json"
+	"errors"
 	"fmt"
+	"slices"
 	"sort"
+	"strings"
 	"time"
 
 	json5 "github.com/titanous/json5"
@@ -19,27 +22,174 @@ const (
 	CondPrice
 	CondHours
 	CondCheap
+	CondTariff
+	CondDailyBudget
+	CondFirstCheapHour
+	CondOffPeakCheapest
+	CondComment
+	CondTrend
+	CondCheapQuarters
+	CondPriceAbove
+	CondBelowAverage
+	CondExpensive
+	CondDayOfWeek
+	CondExactlyOne
+	CondCheapestBlock
+	CondPercentile
+	CondTimeRange
+	CondCheapestAhead
+	CondRising
+	CondFalling
+	CondAtLeast
 	CondDebug // test-only
 )
 
 // Condition mirrors the Rust enum.
 type Condition struct {
-	Kind     ConditionKind
-	Children []Condition    // And, Or
-	Inner    *Condition     // Not
-	Price    float32        // Price
-	HoursMin uint32         // Hours
-	HoursMax uint32         // Hours
-	Cheap    CheapCondition // Cheap
-	Debug    bool           // Debug (tests)
+	Kind       ConditionKind
+	Children   []Condition         // And, Or, ExactlyOne, AtLeast
+	AtLeast    uint8               // AtLeast: how many children must hold
+	Inner      *Condition          // Not, Comment
+	Price      float32             // Price: true at or below; PriceAbove: true strictly above
+	HoursMin   uint32              // Hours
+	HoursMax   uint32              // Hours
+	TimeRange  TimeRange           // TimeRange
+	Cheap      CheapCondition      // Cheap, FirstCheapHour, CheapestBlock
+	Expensive  ExpensiveCondition  // Expensive
+	Percentile PercentileCondition // Percentile
+	Ahead      CheapestAhead       // CheapestAhead
+	High       bool                // Tariff: true matches high-tariff hours, false low-tariff
+	Budget     uint8               // DailyBudget, OffPeakCheapest: cheapest hours to use
+	Note       string              // Comment: human label, no effect on evaluation
+	Trend      TrendCondition      // Trend
+	Quarters   CheapQuarters       // CheapQuarters
+	Factor     float32             // BelowAverage: multiplier of the day's mean price
+	Delta      float32             // Rising, Falling: hour-over-hour change to exceed
+	Days       []int               // DayOfWeek: 0 = Monday .. 6 = Sunday
+	Debug      bool                // Debug (tests)
 }
 
+// TrendCondition matches when the price moved by at least By over the last
+// Hours slots in the given direction.
+type TrendCondition struct {
+	Hours  uint8
+	Rising bool
+	By     float32
+}
+
+func trendDirection(rising bool) string {
+	if rising {
+		return "up"
+	}
+	return "down"
+}
+
+// CheapQuarters selects the Count cheapest 15-minute slots in [From, To),
+// both given as quarter-of-day indexes (0..96). It expects a context whose
+// prices are quarter-hourly, 96 slots per day.
+type CheapQuarters struct {
+	Count uint8
+	From  uint8
+	To    uint8
+}
+
+// TimeRange matches wall-clock times in [From, To), both in minutes since
+// midnight. A From after To wraps past midnight.
+type TimeRange struct {
+	From uint16
+	To   uint16
+}
+
+// parseClockTime turns "HH:MM" into minutes since midnight; "24:00" is
+// accepted as the end of the day.
+func parseClockTime(s string) (uint16, error) {
+	var h, m int
+	if _, err := fmt.Sscanf(s, "%d:%d", &h, &m); err != nil || len(s) != 5 {
+		return 0, fmt.Errorf("time must be HH:MM, got %q", s)
+	}
+	if m < 0 || m > 59 || h < 0 || h > 24 || (h == 24 && m != 0) {
+		return 0, fmt.Errorf("time must be between 00:00 and 24:00, got %q", s)
+	}
+	return uint16(h*60 + m), nil
+}
+
+func clockTime(minutes uint16) string {
+	return fmt.Sprintf("%02d:%02d", minutes/60, minutes%60)
+}
+
+// PercentileCondition matches when the current price is at or below the
+// Value-th percentile (0..100) of the prices in hours [From, To).
+type PercentileCondition struct {
+	Value uint8 `json:"value"`
+	From  uint8 `json:"from"`
+	To    uint8 `json:"to"`
+}
+
+// CheapestAhead matches when the current slot is among the Hours cheapest
+// hours of the next Horizon hours, counted from now and crossing midnight
+// into tomorrow's prices when they are known.
+type CheapestAhead struct {
+	Hours   uint8 `json:"hours"`
+	Horizon uint8 `json:"horizon"`
+}
+
+// MaxAheadHours bounds CheapestAhead.Horizon: today's rest and tomorrow is
+// all OTE ever publishes.
+const MaxAheadHours = 48
+
+// parseQuarterTime turns "HH:MM" on a 15-minute boundary into a
+// quarter-of-day index; "24:00" is accepted as the end of the day.
+func parseQuarterTime(s string) (uint8, error) {
+	var h, m int
+	if _, err := fmt.Sscanf(s, "%d:%d", &h, &m); err != nil || len(s) != 5 {
+		return 0, fmt.Errorf("time must be HH:MM, got %q", s)
+	}
+	if m%15 != 0 || m > 45 || h < 0 || h > 24 || (h == 24 && m != 0) {
+		return 0, fmt.Errorf("time must be a quarter hour between 00:00 and 24:00, got %q", s)
+	}
+	return uint8(h*4 + m/15), nil
+}
+
+func quarterTime(q uint8) string {
+	return fmt.Sprintf("%02d:%02d", q/4, q%4*15)
+}
+
+// weekdayNames are indexed like DayOfWeek days, Monday first.
+var weekdayNames = [7]string{"Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"}
+
+// dayNames returns the short names of days, skipping out-of-range entries.
+func dayNames(days []int) []string {
+	names := make([]string, 0, len(days))
+	for _, d := range days {
+		if d >= 0 && d < len(weekdayNames) {
+			names = append(names, weekdayNames[d])
+		}
+	}
+	return names
+}
+
+// mondayFirst turns Go's Sunday-first weekday into 0 = Monday .. 6 = Sunday.
+func mondayFirst(d time.Weekday) int {
+	return (int(d) + 6) % 7
+}
+
+// The fixed overnight window used by OffPeakCheapest. It crosses midnight, so
+// the selection spans two calendar days.
+const (
+	OffPeakFrom uint8 = 22
+	OffPeakTo   uint8 = 6
+)
+
 type CheapCondition struct {
 	Hours uint8 `json:"hours"`
 	From  uint8 `json:"from"`
 	To    uint8 `json:"to"`
 }
 
+// ExpensiveCondition is the mirror of CheapCondition: it selects the Hours
+// most expensive hours in [From, To).
+type ExpensiveCondition CheapCondition
+
 // MarshalJSON encodes a Condition the same way Serde does for the tagged enum.
 func (c Condition) MarshalJSON() ([]byte, error) {
 	switch c.Kind {
@@ -47,14 +197,58 @@ func (c Condition) MarshalJSON() ([]byte, error) {
 		return json.Marshal(map[string]any{"and": c.Children})
 	case CondOr:
 		return json.Marshal(map[string]any{"or": c.Children})
+	case CondExactlyOne:
+		return json.Marshal(map[string]any{"exactly_one": c.Children})
+	case CondAtLeast:
+		return json.Marshal(map[string]any{"at_least": map[string]any{"k": c.AtLeast, "of": c.Children}})
 	case CondNot:
 		return json.Marshal(map[string]any{"not": c.Inner})
 	case CondPrice:
 		return json.Marshal(map[string]any{"price": c.Price})
+	case CondPriceAbove:
+		return json.Marshal(map[string]any{"price_above": c.Price})
+	case CondBelowAverage:
+		return json.Marshal(map[string]any{"below_average": c.Factor})
 	case CondHours:
 		return json.Marshal(map[string]any{"hours": [2]uint32{c.HoursMin, c.HoursMax}})
+	case CondTimeRange:
+		return json.Marshal(map[string]any{"time_range": map[string]string{
+			"from": clockTime(c.TimeRange.From), "to": clockTime(c.TimeRange.To),
+		}})
 	case CondCheap:
 		return json.Marshal(map[string]any{"cheap": c.Cheap})
+	case CondTariff:
+		return json.Marshal(map[string]any{"tariff": tariffName(c.High)})
+	case CondDailyBudget:
+		return json.Marshal(map[string]any{"daily_budget": map[string]uint8{"hours": c.Budget}})
+	case CondFirstCheapHour:
+		return json.Marshal(map[string]any{"first_cheap_hour": c.Cheap})
+	case CondCheapestBlock:
+		return json.Marshal(map[string]any{"cheapest_block": c.Cheap})
+	case CondExpensive:
+		return json.Marshal(map[string]any{"expensive": c.Expensive})
+	case CondPercentile:
+		return json.Marshal(map[string]any{"percentile": c.Percentile})
+	case CondCheapestAhead:
+		return json.Marshal(map[string]any{"cheapest_ahead": c.Ahead})
+	case CondRising:
+		return json.Marshal(map[string]any{"rising": c.Delta})
+	case CondFalling:
+		return json.Marshal(map[string]any{"falling": c.Delta})
+	case CondDayOfWeek:
+		return json.Marshal(map[string]any{"day_of_week": c.Days})
+	case CondOffPeakCheapest:
+		return json.Marshal(map[string]any{"off_peak_cheapest": map[string]uint8{"hours": c.Budget}})
+	case CondComment:
+		return json.Marshal(map[string]any{"comment": map[string]any{"note": c.Note, "inner": c.Inner}})
+	case CondCheapQuarters:
+		return json.Marshal(map[string]any{"cheap_quarters": map[string]any{
+			"count": c.Quarters.Count, "from": quarterTime(c.Quarters.From), "to": quarterTime(c.Quarters.To),
+		}})
+	case CondTrend:
+		return json.Marshal(map[string]any{"trend": map[string]any{
+			"hours": c.Trend.Hours, "direction": trendDirection(c.Trend.Rising), "by": c.Trend.By,
+		}})
 	case CondDebug:
 		return json.Marshal(map[string]any{"debug": c.Debug})
 	}
@@ -86,6 +280,24 @@ func (c *Condition) UnmarshalJSON(data []byte) error {
 			}
 			c.Kind = CondOr
 			c.Children = arr
+		case "exactly_one":
+			var arr []Condition
+			if err := json.Unmarshal(val, &arr); err != nil {
+				return err
+			}
+			c.Kind = CondExactlyOne
+			c.Children = arr
+		case "at_least":
+			var v struct {
+				K  uint8       `json:"k"`
+				Of []Condition `json:"of"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondAtLeast
+			c.AtLeast = v.K
+			c.Children = v.Of
 		case "not":
 			var inner Condition
 			if err := json.Unmarshal(val, &inner); err != nil {
@@ -100,6 +312,20 @@ func (c *Condition) UnmarshalJSON(data []byte) error {
 			}
 			c.Kind = CondPrice
 			c.Price = v
+		case "price_above":
+			var v float32
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondPriceAbove
+			c.Price = v
+		case "below_average":
+			var v float32
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondBelowAverage
+			c.Factor = v
 		case "hours":
 			var arr [2]uint32
 			if err := json.Unmarshal(val, &arr); err != nil {
@@ -108,6 +334,24 @@ func (c *Condition) UnmarshalJSON(data []byte) error {
 			c.Kind = CondHours
 			c.HoursMin = arr[0]
 			c.HoursMax = arr[1]
+		case "time_range":
+			var v struct {
+				From string `json:"from"`
+				To   string `json:"to"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			from, err := parseClockTime(v.From)
+			if err != nil {
+				return fmt.Errorf("time_range from: %w", err)
+			}
+			to, err := parseClockTime(v.To)
+			if err != nil {
+				return fmt.Errorf("time_range to: %w", err)
+			}
+			c.Kind = CondTimeRange
+			c.TimeRange = TimeRange{From: from, To: to}
 		case "cheap":
 			var cc CheapCondition
 			if err := json.Unmarshal(val, &cc); err != nil {
@@ -115,6 +359,140 @@ func (c *Condition) UnmarshalJSON(data []byte) error {
 			}
 			c.Kind = CondCheap
 			c.Cheap = cc
+		case "tariff":
+			var v string
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			switch v {
+			case "high":
+				c.High = true
+			case "low":
+				c.High = false
+			default:
+				return fmt.Errorf("tariff must be \"high\" or \"low\", got %q", v)
+			}
+			c.Kind = CondTariff
+		case "daily_budget":
+			var v struct {
+				Hours uint8 `json:"hours"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondDailyBudget
+			c.Budget = v.Hours
+		case "first_cheap_hour":
+			var cc CheapCondition
+			if err := json.Unmarshal(val, &cc); err != nil {
+				return err
+			}
+			c.Kind = CondFirstCheapHour
+			c.Cheap = cc
+		case "cheapest_block":
+			var cc CheapCondition
+			if err := json.Unmarshal(val, &cc); err != nil {
+				return err
+			}
+			c.Kind = CondCheapestBlock
+			c.Cheap = cc
+		case "expensive":
+			var ec ExpensiveCondition
+			if err := json.Unmarshal(val, &ec); err != nil {
+				return err
+			}
+			c.Kind = CondExpensive
+			c.Expensive = ec
+		case "percentile":
+			var pc PercentileCondition
+			if err := json.Unmarshal(val, &pc); err != nil {
+				return err
+			}
+			c.Kind = CondPercentile
+			c.Percentile = pc
+		case "cheapest_ahead":
+			var ca CheapestAhead
+			if err := json.Unmarshal(val, &ca); err != nil {
+				return err
+			}
+			c.Kind = CondCheapestAhead
+			c.Ahead = ca
+		case "rising", "falling":
+			var v float32
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondRising
+			if key == "falling" {
+				c.Kind = CondFalling
+			}
+			c.Delta = v
+		case "day_of_week":
+			var days []int
+			if err := json.Unmarshal(val, &days); err != nil {
+				return err
+			}
+			c.Kind = CondDayOfWeek
+			c.Days = days
+		case "off_peak_cheapest":
+			var v struct {
+				Hours uint8 `json:"hours"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondOffPeakCheapest
+			c.Budget = v.Hours
+		case "comment":
+			var v struct {
+				Note  string     `json:"note"`
+				Inner *Condition `json:"inner"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			c.Kind = CondComment
+			c.Note = v.Note
+			c.Inner = v.Inner
+		case "cheap_quarters":
+			var v struct {
+				Count uint8  `json:"count"`
+				From  string `json:"from"`
+				To    string `json:"to"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			from, err := parseQuarterTime(v.From)
+			if err != nil {
+				return fmt.Errorf("cheap_quarters from: %w", err)
+			}
+			to, err := parseQuarterTime(v.To)
+			if err != nil {
+				return fmt.Errorf("cheap_quarters to: %w", err)
+			}
+			c.Kind = CondCheapQuarters
+			c.Quarters = CheapQuarters{Count: v.Count, From: from, To: to}
+		case "trend":
+			var v struct {
+				Hours     uint8   `json:"hours"`
+				Direction string  `json:"direction"`
+				By        float32 `json:"by"`
+			}
+			if err := json.Unmarshal(val, &v); err != nil {
+				return err
+			}
+			switch v.Direction {
+			case "up":
+				c.Trend.Rising = true
+			case "down":
+				c.Trend.Rising = false
+			default:
+				return fmt.Errorf("trend direction must be \"up\" or \"down\", got %q", v.Direction)
+			}
+			c.Kind = CondTrend
+			c.Trend.Hours = v.Hours
+			c.Trend.By = v.By
 		case "debug":
 			var v bool
 			if err := json.Unmarshal(val, &v); err != nil {
@@ -129,6 +507,11 @@ func (c *Condition) UnmarshalJSON(data []byte) error {
 	return nil
 }
 
+// MaxConditionDepth bounds how deeply And/Or/Not may be nested in a parsed
+// expression. Evaluate and RenderHTML recurse on the tree, so an unbounded
+// user-supplied expression could exhaust the stack of a request handler.
+const MaxConditionDepth = 64
+
 // ParseCondition parses a JSON5 array of conditions and wraps it in And.
 func ParseCondition(s string) (Condition, error) {
 	// json5 -> normal json first, then unmarshal
@@ -136,6 +519,9 @@ func ParseCondition(s string) (Condition, error) {
 	if err := json5.Unmarshal([]byte(s), &raw); err != nil {
 		return Condition{}, err
 	}
+	if jsonDepthExceeds(raw, MaxConditionDepth) {
+		return Condition{}, fmt.Errorf("expression is nested too deeply (max depth %d)", MaxConditionDepth)
+	}
 	normal, err := json.Marshal(raw)
 	if err != nil {
 		return Condition{}, err
@@ -147,6 +533,199 @@ func ParseCondition(s string) (Condition, error) {
 	return Condition{Kind: CondAnd, Children: items}, nil
 }
 
+// ExpParam is the inverse of ParseCondition: the canonical JSON array ?exp=
+// takes. A condition that is not ParseCondition's top-level And is encoded
+// as a one-element array.
+func (c Condition) ExpParam() (string, error) {
+	items := []Condition{c}
+	if c.Kind == CondAnd {
+		items = c.Children
+	}
+	if items == nil {
+		items = []Condition{}
+	}
+	b, err := json.Marshal(items)
+	if err != nil {
+		return "", err
+	}
+	return string(b), nil
+}
+
+// ParseErrorPosition returns the byte offset of a JSON5 syntax error returned
+// by ParseCondition, if the error carries one.
+func ParseErrorPosition(err error) (int64, bool) {
+	var synErr *json5.SyntaxError
+	if errors.As(err, &synErr) {
+		return synErr.Offset, true
+	}
+	return 0, false
+}
+
+// Validate recursively checks field bounds that the JSON shape alone cannot
+// express. It does not need prices, so it is cheap enough to run on every
+// keystroke.
+func (c Condition) Validate() error {
+	switch c.Kind {
+	case CondAtLeast:
+		if int(c.AtLeast) > len(c.Children) {
+			return fmt.Errorf("at_least: k must be at most the %d conditions given, got %d", len(c.Children), c.AtLeast)
+		}
+		for _, child := range c.Children {
+			if err := child.Validate(); err != nil {
+				return err
+			}
+		}
+	case CondAnd, CondOr, CondExactlyOne:
+		for _, child := range c.Children {
+			if err := child.Validate(); err != nil {
+				return err
+			}
+		}
+	case CondNot:
+		if c.Inner == nil {
+			return fmt.Errorf("not: missing inner condition")
+		}
+		return c.Inner.Validate()
+	case CondComment:
+		if c.Inner == nil {
+			return fmt.Errorf("comment %q: missing inner condition", c.Note)
+		}
+		return c.Inner.Validate()
+	case CondHours:
+		if c.HoursMax > 23 {
+			return fmt.Errorf("hours: end must be between 0 and 23, got %d", c.HoursMax)
+		}
+		if c.HoursMin > c.HoursMax {
+			return fmt.Errorf("hours: start %d is after end %d", c.HoursMin, c.HoursMax)
+		}
+	case CondTimeRange:
+		if c.TimeRange.From >= 24*60 {
+			return fmt.Errorf("time_range: from must be before 24:00, got %s", clockTime(c.TimeRange.From))
+		}
+		if c.TimeRange.To > 24*60 {
+			return fmt.Errorf("time_range: to must be at most 24:00, got %s", clockTime(c.TimeRange.To))
+		}
+		if c.TimeRange.From == c.TimeRange.To {
+			return fmt.Errorf("time_range: from and to must differ, got %s", clockTime(c.TimeRange.From))
+		}
+	case CondCheap, CondFirstCheapHour:
+		return c.Cheap.Validate()
+	case CondExpensive:
+		return CheapCondition(c.Expensive).validate("expensive")
+	case CondCheapestBlock:
+		return c.Cheap.validate("cheapest_block")
+	case CondPercentile:
+		if c.Percentile.Value > 100 {
+			return fmt.Errorf("percentile: value must be between 0 and 100, got %d", c.Percentile.Value)
+		}
+		if c.Percentile.From > 23 {
+			return fmt.Errorf("percentile: from must be between 0 and 23, got %d", c.Percentile.From)
+		}
+		if c.Percentile.To > 24 {
+			return fmt.Errorf("percentile: to must be between 0 and 24, got %d", c.Percentile.To)
+		}
+	case CondCheapestAhead:
+		if c.Ahead.Horizon == 0 || c.Ahead.Horizon > MaxAheadHours {
+			return fmt.Errorf("cheapest_ahead: horizon must be between 1 and %d, got %d", MaxAheadHours, c.Ahead.Horizon)
+		}
+		if c.Ahead.Hours == 0 || c.Ahead.Hours > c.Ahead.Horizon {
+			return fmt.Errorf("cheapest_ahead: hours must be between 1 and the horizon %d, got %d", c.Ahead.Horizon, c.Ahead.Hours)
+		}
+	case CondRising, CondFalling:
+		if c.Delta < 0 {
+			return fmt.Errorf("%s: threshold must not be negative, got %g", changeName(c.Kind), c.Delta)
+		}
+	case CondDayOfWeek:
+		if len(c.Days) == 0 {
+			return fmt.Errorf("day_of_week: at least one day is required")
+		}
+		for _, d := range c.Days {
+			if d < 0 || d > 6 {
+				return fmt.Errorf("day_of_week: days must be between 0 (Monday) and 6 (Sunday), got %d", d)
+			}
+		}
+	case CondBelowAverage:
+		if c.Factor <= 0 {
+			return fmt.Errorf("below_average: factor must be positive, got %g", c.Factor)
+		}
+	case CondDailyBudget:
+		if c.Budget == 0 || c.Budget > 24 {
+			return fmt.Errorf("daily_budget: hours must be between 1 and 24, got %d", c.Budget)
+		}
+	case CondCheapQuarters:
+		if c.Quarters.Count == 0 || c.Quarters.Count > 96 {
+			return fmt.Errorf("cheap_quarters: count must be between 1 and 96, got %d", c.Quarters.Count)
+		}
+		if c.Quarters.From > 95 {
+			return fmt.Errorf("cheap_quarters: from must be before 24:00, got %s", quarterTime(c.Quarters.From))
+		}
+	case CondTrend:
+		if c.Trend.Hours == 0 || c.Trend.Hours > 24 {
+			return fmt.Errorf("trend: hours must be between 1 and 24, got %d", c.Trend.Hours)
+		}
+		if c.Trend.By < 0 {
+			return fmt.Errorf("trend: by must not be negative, got %g", c.Trend.By)
+		}
+	case CondOffPeakCheapest:
+		if window := 24 - OffPeakFrom + OffPeakTo; c.Budget == 0 || c.Budget > window {
+			return fmt.Errorf("off_peak_cheapest: hours must be between 1 and %d, got %d", window, c.Budget)
+		}
+	}
+	return nil
+}
+
+// Validate checks that the window and count describe a satisfiable selection.
+func (cc CheapCondition) Validate() error {
+	return cc.validate("cheap")
+}
+
+// validate is Validate with errors prefixed by name, so conditions sharing
+// the window shape report under their own key.
+func (cc CheapCondition) validate(name string) error {
+	if cc.Hours == 0 || cc.Hours > 24 {
+		return fmt.Errorf("%s: hours must be between 1 and 24, got %d", name, cc.Hours)
+	}
+	if cc.From > 23 {
+		return fmt.Errorf("%s: from must be between 0 and 23, got %d", name, cc.From)
+	}
+	if cc.To > 24 {
+		return fmt.Errorf("%s: to must be between 0 and 24, got %d", name, cc.To)
+	}
+	return nil
+}
+
+// jsonDepthExceeds reports whether the decoded JSON value nests objects and
+// arrays deeper than limit. It walks with an explicit stack so the check
+// itself cannot overflow on hostile input.
+func jsonDepthExceeds(v any, limit int) bool {
+	type frame struct {
+		v     any
+		depth int
+	}
+	stack := []frame{{v, 0}}
+	for len(stack) > 0 {
+		f := stack[len(stack)-1]
+		stack = stack[:len(stack)-1]
+		switch x := f.v.(type) {
+		case map[string]any:
+			if f.depth+1 > limit {
+				return true
+			}
+			for _, child := range x {
+				stack = append(stack, frame{child, f.depth + 1})
+			}
+		case []any:
+			if f.depth+1 > limit {
+				return true
+			}
+			for _, child := range x {
+				stack = append(stack, frame{child, f.depth + 1})
+			}
+		}
+	}
+	return false
+}
+
 // Format formats a Condition similarly to Rust's Debug.
 func (c Condition) Format() string {
 	switch c.Kind {
@@ -168,21 +747,226 @@ func (c Condition) Format() string {
 			s += child.Format()
 		}
 		return s + "])"
+	case CondExactlyOne:
+		s := "ExactlyOne(["
+		for i, child := range c.Children {
+			if i > 0 {
+				s += ", "
+			}
+			s += child.Format()
+		}
+		return s + "])"
+	case CondAtLeast:
+		s := fmt.Sprintf("AtLeast { k: %d, of: [", c.AtLeast)
+		for i, child := range c.Children {
+			if i > 0 {
+				s += ", "
+			}
+			s += child.Format()
+		}
+		return s + "] }"
 	case CondNot:
 		return "Not(" + c.Inner.Format() + ")"
 	case CondPrice:
 		return fmt.Sprintf("Price(%g)", c.Price)
+	case CondPriceAbove:
+		return fmt.Sprintf("PriceAbove(%g)", c.Price)
+	case CondBelowAverage:
+		return fmt.Sprintf("BelowAverage(%g)", c.Factor)
 	case CondHours:
 		return fmt.Sprintf("Hours(%d, %d)", c.HoursMin, c.HoursMax)
+	case CondTimeRange:
+		return fmt.Sprintf("TimeRange { from: %s, to: %s }", clockTime(c.TimeRange.From), clockTime(c.TimeRange.To))
 	case CondCheap:
 		return fmt.Sprintf("Cheap(CheapCondition { hours: %d, from: %d, to: %d })",
 			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondTariff:
+		return fmt.Sprintf("Tariff(%s)", tariffName(c.High))
+	case CondDailyBudget:
+		return fmt.Sprintf("DailyBudget { hours: %d }", c.Budget)
+	case CondFirstCheapHour:
+		return fmt.Sprintf("FirstCheapHour { hours: %d, from: %d, to: %d }",
+			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondExpensive:
+		return fmt.Sprintf("Expensive(ExpensiveCondition { hours: %d, from: %d, to: %d })",
+			c.Expensive.Hours, c.Expensive.From, c.Expensive.To)
+	case CondCheapestBlock:
+		return fmt.Sprintf("CheapestBlock { hours: %d, from: %d, to: %d }",
+			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondPercentile:
+		return fmt.Sprintf("Percentile { value: %d, from: %d, to: %d }",
+			c.Percentile.Value, c.Percentile.From, c.Percentile.To)
+	case CondCheapestAhead:
+		return fmt.Sprintf("CheapestAhead { hours: %d, horizon: %d }", c.Ahead.Hours, c.Ahead.Horizon)
+	case CondRising:
+		return fmt.Sprintf("Rising(%g)", c.Delta)
+	case CondFalling:
+		return fmt.Sprintf("Falling(%g)", c.Delta)
+	case CondDayOfWeek:
+		return fmt.Sprintf("DayOfWeek(%v)", c.Days)
+	case CondOffPeakCheapest:
+		return fmt.Sprintf("OffPeakCheapest { hours: %d }", c.Budget)
+	case CondCheapQuarters:
+		return fmt.Sprintf("CheapQuarters { count: %d, from: %s, to: %s }",
+			c.Quarters.Count, quarterTime(c.Quarters.From), quarterTime(c.Quarters.To))
+	case CondTrend:
+		dir := "Down"
+		if c.Trend.Rising {
+			dir = "Up"
+		}
+		return fmt.Sprintf("Trend { hours: %d, direction: %s, by: %g }", c.Trend.Hours, dir, c.Trend.By)
+	case CondComment:
+		return fmt.Sprintf("Comment { note: %q, inner: %s }", c.Note, c.Inner.Format())
 	case CondDebug:
 		return fmt.Sprintf("Debug(%v)", c.Debug)
 	}
 	return ""
 }
 
+// Expression renders the condition as a compact, readable expression such as
+// AND(price<=120, hours[0..10]) for display. JSON stays the wire format; this
+// form is not parsed back.
+func (c Condition) Expression() string {
+	list := func(name string) string {
+		parts := make([]string, len(c.Children))
+		for i, child := range c.Children {
+			parts[i] = child.Expression()
+		}
+		return name + "(" + strings.Join(parts, ", ") + ")"
+	}
+	switch c.Kind {
+	case CondAnd:
+		return list("AND")
+	case CondOr:
+		return list("OR")
+	case CondExactlyOne:
+		return list("EXACTLY_ONE")
+	case CondAtLeast:
+		return list(fmt.Sprintf("AT_LEAST_%d", c.AtLeast))
+	case CondNot:
+		return "NOT(" + c.Inner.Expression() + ")"
+	case CondPrice:
+		return fmt.Sprintf("price<=%g", c.Price)
+	case CondPriceAbove:
+		return fmt.Sprintf("price>%g", c.Price)
+	case CondBelowAverage:
+		return fmt.Sprintf("price<%g*avg", c.Factor)
+	case CondHours:
+		return fmt.Sprintf("hours[%d..%d]", c.HoursMin, c.HoursMax)
+	case CondTimeRange:
+		return fmt.Sprintf("time[%s..%s)", clockTime(c.TimeRange.From), clockTime(c.TimeRange.To))
+	case CondCheap:
+		return fmt.Sprintf("cheap(%dh in %d..%d)", c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondFirstCheapHour:
+		return fmt.Sprintf("first_cheap(%dh in %d..%d)", c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondExpensive:
+		return fmt.Sprintf("expensive(%dh in %d..%d)", c.Expensive.Hours, c.Expensive.From, c.Expensive.To)
+	case CondCheapestBlock:
+		return fmt.Sprintf("cheapest_block(%dh in %d..%d)", c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondPercentile:
+		return fmt.Sprintf("percentile(p%d in %d..%d)", c.Percentile.Value, c.Percentile.From, c.Percentile.To)
+	case CondCheapestAhead:
+		return fmt.Sprintf("cheapest_ahead(%dh in next %dh)", c.Ahead.Hours, c.Ahead.Horizon)
+	case CondRising, CondFalling:
+		return fmt.Sprintf("%s(>%g/h)", changeName(c.Kind), c.Delta)
+	case CondTariff:
+		return "tariff=" + tariffName(c.High)
+	case CondDailyBudget:
+		return fmt.Sprintf("daily_budget(%dh)", c.Budget)
+	case CondOffPeakCheapest:
+		return fmt.Sprintf("off_peak_cheapest(%dh)", c.Budget)
+	case CondCheapQuarters:
+		return fmt.Sprintf("cheap_quarters(%d in %s..%s)",
+			c.Quarters.Count, quarterTime(c.Quarters.From), quarterTime(c.Quarters.To))
+	case CondTrend:
+		return fmt.Sprintf("trend(%s %g in %dh)", trendDirection(c.Trend.Rising), c.Trend.By, c.Trend.Hours)
+	case CondDayOfWeek:
+		return "day_of_week(" + strings.Join(dayNames(c.Days), ", ") + ")"
+	case CondComment:
+		return fmt.Sprintf("comment(%q, %s)", c.Note, c.Inner.Expression())
+	case CondDebug:
+		return fmt.Sprintf("debug(%v)", c.Debug)
+	}
+	return ""
+}
+
+// Equal reports whether two conditions are structurally identical.
+func (c Condition) Equal(other Condition) bool {
+	return c.Format() == other.Format()
+}
+
+// Simplify returns an equivalent, tidier condition: nested And-in-And and
+// Or-in-Or are flattened, identical siblings are removed and double negation
+// is dropped. With deMorgan, Not is also pushed inward through And/Or, which
+// evaluates the same but can read worse, so it is opt-in.
+//
+// Empty And/Or evaluate to false, so they are never flattened into a parent
+// or rewritten by De Morgan.
+func (c Condition) Simplify(deMorgan bool) Condition {
+	switch c.Kind {
+	case CondAnd, CondOr:
+		var children []Condition
+		for _, child := range c.Children {
+			child = child.Simplify(deMorgan)
+			if child.Kind == c.Kind && len(child.Children) > 0 {
+				children = append(children, child.Children...)
+			} else {
+				children = append(children, child)
+			}
+		}
+		out := Condition{Kind: c.Kind}
+		for _, child := range children {
+			dup := false
+			for _, seen := range out.Children {
+				if seen.Equal(child) {
+					dup = true
+					break
+				}
+			}
+			if !dup {
+				out.Children = append(out.Children, child)
+			}
+		}
+		return out
+	case CondNot:
+		inner := c.Inner.Simplify(deMorgan)
+		if inner.Kind == CondNot {
+			return *inner.Inner
+		}
+		if deMorgan && (inner.Kind == CondAnd || inner.Kind == CondOr) && len(inner.Children) > 0 {
+			flipped := Condition{Kind: CondOr}
+			if inner.Kind == CondOr {
+				flipped.Kind = CondAnd
+			}
+			for _, child := range inner.Children {
+				child := child
+				flipped.Children = append(flipped.Children, Condition{Kind: CondNot, Inner: &child})
+			}
+			return flipped.Simplify(deMorgan)
+		}
+		return Condition{Kind: CondNot, Inner: &inner}
+	case CondComment:
+		inner := c.Inner.Simplify(deMorgan)
+		return Condition{Kind: CondComment, Note: c.Note, Inner: &inner}
+	case CondExactlyOne:
+		// Duplicates matter here (exactly one of A, A never holds), so only
+		// the children themselves are simplified.
+		out := Condition{Kind: CondExactlyOne}
+		for _, child := range c.Children {
+			out.Children = append(out.Children, child.Simplify(deMorgan))
+		}
+		return out
+	case CondAtLeast:
+		// As with ExactlyOne, duplicate children each count.
+		out := Condition{Kind: CondAtLeast, AtLeast: c.AtLeast}
+		for _, child := range c.Children {
+			out.Children = append(out.Children, child.Simplify(deMorgan))
+		}
+		return out
+	}
+	return c
+}
+
 // Evaluate evaluates the condition against the given context.
 func (c Condition) Evaluate(ctx *EvaluateContext) bool {
 	switch c.Kind {
@@ -206,64 +990,445 @@ func (c Condition) Evaluate(ctx *EvaluateContext) bool {
 			}
 		}
 		return false
+	case CondExactlyOne:
+		// Exactly one child must hold, not an odd number of them. Like And
+		// and Or, no children evaluates to false.
+		matched := 0
+		for _, child := range c.Children {
+			if child.Evaluate(ctx) {
+				matched++
+				if matched > 1 {
+					return false
+				}
+			}
+		}
+		return matched == 1
+	case CondAtLeast:
+		// k = 0 always holds, even with no children; k above the number of
+		// children never does (Validate rejects it).
+		matched := 0
+		for _, child := range c.Children {
+			if matched >= int(c.AtLeast) {
+				break
+			}
+			if child.Evaluate(ctx) {
+				matched++
+			}
+		}
+		return matched >= int(c.AtLeast)
 	case CondNot:
 		return !c.Inner.Evaluate(ctx)
 	case CondPrice:
 		return ctx.Prices.Prices[ctx.Prices.NowIndex] <= c.Price
+	case CondPriceAbove:
+		return ctx.Prices.Prices[ctx.Prices.NowIndex] > c.Price
+	case CondBelowAverage:
+		// Only the current calendar day counts, even when the context also
+		// holds yesterday and tomorrow.
+		day, ok := ctx.Slice(0, 24)
+		if !ok || len(day) == 0 {
+			return false
+		}
+		var sum float32
+		for _, p := range day {
+			sum += p
+		}
+		return ctx.ActualPrice() < sum/float32(len(day))*c.Factor
 	case CondHours:
 		hour := uint32(ctx.Now.Hour())
 		return c.HoursMin <= hour && hour <= c.HoursMax
+	case CondTimeRange:
+		return c.TimeRange.Contains(uint16(ctx.Now.Hour()*60 + ctx.Now.Minute()))
 	case CondCheap:
 		return c.Cheap.Evaluate(ctx)
+	case CondTariff:
+		if ctx.Distribution == nil {
+			return false
+		}
+		return containsByte(ctx.Distribution.HighHours, byte(ctx.Now.Hour())) == c.High
+	case CondDailyBudget:
+		// A 0..24 window never crosses midnight, so the ranking only ever sees
+		// the current calendar day.
+		return CheapCondition{Hours: c.Budget, From: 0, To: 24}.Evaluate(ctx)
+	case CondFirstCheapHour:
+		return c.Cheap.EvaluateFirst(ctx)
+	case CondExpensive:
+		return c.Expensive.Evaluate(ctx)
+	case CondCheapestBlock:
+		return c.Cheap.EvaluateBlock(ctx)
+	case CondPercentile:
+		return c.Percentile.Evaluate(ctx)
+	case CondCheapestAhead:
+		return c.Ahead.Evaluate(ctx)
+	case CondRising, CondFalling:
+		return hourlyChangeExceeds(ctx, c.Kind == CondRising, c.Delta)
+	case CondDayOfWeek:
+		return slices.Contains(c.Days, mondayFirst(ctx.Now.Weekday()))
+	case CondOffPeakCheapest:
+		return CheapCondition{Hours: c.Budget, From: OffPeakFrom, To: OffPeakTo}.Evaluate(ctx)
+	case CondComment:
+		return c.Inner.Evaluate(ctx)
+	case CondTrend:
+		return c.Trend.Evaluate(ctx)
+	case CondCheapQuarters:
+		return c.Quarters.Evaluate(ctx)
 	case CondDebug:
 		return c.Debug
 	}
 	return false
 }
 
+func changeName(kind ConditionKind) string {
+	if kind == CondRising {
+		return "rising"
+	}
+	return "falling"
+}
+
+func tariffName(high bool) string {
+	if high {
+		return "high"
+	}
+	return "low"
+}
+
 // EvaluateAll evaluates the condition across all price slots in the context.
 func (c Condition) EvaluateAll(ctx *EvaluateContext) []bool {
-	startTime := ctx.Now.Add(-time.Duration(ctx.Prices.NowIndex) * time.Hour)
 	out := make([]bool, len(ctx.Prices.Prices))
 	for i := range ctx.Prices.Prices {
-		updatedCtx := &EvaluateContext{
-			Now: startTime.Add(time.Duration(i) * time.Hour),
-			Prices: PricesContext{
-				Prices:   append([]float32(nil), ctx.Prices.Prices...),
-				NowIndex: i,
-			},
-		}
-		out[i] = c.Evaluate(updatedCtx)
+		out[i] = c.Evaluate(ctx.at(i))
 	}
 	return out
 }
 
+// at returns a copy of ctx with now moved to the start of slot i.
+func (ctx *EvaluateContext) at(i int) *EvaluateContext {
+	return &EvaluateContext{
+		Now: ctx.slotTime(i),
+		Prices: PricesContext{
+			Prices:   append([]float32(nil), ctx.Prices.Prices...),
+			NowIndex: i,
+			DayLens:  ctx.Prices.DayLens,
+		},
+		Distribution: ctx.Distribution,
+		SlotsPerHour: ctx.SlotsPerHour,
+	}
+}
+
+// NextChange returns when the condition's result next differs from its
+// result for the current slot, as a wall-clock time in the form of ctx.Now.
+// It is false when the result holds for every known price.
+func (c Condition) NextChange(ctx *EvaluateContext) (time.Time, bool) {
+	idx, ok := nextChange(c.EvaluateAll(ctx), ctx.Prices.NowIndex)
+	if !ok {
+		return time.Time{}, false
+	}
+	return ctx.slotTime(idx), true
+}
+
+// nextChange returns the first index after i whose result differs from
+// results[i], or false when the result holds to the end of results.
+func nextChange(results []bool, i int) (int, bool) {
+	for j := i + 1; j < len(results); j++ {
+		if results[j] != results[i] {
+			return j, true
+		}
+	}
+	return 0, false
+}
+
 func (cc CheapCondition) Evaluate(ctx *EvaluateContext) bool {
 	prices, ok := ctx.Slice(int(cc.From), int(cc.To))
 	if !ok {
 		return false
 	}
+	return withinCheapest(prices, ctx.ActualPrice(), int(cc.Hours)*ctx.slotsPerHour())
+}
+
+func (ec ExpensiveCondition) Evaluate(ctx *EvaluateContext) bool {
+	prices, ok := ctx.Slice(int(ec.From), int(ec.To))
+	if !ok {
+		return false
+	}
+	return withinMostExpensive(prices, ctx.ActualPrice(), int(ec.Hours)*ctx.slotsPerHour())
+}
+
+// Contains reports whether minute of the day lies in the range, wrapping past
+// midnight when From is after To.
+func (tr TimeRange) Contains(minute uint16) bool {
+	if tr.From <= tr.To {
+		return tr.From <= minute && minute < tr.To
+	}
+	return minute >= tr.From || minute < tr.To
+}
+
+// Evaluate compares the current price with the nearest-rank percentile of the
+// window: the smallest price that at least Value percent of slots do not
+// exceed. Value 0 therefore matches only the window's minimum.
+func (pc PercentileCondition) Evaluate(ctx *EvaluateContext) bool {
+	prices, ok := ctx.Slice(int(pc.From), int(pc.To))
+	if !ok || len(prices) == 0 {
+		return false
+	}
+	sorted := slices.Clone(prices)
+	slices.Sort(sorted)
+	rank := (int(pc.Value)*len(sorted) + 99) / 100
+	return ctx.ActualPrice() <= sorted[max(rank, 1)-1]
+}
+
+// Evaluate ranks the slots from NowIndex to Horizon hours ahead, or to the
+// last known price when that comes sooner.
+func (ca CheapestAhead) Evaluate(ctx *EvaluateContext) bool {
+	sph := ctx.slotsPerHour()
+	now := ctx.Prices.NowIndex
+	end := min(now+int(ca.Horizon)*sph, len(ctx.Prices.Prices))
+	if now < 0 || now >= end {
+		return false
+	}
+	window := slices.Clone(ctx.Prices.Prices[now:end])
+	return withinCheapest(window, ctx.ActualPrice(), int(ca.Hours)*sph)
+}
+
+// Evaluate ranks the quarter-hour slots of the window around NowIndex.
+func (cq CheapQuarters) Evaluate(ctx *EvaluateContext) bool {
+	rng, ok := ctx.quarterRange(int(cq.From), int(cq.To))
+	if !ok {
+		return false
+	}
+	window := append([]float32(nil), ctx.Prices.Prices[rng[0]:rng[1]]...)
+	return withinCheapest(window, ctx.ActualPrice(), int(cq.Count))
+}
+
+// withinCheapest reports whether actual ranks among the n lowest of prices.
+// It sorts prices in place.
+func withinCheapest(prices []float32, actual float32, n int) bool {
 	sort.Slice(prices, func(i, j int) bool { return prices[i] < prices[j] })
-	actualPrice := ctx.ActualPrice()
 	pos := len(prices)
 	for i, p := range prices {
-		if actualPrice < p {
+		if actual < p {
 			pos = i
 			break
 		}
 	}
-	return pos <= int(cc.Hours)
+	return pos <= n
+}
+
+// withinMostExpensive is withinCheapest with the order reversed: ties with
+// the n-th most expensive price count as inside.
+func withinMostExpensive(prices []float32, actual float32, n int) bool {
+	sort.Slice(prices, func(i, j int) bool { return prices[i] > prices[j] })
+	pos := len(prices)
+	for i, p := range prices {
+		if actual > p {
+			pos = i
+			break
+		}
+	}
+	return pos <= n
+}
+
+// hourlyChangeExceeds reports whether the price moved by more than threshold
+// since the same slot an hour earlier, up when rising and down otherwise. The
+// first hour of the context has no predecessor and is false.
+func hourlyChangeExceeds(ctx *EvaluateContext, rising bool, threshold float32) bool {
+	prev := ctx.Prices.NowIndex - ctx.slotsPerHour()
+	if prev < 0 {
+		return false
+	}
+	delta := ctx.ActualPrice() - ctx.Prices.Prices[prev]
+	if !rising {
+		delta = -delta
+	}
+	return delta > threshold
+}
+
+// Evaluate compares the current price with the one Hours hours earlier. It is
+// false when the context does not reach that far back.
+func (tc TrendCondition) Evaluate(ctx *EvaluateContext) bool {
+	past := ctx.Prices.NowIndex - int(tc.Hours)*ctx.slotsPerHour()
+	if past < 0 {
+		return false
+	}
+	delta := ctx.ActualPrice() - ctx.Prices.Prices[past]
+	if !tc.Rising {
+		delta = -delta
+	}
+	return delta >= tc.By
+}
+
+// EvaluateFirst is true only at the earliest of the window's cheapest hours,
+// so a device driven by it gets a single start instead of toggling between
+// non-adjacent cheap hours.
+func (cc CheapCondition) EvaluateFirst(ctx *EvaluateContext) bool {
+	rng, ok := ctx.hourRange(cc.From, cc.To)
+	if !ok {
+		return false
+	}
+	selected := cheapestN(ctx.Prices.Prices[rng[0]:rng[1]], int(cc.Hours)*ctx.slotsPerHour())
+	if len(selected) == 0 {
+		return false
+	}
+	return rng[0]+slices.Min(selected) == ctx.Prices.NowIndex
+}
+
+// EvaluateBlock is true inside the cheapest contiguous run of Hours hours in
+// the window, for loads that must not be interrupted once started.
+func (cc CheapCondition) EvaluateBlock(ctx *EvaluateContext) bool {
+	rng, ok := ctx.hourRange(cc.From, cc.To)
+	if !ok {
+		return false
+	}
+	n := int(cc.Hours) * ctx.slotsPerHour()
+	idx, _, ok := CheapestBlock(ctx.Prices.Prices[rng[0]:rng[1]], n)
+	if !ok {
+		return false
+	}
+	start := rng[0] + idx
+	return start <= ctx.Prices.NowIndex && ctx.Prices.NowIndex < start+n
+}
+
+// cheapestN returns the indices of the n lowest prices. Ties go to the
+// earlier index so the selection is deterministic.
+func cheapestN(prices []float32, n int) []int {
+	idx := make([]int, len(prices))
+	for i := range idx {
+		idx[i] = i
+	}
+	sort.SliceStable(idx, func(a, b int) bool { return prices[idx[a]] < prices[idx[b]] })
+	return idx[:min(n, len(idx))]
+}
+
+// mostExpensiveN is cheapestN in descending price order.
+func mostExpensiveN(prices []float32, n int) []int {
+	idx := make([]int, len(prices))
+	for i := range idx {
+		idx[i] = i
+	}
+	sort.SliceStable(idx, func(a, b int) bool { return prices[idx[a]] > prices[idx[b]] })
+	return idx[:min(n, len(idx))]
 }
 
 // EvaluateContext is the price + time context for evaluation.
 type EvaluateContext struct {
 	Now    time.Time
 	Prices PricesContext
+	// Distribution supplies the high-tariff hours for Tariff conditions. Nil
+	// means tariff information is unavailable and Tariff evaluates to false.
+	Distribution *Distribution
+	// SlotsPerHour is how many price slots cover one hour: 4 for OTE's
+	// quarter-hour prices. Zero is treated as 1, i.e. hourly prices.
+	SlotsPerHour int
+}
+
+func (ctx *EvaluateContext) slotsPerHour() int {
+	return max(ctx.SlotsPerHour, 1)
+}
+
+// hourRange is findTimeRange for the context's slot resolution: from and to
+// are hours of the day, the result indexes ctx.Prices. A window reaching
+// before the first or past the last known price is not found.
+func (ctx *EvaluateContext) hourRange(from, to uint8) ([2]int, bool) {
+	sph := ctx.slotsPerHour()
+	if sph == 4 {
+		return ctx.quarterRange(int(from)*4, int(to)*4)
+	}
+	return ctx.Prices.within(findSlotRange(ctx.Prices.NowIndex, int(from)*sph, int(to)*sph, 24*sph))
+}
+
+// quarterRange is findSlotRange over quarter-hour prices; from and to are
+// wall-clock quarters of the day (0..96). Without DayLens every day has 96
+// quarters. With DayLens the window is located on a uniform 96-quarter grid
+// and its ends are mapped back to the actual slots, so on DST days a window
+// starting in the skipped hour starts at 03:00 and one ending at 03:00
+// includes both copies of the repeated hour.
+func (ctx *EvaluateContext) quarterRange(from, to int) ([2]int, bool) {
+	lens := ctx.Prices.DayLens
+	if lens == nil {
+		return ctx.Prices.within(findSlotRange(ctx.Prices.NowIndex, from, to, 96))
+	}
+	day, start, ok := ctx.Prices.locate(ctx.Prices.NowIndex)
+	if !ok {
+		return [2]int{}, false
+	}
+	hour, minute := SlotClock(ctx.Prices.NowIndex-start, lens[day])
+	rng, ok := findSlotRange(day*96+hour*4+minute/15, from, to, 96)
+	if !ok {
+		return [2]int{}, false
+	}
+	return ctx.Prices.within([2]int{ctx.Prices.slotAt(rng[0]), ctx.Prices.slotAt(rng[1])}, true)
+}
+
+// slotTime is the wall-clock time slot i of the prices starts at, in the same
+// UTC-labelled form as Now.
+func (ctx *EvaluateContext) slotTime(i int) time.Time {
+	step := time.Hour / time.Duration(ctx.slotsPerHour())
+	nowDay, _, ok := ctx.Prices.locate(ctx.Prices.NowIndex)
+	day, start, iok := ctx.Prices.locate(i)
+	if !ok || !iok {
+		return ctx.Now.Add(time.Duration(i-ctx.Prices.NowIndex) * step)
+	}
+	midnight := time.Date(ctx.Now.Year(), ctx.Now.Month(), ctx.Now.Day(), 0, 0, 0, 0, ctx.Now.Location())
+	hour, minute := SlotClock(i-start, ctx.Prices.DayLens[day])
+	return midnight.AddDate(0, 0, day-nowDay).Add(time.Duration(hour)*time.Hour + time.Duration(minute)*time.Minute)
 }
 
 type PricesContext struct {
 	Prices   []float32
 	NowIndex int
+	// DayLens holds the number of quarter-hour slots of each consecutive day
+	// in Prices: 96, or 92 and 100 on DST days. Nil means every day is full
+	// length.
+	DayLens []int
+}
+
+// locate returns the day of DayLens containing slot i and that day's first
+// slot. It is false without DayLens or when i is out of range.
+func (pc PricesContext) locate(i int) (day, start int, ok bool) {
+	if i < 0 {
+		return 0, 0, false
+	}
+	for d, n := range pc.DayLens {
+		if i < start+n {
+			return d, start, true
+		}
+		start += n
+	}
+	return 0, 0, false
+}
+
+// within passes a found window through only when all of it indexes Prices,
+// so callers never slice past either end of the context.
+func (pc PricesContext) within(rng [2]int, ok bool) ([2]int, bool) {
+	if !ok || rng[0] < 0 || rng[1] > len(pc.Prices) {
+		return [2]int{}, false
+	}
+	return rng, true
+}
+
+// slotAt maps quarter v of a uniform 96-quarter grid to the index of the
+// first slot at or after that wall-clock time.
+func (pc PricesContext) slotAt(v int) int {
+	day, w := v/96, v%96
+	start := 0
+	for d := 0; d < day; d++ {
+		if d < len(pc.DayLens) {
+			start += pc.DayLens[d]
+		} else {
+			start += 96
+		}
+	}
+	if day >= len(pc.DayLens) {
+		return start + w
+	}
+	switch n := pc.DayLens[day]; {
+	case n == 92 && w >= 12:
+		return start + w - 4
+	case n == 92 && w >= 8:
+		return start + 8
+	case n == 100 && w >= 12:
+		return start + w + 4
+	}
+	return start + w
 }
 
 func NewEvaluateContext(now time.Time, prices []float32, targetPriceIndex int) *EvaluateContext {
@@ -280,13 +1445,20 @@ func (ctx *EvaluateContext) ActualPrice() float32 {
 	return ctx.Prices.Prices[ctx.Prices.NowIndex]
 }
 
-// Slice returns the price slice for the time range [from..to), or false if not applicable.
-func (ctx *EvaluateContext) Slice(from, to int) ([]float32, bool) {
-	rng, ok := findTimeRange(ctx.Prices.NowIndex, uint8(from), uint8(to))
+// CurrentRank is CheaperThan for the current price among the slots of its
+// calendar day.
+func (ctx *EvaluateContext) CurrentRank() (float32, bool) {
+	day, ok := ctx.Slice(0, 24)
 	if !ok {
-		return nil, false
+		return 0, false
 	}
-	if rng[1] > len(ctx.Prices.Prices) {
+	return CheaperThan(day, ctx.ActualPrice())
+}
+
+// Slice returns the price slice for the hour range [from..to), or false if not applicable.
+func (ctx *EvaluateContext) Slice(from, to int) ([]float32, bool) {
+	rng, ok := ctx.hourRange(uint8(from), uint8(to))
+	if !ok {
 		return nil, false
 	}
 	out := make([]float32, rng[1]-rng[0])
@@ -296,25 +1468,34 @@ func (ctx *EvaluateContext) Slice(from, to int) ([]float32, bool) {
 
 // findTimeRange — see Rust doc; returns [start,end) if current index lies inside.
 func findTimeRange(currentHourIdx int, fromHour, toHour uint8) ([2]int, bool) {
-	currentDay := currentHourIdx / 24
-	currentHour := currentHourIdx % 24
+	return findSlotRange(currentHourIdx, int(fromHour), int(toHour), 24)
+}
+
+// findSlotRange is findTimeRange for any number of slots per day; from and
+// to are slot-of-day indexes.
+func findSlotRange(currentIdx, from, to, slotsPerDay int) ([2]int, bool) {
+	if currentIdx < 0 {
+		return [2]int{}, false
+	}
+	currentDay := currentIdx / slotsPerDay
+	currentSlot := currentIdx % slotsPerDay
 
 	fromDayOffset := currentDay
-	if int(fromHour) > currentHour {
+	if from > currentSlot {
 		fromDayOffset--
 	}
 
 	toDayOffset := fromDayOffset
-	if fromHour > toHour {
+	if from > to {
 		toDayOffset++
 	}
 
-	startISize := fromDayOffset*24 + int(fromHour)
-	endISize := toDayOffset*24 + int(toHour)
+	startISize := fromDayOffset*slotsPerDay + from
+	endISize := toDayOffset*slotsPerDay + to
 	if startISize < 0 || endISize < 0 {
 		return [2]int{}, false
 	}
-	if startISize <= currentHourIdx && currentHourIdx < endISize {
+	if startISize <= currentIdx && currentIdx < endISize {
 		return [2]int{startISize, endISize}, true
 	}
 	return [2]int{}, false
@@ -1,6 +1,10 @@
 package webserver
 
 import (
+	"encoding/json"
+	"math/rand/v2"
+	"slices"
+	"strings"
 	"testing"
 	"time"
 )
@@ -41,6 +45,221 @@ func TestPrice(t *testing.T) {
 	}
 }
 
+func TestPriceAbove(t *testing.T) {
+	ctx := setupCtx() // current price is 2
+
+	for threshold, want := range map[float32]bool{1: true, 2: false, 3: false} {
+		cond := Condition{Kind: CondPriceAbove, Price: threshold}
+		if got := cond.Evaluate(ctx); got != want {
+			t.Errorf("price_above %g: got %v want %v", threshold, got, want)
+		}
+		// Price and PriceAbove partition every slot.
+		price := Condition{Kind: CondPrice, Price: threshold}
+		if cond.Evaluate(ctx) == price.Evaluate(ctx) {
+			t.Errorf("threshold %g: price and price_above agree", threshold)
+		}
+	}
+}
+
+func TestPriceAbove_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{price_above: 85.5}, {not: {price_above: -3}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	if c := cond.Children[0]; c.Kind != CondPriceAbove || c.Price != 85.5 {
+		t.Fatalf("got %s", c.Format())
+	}
+	b, err := json.Marshal(cond)
+	if err != nil {
+		t.Fatalf("marshal: %v", err)
+	}
+	if string(b) != `{"and":[{"price_above":85.5},{"not":{"price_above":-3}}]}` {
+		t.Fatalf("marshal: %s", b)
+	}
+	var back Condition
+	if err := json.Unmarshal(b, &back); err != nil || !back.Equal(cond) {
+		t.Fatalf("round trip: %v, %s", err, back.Format())
+	}
+}
+
+func TestBelowAverage_MarksHoursUnderDayMean(t *testing.T) {
+	// Yesterday is uniformly cheap so it would drag the mean down if it were
+	// counted. Today is 100 except 05:00 at 10; its mean is 96.25.
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 5
+		if i >= 24 {
+			prices[i] = 100
+		}
+	}
+	prices[24+5] = 10
+	now, _ := time.Parse("2006-01-02 15:04:05", "2020-01-02 00:00:00")
+	ctx := NewEvaluateContext(now, prices, 24)
+
+	cond := Condition{Kind: CondBelowAverage, Factor: 0.8}
+	got := cond.EvaluateAll(ctx)
+	for i, v := range got[24:] {
+		if want := i == 5; v != want {
+			t.Errorf("hour %d: got %v want %v", i, v, want)
+		}
+	}
+	// At 1.05 the threshold is 101.06, so the regular hours count as below too.
+	if !(Condition{Kind: CondBelowAverage, Factor: 1.05}.EvaluateAll(ctx)[24+10]) {
+		t.Error("factor 1.05: expected 10:00 to be below")
+	}
+	if err := (Condition{Kind: CondBelowAverage}).Validate(); err == nil {
+		t.Error("zero factor should fail validation")
+	}
+}
+
+func TestCheapestAhead_SpansMidnight(t *testing.T) {
+	// Two hourly days; the three cheapest hours of the evening horizon are
+	// 23:00 today and 00:00 and 01:00 tomorrow.
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 100
+	}
+	prices[22], prices[23], prices[24], prices[25] = 4, 1, 2, 3
+	now, _ := time.Parse("2006-01-02 15:04", "2026-05-10 14:00")
+	ctx := NewEvaluateContext(now, prices, 14)
+
+	cond := Condition{Kind: CondCheapestAhead, Ahead: CheapestAhead{Hours: 3, Horizon: 12}}
+	got := cond.EvaluateAll(ctx)
+	for i := 14; i <= 25; i++ {
+		if want := i >= 23; got[i] != want {
+			t.Errorf("slot %d: got %v, want %v", i, got[i], want)
+		}
+	}
+	if cond.Evaluate(ctx) {
+		t.Error("14:00 is not among the cheapest ahead")
+	}
+
+	parsed, err := ParseCondition(`[{cheapest_ahead: {hours: 3, horizon: 12}}]`)
+	if err != nil || parsed.Children[0].Kind != CondCheapestAhead || parsed.Children[0].Ahead != cond.Ahead {
+		t.Fatalf("parse: got %+v, %v", parsed, err)
+	}
+	for _, bad := range []CheapestAhead{{Hours: 0, Horizon: 12}, {Hours: 13, Horizon: 12}, {Hours: 3, Horizon: 49}} {
+		if err := (Condition{Kind: CondCheapestAhead, Ahead: bad}).Validate(); err == nil {
+			t.Errorf("%+v: expected a validation error", bad)
+		}
+	}
+}
+
+func TestPercentile_MedianOfKnownPrices(t *testing.T) {
+	ctx := setupCtx() // prices 0..23, so the 50th percentile is 11
+
+	got := Condition{Kind: CondPercentile, Percentile: PercentileCondition{Value: 50, From: 0, To: 24}}.EvaluateAll(ctx)
+	for i, v := range got {
+		if want := i <= 11; v != want {
+			t.Errorf("hour %d: got %v want %v", i, v, want)
+		}
+	}
+	// Within 12..24 the median is 17; hours outside the window never match.
+	got = Condition{Kind: CondPercentile, Percentile: PercentileCondition{Value: 50, From: 12, To: 24}}.EvaluateAll(ctx)
+	for i, v := range got {
+		if want := i >= 12 && i <= 17; v != want {
+			t.Errorf("window 12..24, hour %d: got %v want %v", i, v, want)
+		}
+	}
+	for value, matches := range map[uint8]int{0: 1, 100: 24} {
+		n := 0
+		for _, v := range (Condition{Kind: CondPercentile, Percentile: PercentileCondition{Value: value, From: 0, To: 24}}).EvaluateAll(ctx) {
+			if v {
+				n++
+			}
+		}
+		if n != matches {
+			t.Errorf("p%d: %d hours match, want %d", value, n, matches)
+		}
+	}
+
+	cond, err := ParseCondition(`[{percentile: {value: 50, from: 0, to: 24}}]`)
+	if err != nil || cond.Children[0].Kind != CondPercentile || cond.Children[0].Percentile.Value != 50 {
+		t.Fatalf("parse: %v, %s", err, cond.Format())
+	}
+	if err := (Condition{Kind: CondPercentile, Percentile: PercentileCondition{Value: 101, To: 24}}).Validate(); err == nil {
+		t.Error("value 101 should fail validation")
+	}
+}
+
+func TestTimeRange_MinutesAndMidnightWrap(t *testing.T) {
+	at := func(clock string) *EvaluateContext {
+		now, _ := time.Parse("2006-01-02 15:04", "2025-02-15 "+clock)
+		return NewEvaluateContext(now, []float32{1}, 0)
+	}
+	morning := Condition{Kind: CondTimeRange, TimeRange: TimeRange{From: 6*60 + 30, To: 8*60 + 15}}
+	for clock, want := range map[string]bool{"06:29": false, "06:30": true, "07:59": true, "08:14": true, "08:15": false} {
+		if got := morning.Evaluate(at(clock)); got != want {
+			t.Errorf("06:30-08:15 at %s: got %v want %v", clock, got, want)
+		}
+	}
+
+	cond, err := ParseCondition(`[{time_range: {from: "23:30", to: "00:30"}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	overnight := cond.Children[0]
+	if overnight.Kind != CondTimeRange || overnight.TimeRange != (TimeRange{From: 23*60 + 30, To: 30}) {
+		t.Fatalf("parsed %s", overnight.Format())
+	}
+	for clock, want := range map[string]bool{"23:29": false, "23:30": true, "23:59": true, "00:00": true, "00:29": true, "00:30": false, "12:00": false} {
+		if got := overnight.Evaluate(at(clock)); got != want {
+			t.Errorf("23:30-00:30 at %s: got %v want %v", clock, got, want)
+		}
+	}
+	if b, _ := json.Marshal(overnight); string(b) != `{"time_range":{"from":"23:30","to":"00:30"}}` {
+		t.Errorf("marshal: %s", b)
+	}
+
+	for _, bad := range []string{`[{time_range: {from: "6:30", to: "08:15"}}]`, `[{time_range: {from: "06:60", to: "08:15"}}]`} {
+		if _, err := ParseCondition(bad); err == nil {
+			t.Errorf("%s: expected a parse error", bad)
+		}
+	}
+	if err := (Condition{Kind: CondTimeRange, TimeRange: TimeRange{From: 0, To: 24 * 60}}).Validate(); err != nil {
+		t.Errorf("00:00-24:00 covers the whole day: %v", err)
+	}
+	if err := (Condition{Kind: CondTimeRange, TimeRange: TimeRange{From: 90, To: 90}}).Validate(); err == nil {
+		t.Error("an empty range should fail validation")
+	}
+}
+
+func TestDayOfWeek_Saturday(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-15 10:00:00") // a Saturday
+	ctx := NewEvaluateContext(now, []float32{1}, 0)
+
+	for _, c := range []struct {
+		days []int
+		want bool
+	}{
+		{[]int{5, 6}, true},
+		{[]int{5}, true},
+		{[]int{0, 1, 2, 3, 4}, false},
+		{[]int{6}, false},
+	} {
+		cond := Condition{Kind: CondDayOfWeek, Days: c.days}
+		if got := cond.Evaluate(ctx); got != c.want {
+			t.Errorf("days %v: got %v want %v", c.days, got, c.want)
+		}
+	}
+
+	cond, err := ParseCondition(`[{day_of_week: [5, 6]}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	if b, _ := json.Marshal(cond.Children[0]); string(b) != `{"day_of_week":[5,6]}` {
+		t.Errorf("marshal: %s", b)
+	}
+	if html := cond.Children[0].RenderHTML(); !strings.Contains(html, "Sat, Sun") {
+		t.Errorf("render: %s", html)
+	}
+	for _, bad := range []string{`[]`, `[7]`, `[-1]`} {
+		if c, err := ParseCondition(`[{day_of_week: ` + bad + `}]`); err == nil && c.Validate() == nil {
+			t.Errorf("%s should fail validation", bad)
+		}
+	}
+}
+
 func TestHours(t *testing.T) {
 	ctx := setupCtx()
 
@@ -131,6 +350,109 @@ func TestOr(t *testing.T) {
 	}
 }
 
+func TestExactlyOne(t *testing.T) {
+	ctx := setupCtx()
+	yes := Condition{Kind: CondDebug, Debug: true}
+	no := Condition{Kind: CondDebug, Debug: false}
+
+	for _, c := range []struct {
+		children []Condition
+		want     bool
+	}{
+		{nil, false},
+		{[]Condition{yes}, true},
+		{[]Condition{no}, false},
+		{[]Condition{yes, no}, true},
+		{[]Condition{yes, yes}, false},
+		{[]Condition{yes, no, no}, true},
+		{[]Condition{yes, yes, yes}, false}, // odd, but not exactly one
+	} {
+		cond := Condition{Kind: CondExactlyOne, Children: c.children}
+		if got := cond.Evaluate(ctx); got != c.want {
+			t.Errorf("%s: got %v want %v", cond.Format(), got, c.want)
+		}
+	}
+}
+
+func TestExactlyOne_JSONRoundTrip(t *testing.T) {
+	for _, src := range []string{
+		`{"exactly_one":[]}`,
+		`{"exactly_one":[{"price":10}]}`,
+		`{"exactly_one":[{"hours":[0,6]},{"hours":[22,23]}]}`,
+		`{"exactly_one":[{"price":10},{"tariff":"high"},{"not":{"price":5}}]}`,
+	} {
+		var c Condition
+		if err := json.Unmarshal([]byte(src), &c); err != nil {
+			t.Fatalf("%s: %v", src, err)
+		}
+		if c.Kind != CondExactlyOne {
+			t.Fatalf("%s: got %s", src, c.Format())
+		}
+		b, err := json.Marshal(c)
+		if err != nil || string(b) != src {
+			t.Errorf("round trip: got %s, want %s (%v)", b, src, err)
+		}
+	}
+}
+
+func TestAtLeast_CountsMatchingChildren(t *testing.T) {
+	ctx := setupCtx()
+	yes := Condition{Kind: CondDebug, Debug: true}
+	no := Condition{Kind: CondDebug, Debug: false}
+	for _, c := range []struct {
+		k        uint8
+		children []Condition
+		want     bool
+	}{
+		{0, nil, true},
+		{0, []Condition{no, no}, true},
+		{2, []Condition{yes, no, yes}, true},
+		{2, []Condition{yes, no, no}, false},
+		{3, []Condition{yes, yes, yes}, true},
+		{4, []Condition{yes, yes, yes}, false}, // more than there are children
+	} {
+		cond := Condition{Kind: CondAtLeast, AtLeast: c.k, Children: c.children}
+		if got := cond.Evaluate(ctx); got != c.want {
+			t.Errorf("%s: got %v want %v", cond.Format(), got, c.want)
+		}
+	}
+	if err := (Condition{Kind: CondAtLeast, AtLeast: 4, Children: []Condition{yes, yes, yes}}).Validate(); err == nil {
+		t.Error("k above the number of children should not validate")
+	}
+
+	cond, err := ParseCondition(`[{at_least:{k:2,of:[{price:10},{tariff:"low"},{hours:[0,6]}]}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	if got := c.Expression(); got != "AT_LEAST_2(price<=10, tariff=low, hours[0..6])" {
+		t.Errorf("expression: got %s", got)
+	}
+	b, _ := json.Marshal(c)
+	if want := `{"at_least":{"k":2,"of":[{"price":10},{"tariff":"low"},{"hours":[0,6]}]}}`; string(b) != want {
+		t.Errorf("json: got %s, want %s", b, want)
+	}
+}
+
+func TestExpression_Nested(t *testing.T) {
+	cond, err := ParseCondition(`[
+		{price: 120},
+		{hours: [0, 10]},
+		{or: [{cheap: {hours: 3, from: 22, to: 6}}, {not: {tariff: "high"}}]},
+		{not: {and: [{price_above: 50}, {day_of_week: [5, 6]}]}},
+	]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	want := "AND(price<=120, hours[0..10], OR(cheap(3h in 22..6), NOT(tariff=high)), NOT(AND(price>50, day_of_week(Sat, Sun))))"
+	if got := cond.Expression(); got != want {
+		t.Errorf("got  %s\nwant %s", got, want)
+	}
+	if got := (Condition{Kind: CondOr}).Expression(); got != "OR()" {
+		t.Errorf("empty or: got %s", got)
+	}
+}
+
 func TestCheapToday(t *testing.T) {
 	ctx := setupCtx()
 	if !(CheapCondition{Hours: 1, From: 2, To: 3}).Evaluate(ctx) {
@@ -183,6 +505,137 @@ func TestCheapTodayTomorrow(t *testing.T) {
 	}
 }
 
+func TestCheapCondition_ValidateMessages(t *testing.T) {
+	for _, c := range []struct {
+		cheap CheapCondition
+		want  string
+	}{
+		{CheapCondition{Hours: 25, From: 0, To: 24}, "cheap: hours must be between 1 and 24, got 25"},
+		{CheapCondition{Hours: 0, From: 0, To: 24}, "cheap: hours must be between 1 and 24, got 0"},
+		{CheapCondition{Hours: 2, From: 24, To: 6}, "cheap: from must be between 0 and 23, got 24"},
+		{CheapCondition{Hours: 2, From: 22, To: 25}, "cheap: to must be between 0 and 24, got 25"},
+	} {
+		err := Condition{Kind: CondAnd, Children: []Condition{{Kind: CondCheap, Cheap: c.cheap}}}.Validate()
+		if err == nil || err.Error() != c.want {
+			t.Errorf("%+v: got %v, want %q", c.cheap, err, c.want)
+		}
+	}
+	if err := (CheapCondition{Hours: 24, From: 0, To: 24}).Validate(); err != nil {
+		t.Errorf("full day: %v", err)
+	}
+}
+
+func TestCheapestBlock_PrefersContiguousRun(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 00:00:00")
+	prices := make([]float32, 24)
+	for i := range prices {
+		prices[i] = 50
+	}
+	// The three cheapest single hours are scattered...
+	prices[1], prices[5], prices[9] = 0, 0, 0
+	// ...but 14:00-17:00 is the cheapest three-hour run (30 against 100).
+	prices[14], prices[15], prices[16] = 10, 10, 10
+	ctx := NewEvaluateContext(now, prices, 0)
+
+	block := Condition{Kind: CondCheapestBlock, Cheap: CheapCondition{Hours: 3, From: 0, To: 24}}.EvaluateAll(ctx)
+	cheap := Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 3, From: 0, To: 24}}.EvaluateAll(ctx)
+	for i := range prices {
+		if want := i >= 14 && i < 17; block[i] != want {
+			t.Errorf("block hour %d: got %v want %v", i, block[i], want)
+		}
+		if want := i == 1 || i == 5 || i == 9; cheap[i] != want {
+			t.Errorf("cheap hour %d: got %v want %v", i, cheap[i], want)
+		}
+	}
+}
+
+func TestCheapestBlock_OverMidnight(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-15 00:00:00")
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 50
+	}
+	// 23:00 yesterday to 01:00 today beats 02:00-04:00 today.
+	prices[23], prices[24] = 5, 5
+	prices[26], prices[27] = 0, 20
+	ctx := NewEvaluateContext(now, prices, 0)
+
+	got := Condition{Kind: CondCheapestBlock, Cheap: CheapCondition{Hours: 2, From: 22, To: 4}}.EvaluateAll(ctx)
+	for i := range prices {
+		if want := i == 23 || i == 24; got[i] != want {
+			t.Errorf("slot %d: got %v want %v", i, got[i], want)
+		}
+	}
+}
+
+func TestExpensiveToday(t *testing.T) {
+	ctx := setupCtx()
+	if !(ExpensiveCondition{Hours: 1, From: 2, To: 3}).Evaluate(ctx) {
+		t.Fatal("single price always true")
+	}
+	if (ExpensiveCondition{Hours: 24, From: 3, To: 24}).Evaluate(ctx) {
+		t.Fatal("out of range should be false")
+	}
+	if !(ExpensiveCondition{Hours: 1, From: 0, To: 3}).Evaluate(ctx) {
+		t.Fatal("hours=1 in 0-3 should be true")
+	}
+	if (ExpensiveCondition{Hours: 2, From: 0, To: 6}).Evaluate(ctx) {
+		t.Fatal("hours=2 in 0-6 should be false")
+	}
+}
+
+func TestExpensiveYesterdayToday(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 00:43:44")
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 10
+	}
+	prices[24] = 11
+	ctx := NewEvaluateContext(now, prices, 24)
+	if !(ExpensiveCondition{Hours: 1, From: 23, To: 1}).Evaluate(ctx) {
+		t.Fatal("expected expensive to be true with yesterday[23]=10")
+	}
+	ctx.Prices.Prices[23] = 12
+	if (ExpensiveCondition{Hours: 1, From: 23, To: 1}).Evaluate(ctx) {
+		t.Fatal("expected expensive to be false with yesterday[23]=12")
+	}
+}
+
+func TestExpensiveTodayTomorrow(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 23:43:44")
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 10
+	}
+	prices[23] = 11
+	ctx := NewEvaluateContext(now, prices, 23)
+	if !(ExpensiveCondition{Hours: 1, From: 23, To: 1}).Evaluate(ctx) {
+		t.Fatal("expected expensive to be true")
+	}
+	ctx.Prices.Prices[24] = 12
+	if (ExpensiveCondition{Hours: 1, From: 23, To: 1}).Evaluate(ctx) {
+		t.Fatal("expected expensive to be false")
+	}
+}
+
+func TestExpensive_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{expensive:{hours:2,from:17,to:21}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	if c.Kind != CondExpensive || c.Expensive != (ExpensiveCondition{Hours: 2, From: 17, To: 21}) {
+		t.Fatalf("got %s", c.Format())
+	}
+	b, _ := json.Marshal(c)
+	if string(b) != `{"expensive":{"hours":2,"from":17,"to":21}}` {
+		t.Fatalf("marshal: %s", b)
+	}
+	if err := (Condition{Kind: CondExpensive}).Validate(); err == nil || !strings.HasPrefix(err.Error(), "expensive:") {
+		t.Errorf("validate: got %v", err)
+	}
+}
+
 func TestFindTimeRange(t *testing.T) {
 	type tc struct {
 		idx           int
@@ -204,6 +657,8 @@ func TestFindTimeRange(t *testing.T) {
 		{47, 23, 1, [2]int{47, 49}, true},
 		{0, 0, 24, [2]int{0, 24}, true},
 		{24, 0, 24, [2]int{24, 48}, true},
+		{2, 22, 6, [2]int{}, false}, // would start yesterday at 22:00, before index 0
+		{-1, 0, 24, [2]int{}, false},
 	}
 	for _, c := range cases {
 		got, ok := findTimeRange(c.idx, c.from, c.to)
@@ -216,6 +671,31 @@ func TestFindTimeRange(t *testing.T) {
 	}
 }
 
+func TestHourRange_RejectsWindowsOffTheContext(t *testing.T) {
+	ctx := setupCtx() // one day of hourly prices, now at 02:00
+	if rng, ok := ctx.hourRange(22, 6); ok {
+		t.Errorf("22..6 at 02:00 starts before the first price: got %v", rng)
+	}
+	ctx.Prices.NowIndex = 23
+	if rng, ok := ctx.hourRange(22, 6); ok {
+		t.Errorf("22..6 at 23:00 runs past the last price: got %v", rng)
+	}
+	if rng, ok := ctx.hourRange(20, 24); !ok || rng != [2]int{20, 24} {
+		t.Errorf("20..24 at 23:00: got %v %v", rng, ok)
+	}
+	// Conditions over such windows are false rather than out of range.
+	if (Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 2, From: 22, To: 6}}).Evaluate(ctx) {
+		t.Error("cheap over an unknown window should be false")
+	}
+
+	quarters := NewEvaluateContext(time.Date(2020, 1, 1, 0, 30, 0, 0, time.UTC), make([]float32, 96), 2)
+	quarters.SlotsPerHour = 4
+	quarters.Prices.DayLens = []int{96}
+	if rng, ok := quarters.hourRange(23, 1); ok {
+		t.Errorf("quarters: 23..1 at 00:30 starts before the first price: got %v", rng)
+	}
+}
+
 func TestActualPrice(t *testing.T) {
 	now, _ := time.Parse("2006-01-02 15:04:05", "2020-01-01 02:00:00")
 	prices := make([]float32, 48)
@@ -287,3 +767,686 @@ func TestSlice(t *testing.T) {
 		t.Fatal("expected slice 22..2 at idx 47 to be out of range")
 	}
 }
+
+// nestedNot builds `[{"not":{"not":...{"debug":true}...}}]` with depth Not wrappers.
+func nestedNot(depth int) string {
+	return "[" + strings.Repeat(`{"not":`, depth) + `{"debug":true}` + strings.Repeat("}", depth) + "]"
+}
+
+func TestParseCondition_RejectsDeeplyNestedExpression(t *testing.T) {
+	// A few thousand levels would recurse through UnmarshalJSON, Evaluate and
+	// RenderHTML in the request handler; it must be refused up front.
+	_, err := ParseCondition(nestedNot(5000))
+	if err == nil {
+		t.Fatal("expected 5000-deep Not chain to be rejected")
+	}
+	if !strings.Contains(err.Error(), "nested too deeply") {
+		t.Fatalf("unexpected error: %v", err)
+	}
+}
+
+func TestParseCondition_AcceptsNestingWithinLimit(t *testing.T) {
+	// Array + depth Not maps + the leaf map stays at MaxConditionDepth.
+	depth := MaxConditionDepth - 2
+	cond, err := ParseCondition(nestedNot(depth))
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	// Even number of Nots around true stays true.
+	if got := cond.Evaluate(setupCtx()); got != (depth%2 == 0) {
+		t.Fatalf("Evaluate: got %v", got)
+	}
+}
+
+func TestTariff(t *testing.T) {
+	ctx := setupCtx() // 02:00
+	dist := DefaultDistribution()
+
+	low := Condition{Kind: CondTariff, High: false}
+	high := Condition{Kind: CondTariff, High: true}
+	if low.Evaluate(ctx) || high.Evaluate(ctx) {
+		t.Fatal("without a Distribution neither tariff should match")
+	}
+
+	ctx.Distribution = &dist
+	if !low.Evaluate(ctx) {
+		t.Fatal("02:00 is a low-tariff hour")
+	}
+	if high.Evaluate(ctx) {
+		t.Fatal("02:00 is not a high-tariff hour")
+	}
+
+	got := high.EvaluateAll(ctx)
+	for i, v := range got {
+		want := i == 10 || i == 12 || i == 14 || i == 17
+		if v != want {
+			t.Fatalf("high tariff idx %d: got %v want %v", i, v, want)
+		}
+	}
+	for i, v := range low.EvaluateAll(ctx) {
+		if v == got[i] {
+			t.Fatalf("low tariff idx %d should be the complement of high", i)
+		}
+	}
+}
+
+func TestTariff_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{tariff:"low"},{tariff:"high"}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	if cond.Children[0].Kind != CondTariff || cond.Children[0].High {
+		t.Fatalf("first child: got %s", cond.Children[0].Format())
+	}
+	if cond.Children[1].Kind != CondTariff || !cond.Children[1].High {
+		t.Fatalf("second child: got %s", cond.Children[1].Format())
+	}
+	if _, err := ParseCondition(`[{tariff:"peak"}]`); err == nil {
+		t.Fatal("unknown tariff name should be rejected")
+	}
+}
+
+func TestSimplify_DedupAndFlatten(t *testing.T) {
+	price := Condition{Kind: CondPrice, Price: 120}
+	hours := Condition{Kind: CondHours, HoursMin: 0, HoursMax: 10}
+	cond := Condition{Kind: CondAnd, Children: []Condition{
+		price,
+		{Kind: CondAnd, Children: []Condition{hours, price}},
+		price,
+	}}
+	got := cond.Simplify(false)
+	want := Condition{Kind: CondAnd, Children: []Condition{price, hours}}
+	if !got.Equal(want) {
+		t.Fatalf("got %s, want %s", got.Format(), want.Format())
+	}
+
+	// An empty nested And is false and must survive, or the result would flip.
+	withEmpty := Condition{Kind: CondAnd, Children: []Condition{price, {Kind: CondAnd}}}
+	if got := withEmpty.Simplify(false); len(got.Children) != 2 {
+		t.Fatalf("empty And child was flattened away: %s", got.Format())
+	}
+}
+
+func TestSimplify_DoubleNegationAndDeMorgan(t *testing.T) {
+	a := Condition{Kind: CondPrice, Price: 5}
+	b := Condition{Kind: CondHours, HoursMin: 1, HoursMax: 3}
+	notNotA := Condition{Kind: CondNot, Inner: &Condition{Kind: CondNot, Inner: &a}}
+	if got := notNotA.Simplify(false); !got.Equal(a) {
+		t.Fatalf("Not(Not(a)): got %s", got.Format())
+	}
+
+	and := Condition{Kind: CondAnd, Children: []Condition{a, b}}
+	notAnd := Condition{Kind: CondNot, Inner: &and}
+	if got := notAnd.Simplify(false); got.Kind != CondNot {
+		t.Fatalf("De Morgan must be opt-in, got %s", got.Format())
+	}
+	got := notAnd.Simplify(true)
+	want := "Or([Not(Price(5)), Not(Hours(1, 3))])"
+	if got.Format() != want {
+		t.Fatalf("Not(And): got %s, want %s", got.Format(), want)
+	}
+
+	// Rewrites must not change results anywhere in the context.
+	ctx := setupCtx()
+	before, after := notAnd.EvaluateAll(ctx), got.EvaluateAll(ctx)
+	for i := range before {
+		if before[i] != after[i] {
+			t.Fatalf("idx %d: De Morgan changed result %v -> %v", i, before[i], after[i])
+		}
+	}
+
+	or := Condition{Kind: CondOr, Children: []Condition{a, b}}
+	notOr := Condition{Kind: CondNot, Inner: &or}
+	if got := notOr.Simplify(true).Format(); got != "And([Not(Price(5)), Not(Hours(1, 3))])" {
+		t.Fatalf("Not(Or): got %s", got)
+	}
+}
+
+func TestDailyBudget_IgnoresOtherDay(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 05:00:00")
+	prices := make([]float32, 48)
+	// Yesterday is uniformly cheaper than anything today; a window crossing
+	// midnight would rank today's hours against it.
+	for i := 24; i < 48; i++ {
+		prices[i] = 10
+	}
+	prices[24+5] = 1
+	prices[24+6] = 1
+	ctx := NewEvaluateContext(now, prices, 24+5)
+
+	budget := Condition{Kind: CondDailyBudget, Budget: 2}
+	if !budget.Evaluate(ctx) {
+		t.Fatal("05:00 is one of today's 2 cheapest hours")
+	}
+	got := budget.EvaluateAll(ctx)
+	for i := 24; i < 48; i++ {
+		want := i == 29 || i == 30
+		if got[i] != want {
+			t.Fatalf("idx %d: got %v want %v", i, got[i], want)
+		}
+	}
+}
+
+func TestDailyBudget_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{daily_budget:{hours:4}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	if c := cond.Children[0]; c.Kind != CondDailyBudget || c.Budget != 4 {
+		t.Fatalf("got %s", c.Format())
+	}
+	if err := (Condition{Kind: CondDailyBudget, Budget: 0}).Validate(); err == nil {
+		t.Fatal("zero budget should be invalid")
+	}
+}
+
+func TestFirstCheapHour_OnlyEarliestFires(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 00:00:00")
+	prices := make([]float32, 24)
+	for i := range prices {
+		prices[i] = 50
+	}
+	// Non-contiguous cheapest hours: 19 is the cheapest, 4 and 11 follow.
+	prices[4] = 10
+	prices[11] = 5
+	prices[19] = 1
+	ctx := NewEvaluateContext(now, prices, 0)
+
+	first := Condition{Kind: CondFirstCheapHour, Cheap: CheapCondition{Hours: 3, From: 0, To: 24}}
+	got := first.EvaluateAll(ctx)
+	for i, v := range got {
+		if v != (i == 4) {
+			t.Fatalf("idx %d: got %v", i, v)
+		}
+	}
+
+	// Narrowing the window changes which cheap hour comes first.
+	first.Cheap = CheapCondition{Hours: 2, From: 6, To: 24}
+	got = first.EvaluateAll(ctx)
+	for i, v := range got {
+		if v != (i == 11) {
+			t.Fatalf("window 6-24, idx %d: got %v", i, v)
+		}
+	}
+}
+
+func TestFirstCheapHour_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{first_cheap_hour:{hours:3,from:22,to:6}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	if c.Format() != "FirstCheapHour { hours: 3, from: 22, to: 6 }" {
+		t.Fatalf("got %s", c.Format())
+	}
+	b, err := json.Marshal(c)
+	if err != nil || string(b) != `{"first_cheap_hour":{"hours":3,"from":22,"to":6}}` {
+		t.Fatalf("marshal: %s %v", b, err)
+	}
+}
+
+// offPeakCtx spans today and tomorrow with a cheap run starting at 23:00.
+func offPeakCtx(hour int) *EvaluateContext {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 00:00:00")
+	prices := make([]float32, 48)
+	for i := range prices {
+		prices[i] = 100
+	}
+	prices[22] = 50
+	prices[23] = 1
+	for i, p := range []float32{10, 20, 30, 40, 50, 60} {
+		prices[24+i] = p
+	}
+	idx := hour
+	if hour < 12 {
+		idx += 24
+	}
+	return NewEvaluateContext(now.Add(time.Duration(idx)*time.Hour), prices, idx)
+}
+
+func TestOffPeakCheapest_At23(t *testing.T) {
+	ctx := offPeakCtx(23)
+	if !(Condition{Kind: CondOffPeakCheapest, Budget: 2}).Evaluate(ctx) {
+		t.Fatal("23:00 is the cheapest overnight hour")
+	}
+}
+
+func TestOffPeakCheapest_At03(t *testing.T) {
+	ctx := offPeakCtx(3)
+	if (Condition{Kind: CondOffPeakCheapest, Budget: 4}).Evaluate(ctx) {
+		t.Fatal("03:00 is only the 5th cheapest overnight hour")
+	}
+	if !(Condition{Kind: CondOffPeakCheapest, Budget: 5}).Evaluate(ctx) {
+		t.Fatal("03:00 should be within the 5 cheapest overnight hours")
+	}
+}
+
+func TestOffPeakCheapest_MatchesRawCheapWindow(t *testing.T) {
+	ctx := offPeakCtx(23)
+	preset := Condition{Kind: CondOffPeakCheapest, Budget: 3}.EvaluateAll(ctx)
+	raw := Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 3, From: 22, To: 6}}.EvaluateAll(ctx)
+	for i := range preset {
+		if preset[i] != raw[i] {
+			t.Fatalf("idx %d: preset %v raw %v", i, preset[i], raw[i])
+		}
+	}
+	if err := (Condition{Kind: CondOffPeakCheapest, Budget: 9}).Validate(); err == nil {
+		t.Fatal("9 hours do not fit the 8-hour window")
+	}
+}
+
+func TestComment_TransparentToEvaluate(t *testing.T) {
+	ctx := setupCtx()
+	for _, inner := range []Condition{
+		{Kind: CondPrice, Price: 100},
+		{Kind: CondPrice, Price: 0},
+	} {
+		inner := inner
+		wrapped := Condition{Kind: CondComment, Note: "why", Inner: &inner}
+		if wrapped.Evaluate(ctx) != inner.Evaluate(ctx) {
+			t.Fatalf("comment changed result of %s", inner.Format())
+		}
+	}
+}
+
+func TestComment_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{comment:{note:"heat pump: avoid \"peak\"",inner:{not:{price:50}}}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	want := `Comment { note: "heat pump: avoid \"peak\"", inner: Not(Price(50)) }`
+	if c.Format() != want {
+		t.Fatalf("got %s", c.Format())
+	}
+	b, err := json.Marshal(c)
+	if err != nil {
+		t.Fatalf("marshal: %v", err)
+	}
+	var back Condition
+	if err := json.Unmarshal(b, &back); err != nil {
+		t.Fatalf("unmarshal: %v", err)
+	}
+	if !back.Equal(c) {
+		t.Fatalf("round trip: got %s", back.Format())
+	}
+	if !strings.Contains(c.RenderHTML(), "heat pump: avoid &#34;peak&#34;") {
+		t.Errorf("note missing from HTML: %s", c.RenderHTML())
+	}
+}
+
+func TestTrend_Rising(t *testing.T) {
+	ctx := setupCtx() // prices 0..23, now at index 2
+	if !(Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 2, Rising: true, By: 2}}).Evaluate(ctx) {
+		t.Fatal("price rose by 2 over 2 hours")
+	}
+	if (Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 2, Rising: true, By: 3}}).Evaluate(ctx) {
+		t.Fatal("price rose by only 2")
+	}
+	if (Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 2, Rising: false, By: 0.5}}).Evaluate(ctx) {
+		t.Fatal("a rising price is not falling")
+	}
+}
+
+func TestTrend_Falling(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2020-01-01 05:00:00")
+	ctx := NewEvaluateContext(now, []float32{90, 80, 70, 60, 50, 40}, 5)
+	if !(Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 3, Rising: false, By: 30}}).Evaluate(ctx) {
+		t.Fatal("price fell by 30 over 3 hours")
+	}
+	if (Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 3, Rising: true, By: 0}}).Evaluate(ctx) {
+		t.Fatal("a falling price is not rising")
+	}
+}
+
+func TestTrend_InsufficientHistory(t *testing.T) {
+	ctx := setupCtx()
+	if (Condition{Kind: CondTrend, Trend: TrendCondition{Hours: 3, Rising: true, By: 0}}).Evaluate(ctx) {
+		t.Fatal("only 2 slots precede now_index")
+	}
+}
+
+func TestRisingFalling_OneSteepTransition(t *testing.T) {
+	now, _ := time.Parse("2006-01-02 15:04:05", "2020-01-01 00:00:00")
+	// Only 02:00 -> 03:00 moves by more than 20; 04:00 drops back by 25.
+	ctx := NewEvaluateContext(now, []float32{50, 55, 60, 90, 95, 70}, 0)
+	rising := Condition{Kind: CondRising, Delta: 20}
+	falling := Condition{Kind: CondFalling, Delta: 20}
+	if got, want := rising.EvaluateAll(ctx), []bool{false, false, false, true, false, false}; !slices.Equal(got, want) {
+		t.Errorf("rising: got %v, want %v", got, want)
+	}
+	if got, want := falling.EvaluateAll(ctx), []bool{false, false, false, false, false, true}; !slices.Equal(got, want) {
+		t.Errorf("falling: got %v, want %v", got, want)
+	}
+	// The threshold must be exceeded, not just met.
+	if (Condition{Kind: CondFalling, Delta: 25}).EvaluateAll(ctx)[5] {
+		t.Error("a drop of exactly 25 should not exceed 25")
+	}
+
+	cond, err := ParseCondition(`[{rising:20},{falling:7.5}]`)
+	if err != nil || cond.Children[0].Format() != "Rising(20)" || cond.Children[1].Format() != "Falling(7.5)" {
+		t.Fatalf("parse: %v %v", cond, err)
+	}
+	if got := cond.Children[1].Expression(); got != "falling(>7.5/h)" {
+		t.Errorf("expression: got %s", got)
+	}
+	if err := (Condition{Kind: CondRising, Delta: -1}).Validate(); err == nil {
+		t.Error("a negative threshold should be rejected")
+	}
+}
+
+func TestTrend_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{trend:{hours:4,direction:"down",by:12.5}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	if c.Format() != "Trend { hours: 4, direction: Down, by: 12.5 }" {
+		t.Fatalf("got %s", c.Format())
+	}
+	b, _ := json.Marshal(c)
+	var back Condition
+	if err := json.Unmarshal(b, &back); err != nil || !back.Equal(c) {
+		t.Fatalf("round trip: %s %v", b, err)
+	}
+	if _, err := ParseCondition(`[{trend:{hours:4,direction:"sideways",by:1}}]`); err == nil {
+		t.Fatal("unknown direction should fail")
+	}
+}
+
+// quarterCtx is a single 96-slot day with the current slot at nowIdx.
+func quarterCtx(prices []float32, nowIdx int) *EvaluateContext {
+	start, _ := time.Parse("2006-01-02 15:04:05", "2025-02-16 00:00:00")
+	return NewEvaluateContext(start.Add(time.Duration(nowIdx)*15*time.Minute), prices, nowIdx)
+}
+
+func TestCheapQuarters_RanksQuarterSlots(t *testing.T) {
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 100
+	}
+	// Inside 10:00-11:00 only 10:15 and 10:45 are cheap.
+	prices[41] = 10
+	prices[43] = 20
+	cond := Condition{Kind: CondCheapQuarters, Quarters: CheapQuarters{Count: 2, From: 40, To: 44}}
+
+	for idx, want := range map[int]bool{40: false, 41: true, 42: false, 43: true, 44: false, 30: false} {
+		if got := cond.Evaluate(quarterCtx(prices, idx)); got != want {
+			t.Errorf("slot %s: got %v want %v", quarterTime(uint8(idx)), got, want)
+		}
+	}
+}
+
+func TestCheapQuarters_JSONRoundTrip(t *testing.T) {
+	cond, err := ParseCondition(`[{cheap_quarters:{count:6,from:"22:45",to:"24:00"}}]`)
+	if err != nil {
+		t.Fatalf("parse: %v", err)
+	}
+	c := cond.Children[0]
+	if c.Quarters != (CheapQuarters{Count: 6, From: 91, To: 96}) {
+		t.Fatalf("got %+v", c.Quarters)
+	}
+	b, _ := json.Marshal(c)
+	if string(b) != `{"cheap_quarters":{"count":6,"from":"22:45","to":"24:00"}}` {
+		t.Fatalf("marshal: %s", b)
+	}
+	for _, bad := range []string{`"10:10"`, `"25:00"`, `"9:00"`} {
+		if _, err := ParseCondition(`[{cheap_quarters:{count:1,from:` + bad + `,to:"12:00"}}]`); err == nil {
+			t.Errorf("from %s should be rejected", bad)
+		}
+	}
+}
+
+func TestCheap_CountsHoursInQuarterSlots(t *testing.T) {
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 100
+	}
+	// 02:00-03:00 is the cheapest hour of the night, 03:00-03:30 the runner-up.
+	for i := 8; i < 14; i++ {
+		prices[i] = 10
+		if i >= 12 {
+			prices[i] = 20
+		}
+	}
+	cond := Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 1, From: 0, To: 6}}
+	for idx, want := range map[int]bool{8: true, 11: true, 12: false, 14: false, 7: false} {
+		ctx := quarterCtx(prices, idx)
+		ctx.SlotsPerHour = 4
+		if got := cond.Evaluate(ctx); got != want {
+			t.Errorf("slot %s: got %v want %v", quarterTime(uint8(idx)), got, want)
+		}
+	}
+}
+
+func TestEvaluateAll_StepsByQuarter(t *testing.T) {
+	ctx := quarterCtx(make([]float32, 96), 0)
+	ctx.SlotsPerHour = 4
+	got := Condition{Kind: CondHours, HoursMin: 1, HoursMax: 2}.EvaluateAll(ctx)
+	for i, v := range got {
+		if want := i >= 4 && i < 12; v != want {
+			t.Errorf("slot %s: got %v want %v", quarterTime(uint8(i)), v, want)
+		}
+	}
+}
+
+// randomCondition builds an arbitrary condition tree of at most depth levels
+// whose fields stay within what the JSON form can express.
+func randomCondition(r *rand.Rand, depth int) Condition {
+	kinds := []ConditionKind{CondPrice, CondHours, CondCheap, CondTariff, CondDailyBudget,
+		CondFirstCheapHour, CondOffPeakCheapest, CondTrend, CondCheapQuarters, CondPriceAbove, CondBelowAverage, CondExpensive, CondDayOfWeek, CondCheapestBlock, CondPercentile, CondTimeRange, CondCheapestAhead, CondRising, CondFalling, CondDebug}
+	if depth > 0 {
+		kinds = append(kinds, CondAnd, CondOr, CondExactlyOne, CondAtLeast, CondNot, CondComment)
+	}
+	cheap := func() CheapCondition {
+		return CheapCondition{Hours: uint8(r.IntN(25)), From: uint8(r.IntN(24)), To: uint8(r.IntN(25))}
+	}
+	switch k := kinds[r.IntN(len(kinds))]; k {
+	case CondAnd, CondOr, CondExactlyOne, CondAtLeast:
+		c := Condition{Kind: k}
+		for range r.IntN(4) {
+			c.Children = append(c.Children, randomCondition(r, depth-1))
+		}
+		if k == CondAtLeast {
+			c.AtLeast = uint8(r.IntN(len(c.Children) + 1))
+		}
+		return c
+	case CondNot:
+		inner := randomCondition(r, depth-1)
+		return Condition{Kind: k, Inner: &inner}
+	case CondComment:
+		inner := randomCondition(r, depth-1)
+		notes := []string{"", "plain", `quote " and \ slash`, "<html> & ünïcode", "tab\tnewline\n"}
+		return Condition{Kind: k, Note: notes[r.IntN(len(notes))], Inner: &inner}
+	case CondPrice, CondPriceAbove:
+		return Condition{Kind: k, Price: float32(r.NormFloat64() * 100)}
+	case CondHours:
+		return Condition{Kind: k, HoursMin: uint32(r.IntN(24)), HoursMax: uint32(r.IntN(24))}
+	case CondTimeRange:
+		return Condition{Kind: k, TimeRange: TimeRange{From: uint16(r.IntN(24 * 60)), To: uint16(r.IntN(24*60 + 1))}}
+	case CondCheap, CondFirstCheapHour, CondCheapestBlock:
+		return Condition{Kind: k, Cheap: cheap()}
+	case CondExpensive:
+		return Condition{Kind: k, Expensive: ExpensiveCondition(cheap())}
+	case CondPercentile:
+		return Condition{Kind: k, Percentile: PercentileCondition{Value: uint8(r.IntN(101)), From: uint8(r.IntN(24)), To: uint8(r.IntN(25))}}
+	case CondCheapestAhead:
+		return Condition{Kind: k, Ahead: CheapestAhead{Hours: uint8(r.IntN(49)), Horizon: uint8(r.IntN(49))}}
+	case CondRising, CondFalling:
+		return Condition{Kind: k, Delta: float32(r.Float64() * 50)}
+	case CondDayOfWeek:
+		return Condition{Kind: k, Days: r.Perm(7)[:1+r.IntN(7)]}
+	case CondTariff:
+		return Condition{Kind: k, High: r.IntN(2) == 0}
+	case CondDailyBudget, CondOffPeakCheapest:
+		return Condition{Kind: k, Budget: uint8(r.IntN(25))}
+	case CondTrend:
+		return Condition{Kind: k, Trend: TrendCondition{Hours: uint8(r.IntN(25)), Rising: r.IntN(2) == 0, By: float32(r.Float64() * 50)}}
+	case CondBelowAverage:
+		return Condition{Kind: k, Factor: float32(r.Float64() * 2)}
+	case CondCheapQuarters:
+		return Condition{Kind: k, Quarters: CheapQuarters{Count: uint8(r.IntN(97)), From: uint8(r.IntN(96)), To: uint8(r.IntN(97))}}
+	default:
+		return Condition{Kind: CondDebug, Debug: r.IntN(2) == 0}
+	}
+}
+
+func TestCondition_Property_SerializeParseStable(t *testing.T) {
+	r := rand.New(rand.NewPCG(1, 2))
+	for i := range 2000 {
+		c := randomCondition(r, 4)
+		b, err := json.Marshal(c)
+		if err != nil {
+			t.Fatalf("case %d: marshal %s: %v", i, c.Format(), err)
+		}
+		// ParseCondition takes a list and wraps it in And.
+		parsed, err := ParseCondition("[" + string(b) + "]")
+		if err != nil {
+			t.Fatalf("case %d: parse %s: %v", i, b, err)
+		}
+		if len(parsed.Children) != 1 || !parsed.Children[0].Equal(c) {
+			t.Fatalf("case %d: round trip changed\n got %s\nwant %s", i, parsed.Format(), c.Format())
+		}
+	}
+}
+
+func TestCondition_Property_EvaluateNeverPanics(t *testing.T) {
+	r := rand.New(rand.NewPCG(3, 4))
+	start, _ := time.Parse("2006-01-02 15:04:05", "2025-03-30 00:00:00")
+	dist := DefaultDistribution()
+	for i := range 500 {
+		c := randomCondition(r, 4)
+		prices := make([]float32, 1+r.IntN(300))
+		for j := range prices {
+			prices[j] = float32(r.NormFloat64() * 80)
+		}
+		nowIdx := r.IntN(len(prices))
+		ctx := NewEvaluateContext(start.Add(time.Duration(nowIdx)*time.Hour), prices, nowIdx)
+		if r.IntN(2) == 0 {
+			ctx.Distribution = &dist
+		}
+		func() {
+			defer func() {
+				if p := recover(); p != nil {
+					t.Fatalf("case %d: %s panicked at index %d of %d: %v", i, c.Format(), nowIdx, len(prices), p)
+				}
+			}()
+			c.Evaluate(ctx)
+			c.EvaluateAll(ctx)
+		}()
+	}
+}
+
+func FuzzParseCondition(f *testing.F) {
+	for _, seed := range []string{
+		`[{price:100},{hours:[0,10]}]`,
+		`[{or:[{cheap:{hours:3,from:22,to:6}},{not:{tariff:"high"}}]}]`,
+		`[{comment:{note:"x",inner:{trend:{hours:2,direction:"up",by:5}}}}]`,
+		`[{cheap_quarters:{count:4,from:"10:00",to:"12:00"}},{daily_budget:{hours:4}}]`,
+	} {
+		f.Add(seed)
+	}
+	f.Fuzz(func(t *testing.T, s string) {
+		c, err := ParseCondition(s)
+		if err != nil {
+			return
+		}
+		b, err := json.Marshal(c.Children)
+		if err != nil {
+			t.Fatalf("marshal %s: %v", c.Format(), err)
+		}
+		again, err := ParseCondition(string(b))
+		if err != nil {
+			t.Fatalf("reparse %s: %v", b, err)
+		}
+		if !again.Equal(c) {
+			t.Fatalf("unstable:\n got %s\nwant %s", again.Format(), c.Format())
+		}
+	})
+}
+
+// dstCtx is a single DST day of n ascending quarter prices at slot nowIdx, set
+// up the way ExpressionContext does it.
+func dstCtx(n, nowIdx int) *EvaluateContext {
+	date := "2025-03-30"
+	if n == 100 {
+		date = "2025-10-26"
+	}
+	midnight, _ := time.Parse("2006-01-02", date)
+	hour, minute := SlotClock(nowIdx, n)
+	prices := make([]float32, n)
+	for i := range prices {
+		prices[i] = float32(i)
+	}
+	ctx := NewEvaluateContext(midnight.Add(time.Duration(hour)*time.Hour+time.Duration(minute)*time.Minute), prices, nowIdx)
+	ctx.SlotsPerHour = 4
+	ctx.Prices.DayLens = []int{n}
+	return ctx
+}
+
+func TestEvaluateAll_DSTDays(t *testing.T) {
+	for _, c := range []struct {
+		quarters int
+		cond     Condition
+		from, to int // slots expected to be true
+	}{
+		{92, Condition{Kind: CondHours, HoursMin: 2, HoursMax: 2}, 0, 0},
+		{92, Condition{Kind: CondHours, HoursMin: 3, HoursMax: 3}, 8, 12},
+		{92, Condition{Kind: CondHours, HoursMin: 23, HoursMax: 23}, 88, 92},
+		{100, Condition{Kind: CondHours, HoursMin: 2, HoursMax: 2}, 8, 16},
+		{100, Condition{Kind: CondHours, HoursMin: 23, HoursMax: 23}, 96, 100},
+		// The 02:00-04:00 window has 4 slots in spring, all within the cheapest
+		// 8, and 12 in autumn, of which the first 8 are cheapest.
+		{92, Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 2, From: 2, To: 4}}, 8, 12},
+		{100, Condition{Kind: CondCheap, Cheap: CheapCondition{Hours: 2, From: 2, To: 4}}, 8, 16},
+	} {
+		got := c.cond.EvaluateAll(dstCtx(c.quarters, 0))
+		if len(got) != c.quarters {
+			t.Fatalf("%d quarters: got %d results", c.quarters, len(got))
+		}
+		for i, v := range got {
+			if want := i >= c.from && i < c.to; v != want {
+				t.Errorf("%d quarters, %s, slot %d: got %v want %v", c.quarters, c.cond.Format(), i, v, want)
+			}
+		}
+	}
+}
+
+func TestNextChange(t *testing.T) {
+	results := []bool{false, false, true, true, true, false}
+	cases := []struct {
+		from int
+		want int
+		ok   bool
+	}{
+		{0, 2, true},
+		{1, 2, true},
+		{2, 5, true},
+		{4, 5, true},
+		{5, 0, false},
+	}
+	for _, c := range cases {
+		got, ok := nextChange(results, c.from)
+		if got != c.want || ok != c.ok {
+			t.Errorf("nextChange from %d: got %d, %v; want %d, %v", c.from, got, ok, c.want, c.ok)
+		}
+	}
+	if _, ok := nextChange([]bool{true, true, true}, 0); ok {
+		t.Error("constant results should never change")
+	}
+}
+
+func TestCondition_NextChange(t *testing.T) {
+	ctx := setupCtx()
+
+	flipsOnce := Condition{Kind: CondPrice, Price: 10}
+	at, ok := flipsOnce.NextChange(ctx)
+	if want := time.Date(2020, 1, 1, 11, 0, 0, 0, time.UTC); !ok || !at.Equal(want) {
+		t.Errorf("price<=10: got %v, %v; want %v", at, ok, want)
+	}
+
+	never := Condition{Kind: CondPrice, Price: 100}
+	if at, ok := never.NextChange(ctx); ok {
+		t.Errorf("price<=100 never flips, got %v", at)
+	}
+}
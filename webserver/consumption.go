@@ -31,10 +31,7 @@ type ConsumptionQuarter struct {
 // midnight at the end of that day (not "00:00:00" of the next). Profil +A is
 // average power in kW, so energy in kWh = kW × 0.25.
 func ParseConsumptionCSV(r io.Reader) ([]ConsumptionQuarter, error) {
-	loc, err := time.LoadLocation("Europe/Prague")
-	if err != nil {
-		loc = time.UTC
-	}
+	loc := PragueLocation()
 	cr := csv.NewReader(r)
 	cr.Comma = ';'
 	cr.FieldsPerRecord = -1
@@ -130,10 +127,7 @@ func maxOTEDate(now time.Time, loc *time.Location) time.Time {
 // could have published given `now` are recorded in FutureDates and skipped
 // entirely — no fetch is attempted.
 func (s *AppState) AnalyzeConsumption(quarters []ConsumptionQuarter, now time.Time) (*ConsumptionAnalysis, error) {
-	loc, err := time.LoadLocation("Europe/Prague")
-	if err != nil {
-		loc = time.UTC
-	}
+	loc := PragueLocation()
 	maxDate := maxOTEDate(now, loc)
 
 	byDate := map[string][]ConsumptionQuarter{}
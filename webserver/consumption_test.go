@@ -125,7 +125,7 @@ func TestAnalyze_FlatConsumption_WeightedEqualsFlatAvg(t *testing.T) {
 			Price: float32(i),
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -180,7 +180,7 @@ func TestAnalyze_AllInCheapestQuarter_PerfectScore(t *testing.T) {
 			Price: float32(i), // cheapest at idx 0
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -219,7 +219,7 @@ func TestAnalyze_AllInExpensiveQuarter_ZeroScore(t *testing.T) {
 			Price: float32(i),
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -257,7 +257,7 @@ func TestAnalyze_RearrangementInequality(t *testing.T) {
 		{Ts: day.UTC(), Price: 10},
 		{Ts: day.Add(15 * time.Minute).UTC(), Price: 100},
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -320,7 +320,7 @@ func TestRoute_Consumption_PostRendersAnalysis(t *testing.T) {
 			Price: float32(i),
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 	// Fixture used by AnalyzeConsumption's prefetch (DB hit, but the AppState may
@@ -337,7 +337,7 @@ func TestRoute_Consumption_PostRendersAnalysis(t *testing.T) {
 `
 	body, contentType := buildMultipart(t, "csv", "test.csv", csv)
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodPost, "/consumption", body)
 	req.Header.Set("Content-Type", contentType)
 	rr := httptest.NewRecorder()
@@ -373,7 +373,7 @@ func TestRoute_Consumption_GetRendersUploadForm(t *testing.T) {
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/consumption", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -475,7 +475,7 @@ func TestAnalyze_FutureDates_AreFilteredWithoutFetch(t *testing.T) {
 			Price: 50.0,
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -540,7 +540,7 @@ func TestAnalyze_AfterPublishHour_TomorrowAllowed_DayAfterStillFuture(t *testing
 				Price: 50.0,
 			}
 		}
-		if err := state.db.SaveQuarters(qs); err != nil {
+		if err := state.db.SaveQuartersAt(qs, state.Clock.Now()); err != nil {
 			t.Fatalf("seed: %v", err)
 		}
 	}
@@ -205,6 +205,12 @@ func ruleBody(class string) (string, string, bool) {
 		}
 		return "", "", false
 	}
+	if rest, ok := stripPrefix(class, "stroke-"); ok {
+		if hex, ok := parseColor(rest); ok {
+			return "", "stroke:" + hex, true
+		}
+		return "", "", false
+	}
 	if rest, ok := stripPrefix(class, "text-"); ok {
 		if hex, ok := parseColor(rest); ok {
 			return "", "color:" + hex, true
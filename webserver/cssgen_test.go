@@ -81,6 +81,12 @@ func TestFillWithShade(t *testing.T) {
 	}
 }
 
+func TestStrokeWithShade(t *testing.T) {
+	if got := one("stroke-gray-500"); got != ".stroke-gray-500{stroke:#6b7280}" {
+		t.Fatalf("got %q", got)
+	}
+}
+
 // The consumption-analysis score gradient uses yellow and orange shades —
 // without them the layout renders unstyled and the css_gen log fills with
 // "unknown class" lines.
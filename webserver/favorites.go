@@ -0,0 +1,48 @@
+package webserver
+
+import (
+	"encoding/json"
+	"fmt"
+	"net/url"
+)
+
+// Favorite is a named expression listed on /optimizer with its current
+// decision.
+type Favorite struct {
+	Name      string
+	Exp       string
+	Condition Condition
+}
+
+// ParseFavorites reads the OTE_FAVORITES format, a JSON array of
+// {"name": ..., "exp": ...} objects. Every expression is parsed and validated
+// up front so a typo fails at startup rather than on each page view.
+func ParseFavorites(s string) ([]Favorite, error) {
+	var raw []struct {
+		Name string `json:"name"`
+		Exp  string `json:"exp"`
+	}
+	if err := json.Unmarshal([]byte(s), &raw); err != nil {
+		return nil, fmt.Errorf("favorites: %w", err)
+	}
+	out := make([]Favorite, 0, len(raw))
+	for _, f := range raw {
+		if f.Name == "" {
+			return nil, fmt.Errorf("favorites: entry with exp %q has no name", f.Exp)
+		}
+		cond, err := ParseCondition(f.Exp)
+		if err == nil {
+			err = cond.Validate()
+		}
+		if err != nil {
+			return nil, fmt.Errorf("favorites: %s: %w", f.Name, err)
+		}
+		out = append(out, Favorite{Name: f.Name, Exp: f.Exp, Condition: cond})
+	}
+	return out, nil
+}
+
+// OptimizerURL links to the optimizer page for this favorite.
+func (f Favorite) OptimizerURL() string {
+	return "/optimizer?exp=" + url.QueryEscape(f.Exp)
+}
@@ -6,6 +6,7 @@ import (
 	"html"
 	"html/template"
 	"math"
+	"sort"
 	"strconv"
 	"strings"
 	"time"
@@ -18,8 +19,19 @@ var templatesFS embed.FS
 
 var layoutTmpl = template.Must(template.ParseFS(templatesFS, "templates/*.html"))
 
+// PageMeta carries the optional link-preview tags of a page.
+type PageMeta struct {
+	Title string
+	Image string // absolute URL of the og:image; empty omits all preview tags
+}
+
 // RenderLayout wraps a page body in the full HTML document and emits the generated CSS.
 func RenderLayout(content string) string {
+	return RenderLayoutMeta(content, PageMeta{})
+}
+
+// RenderLayoutMeta is RenderLayout with OpenGraph/Twitter preview tags.
+func RenderLayoutMeta(content string, meta PageMeta) string {
 	classes := map[string]struct{}{}
 	ExtractClassesFromHTML(content, classes)
 	ExtractClassesFromStr(bodyClasses, classes)
@@ -30,10 +42,12 @@ func RenderLayout(content string) string {
 		CSS         template.CSS
 		BodyClasses string
 		Content     template.HTML
+		Meta        PageMeta
 	}{
 		CSS:         template.CSS(css),
 		BodyClasses: bodyClasses,
 		Content:     template.HTML(content),
+		Meta:        meta,
 	})
 	if err != nil {
 		panic(err)
@@ -41,35 +55,71 @@ func RenderLayout(content string) string {
 	return sb.String()
 }
 
+// PriceFormat controls how prices are printed in table cells and chart text.
+type PriceFormat struct {
+	// Precision is the number of decimal places. In chart labels a negative
+	// value selects the currency default (0 for EUR/MWh, 1 for CZK/kWh).
+	Precision int
+	// Unit is appended after the number, e.g. "€/MWh". Empty omits it.
+	Unit string
+	// Rate overrides CurrencyRate for CZK conversion. Zero keeps the default.
+	Rate float32
+}
+
+// Convert converts an EUR/MWh price into currency using f.Rate if set.
+func (f PriceFormat) Convert(price float32, currency Currency) float32 {
+	if f.Rate > 0 {
+		return currency.ConvertAt(price, f.Rate)
+	}
+	return currency.Convert(price)
+}
+
+// DefaultTableFormat is the table's two-decimal format without a unit.
+func DefaultTableFormat() PriceFormat {
+	return PriceFormat{Precision: 2}
+}
+
 // ChartSettings controls bar chart dimensions.
 type ChartSettings struct {
 	Height     float32
 	BarWidth   int
 	BarSpacing int
+	Labels     PriceFormat // bar price text
 }
 
 func DefaultChartSettings() ChartSettings {
-	return ChartSettings{Height: 300.0, BarWidth: 24, BarSpacing: 1}
+	return ChartSettings{Height: 300.0, BarWidth: 24, BarSpacing: 1, Labels: PriceFormat{Precision: -1}}
 }
 
+// Bounds of the chart size embedders may ask for with ?height= and
+// ?bar_width=.
+const (
+	MinChartHeight = 50
+	MaxChartHeight = 1000
+	MinBarWidth    = 2
+	MaxBarWidth    = 64
+)
+
 type chartMetrics struct {
 	scale      float32
 	zeroOffset float32
 	svgWidth   int
 	svgHeight  float32
+	minPrice   float32
+	maxPrice   float32
 }
 
 func (cs ChartSettings) calculateMetrics(prices []float32) chartMetrics {
-	_, cheapest := CheapestHour(prices)
-	_, expensive := ExpensiveHour(prices)
-
-	var scale, zeroOffset float32
-	if cheapest < 0 {
-		scale = cs.Height / (expensive - cheapest)
-		zeroOffset = 15.0 + expensive*scale
-	} else {
-		scale = cs.Height / expensive
-		zeroOffset = cs.Height + 15.0
+	_, cheapest, ok := CheapestHour(prices)
+	_, expensive, _ := ExpensiveHour(prices)
+
+	// The axis spans zero and every price. With no bars or only zero prices
+	// the span is empty; keep the axis at the bottom rather than dividing by
+	// zero.
+	scale, zeroOffset := float32(0), cs.Height+15.0
+	if top, bottom := max(expensive, 0), min(cheapest, 0); ok && top > bottom {
+		scale = cs.Height / (top - bottom)
+		zeroOffset = 15.0 + top*scale
 	}
 
 	return chartMetrics{
@@ -77,7 +127,53 @@ func (cs ChartSettings) calculateMetrics(prices []float32) chartMetrics {
 		zeroOffset: zeroOffset,
 		svgWidth:   len(prices) * (cs.BarWidth + cs.BarSpacing),
 		svgHeight:  cs.Height + 30.0,
+		minPrice:   cheapest,
+		maxPrice:   expensive,
+	}
+}
+
+// writeGrid draws faint gridlines with their values behind the bars. The step
+// is 1, 2 or 5 times a power of ten in the displayed currency, chosen so about
+// three lines span the price range. Zero is left to writeZeroLine.
+func (cs ChartSettings) writeGrid(sb *strings.Builder, m chartMetrics, currency Currency) {
+	if m.scale == 0 || math.IsInf(float64(m.scale), 0) {
+		return
+	}
+	unit := cs.Labels.Convert(1, currency) // displayed units per EUR/MWh
+	lo, hi := min(m.minPrice, 0)*unit, m.maxPrice*unit
+	step := niceStep((hi - lo) / 3)
+	decimals := max(0, int(math.Ceil(-math.Log10(float64(step)))))
+	for k := math.Ceil(float64(lo / step)); float32(k)*step <= hi; k++ {
+		v := float32(k) * step
+		if v == 0 {
+			continue
+		}
+		y := fmtFloat(m.zeroOffset - v/unit*m.scale)
+		fmt.Fprintf(sb, `<line x1="0" y1="%s" x2="%d" y2="%s" class="stroke-gray-200 dark:stroke-gray-700"></line>`, y, m.svgWidth, y)
+		fmt.Fprintf(sb, `<text x="2" y="%s" class="font-mono text-xs fill-gray-400">%.*f</text>`,
+			fmtFloat(m.zeroOffset-v/unit*m.scale-2), decimals, v)
+	}
+}
+
+// writeZeroLine draws the zero baseline over the bars so negative prices are
+// clearly below it.
+func (cs ChartSettings) writeZeroLine(sb *strings.Builder, m chartMetrics) {
+	y := fmtFloat(m.zeroOffset)
+	fmt.Fprintf(sb, `<line x1="0" y1="%s" x2="%d" y2="%s" class="stroke-gray-500"></line>`, y, m.svgWidth, y)
+}
+
+// niceStep rounds raw up to 1, 2 or 5 times a power of ten.
+func niceStep(raw float32) float32 {
+	if !(raw > 0) {
+		return 1
 	}
+	mag := float32(math.Pow(10, math.Floor(math.Log10(float64(raw)))))
+	for _, m := range []float32{1, 2, 5} {
+		if m*mag >= raw {
+			return m * mag
+		}
+	}
+	return 10 * mag
 }
 
 func (cs ChartSettings) calculateBarX(hour int) int {
@@ -93,7 +189,7 @@ func (cs ChartSettings) calculateBarY(price float32, m chartMetrics) float32 {
 
 func (cs ChartSettings) calculateBarHeight(price float32, m chartMetrics) float32 {
 	v := float32(math.Abs(float64(price))) * m.scale
-	if v < 1.0 {
+	if !(v >= 1.0) { // also catches a NaN price
 		return 1.0
 	}
 	return v
@@ -119,47 +215,230 @@ func (cs ChartSettings) Render(prices []float32, labels []string, color func(ind
 	fmt.Fprintf(&sb, `<svg viewBox="0 0 %d %s" style="max-width:%dpx">`,
 		metrics.svgWidth, fmtFloat(metrics.svgHeight), metrics.svgWidth)
 	sb.WriteString("<g>")
+	cs.writeGrid(&sb, metrics, currency)
 	for hour, price := range prices {
 		cls := color(hour, price)
-		fmt.Fprintf(&sb, `<rect x="%d" y="%s" width="%d" height="%s" class="%s" data-idx="%d"></rect>`,
+		fmt.Fprintf(&sb, `<rect x="%d" y="%s" width="%d" height="%s" class="%s" data-idx="%d"><title>%s</title></rect>`,
 			cs.calculateBarX(hour),
 			fmtFloat(cs.calculateBarY(price, metrics)),
 			cs.BarWidth,
 			fmtFloat(cs.calculateBarHeight(price, metrics)),
 			cls,
 			hour,
+			html.EscapeString(cs.barTitle(hour, len(prices), price, currency)),
 		)
-		var priceStr string
-		if currency == CurrencyCzk {
-			priceStr = fmt.Sprintf("%.1f", currency.Convert(price))
-		} else {
-			priceStr = fmt.Sprintf("%.0f", currency.Convert(price))
-		}
+		priceStr := cs.priceLabel(price, currency)
 		fmt.Fprintf(&sb, `<text x="%d" y="%s" text-anchor="middle" class="font-mono text-xs dark:fill-gray-300">%s</text>`,
 			cs.calculateTextX(hour),
 			fmtFloat(cs.calculatePriceTextY(price, metrics)),
 			html.EscapeString(priceStr),
 		)
-		if labels != nil && hour/4 < len(labels) {
+		if label, ok := slotLabel(labels, hour, len(prices)); ok {
 			fmt.Fprintf(&sb, `<text x="%d" y="%s" text-anchor="middle" class="font-mono text-xs dark:fill-gray-100">%s</text>`,
 				cs.calculateTextX(hour),
 				fmtFloat(cs.calculateLabelTextY(metrics)),
-				html.EscapeString(labels[hour/4]),
+				html.EscapeString(label),
 			)
 		}
 	}
+	cs.writeZeroLine(&sb, metrics)
 	sb.WriteString("</g></svg>")
 	return sb.String()
 }
 
+// slotLabel returns the label of bar idx out of n. labels holds one entry per
+// bar when its length matches n, otherwise one per wall-clock hour of
+// quarter-hour bars, so DST days skip or repeat the 02:00 label.
+func slotLabel(labels []string, idx, n int) (string, bool) {
+	if len(labels) != n {
+		idx, _ = SlotClock(idx, n)
+	}
+	if idx >= len(labels) {
+		return "", false
+	}
+	return labels[idx], true
+}
+
+// calculatePairedBarX returns the x offsets and widths of the left and right
+// bar when two series share one slot.
+func (cs ChartSettings) calculatePairedBarX(hour int) (leftX, leftW, rightX, rightW int) {
+	x := cs.calculateBarX(hour)
+	leftW = cs.BarWidth / 2
+	return x, leftW, x + leftW, cs.BarWidth - leftW
+}
+
+// WeekChartSettings are narrower bars that fit 7×24 hours on a page.
+func WeekChartSettings() ChartSettings {
+	return ChartSettings{Height: 200.0, BarWidth: 6, BarSpacing: 1, Labels: PriceFormat{Precision: -1}}
+}
+
+// RenderWeek draws one bar per wall-clock hour of consecutive days, from each
+// day's ClockHourAverages. A nil day leaves a gap of its width, and each day
+// is labelled with its weekday and date.
+func (cs ChartSettings) RenderWeek(dates []time.Time, days []*DayPrices, currency Currency) string {
+	type dayHours struct {
+		avg [24]float32
+		has [24]bool
+	}
+	hours := make([]*dayHours, len(days))
+	var all []float32
+	for i, d := range days {
+		if d == nil {
+			continue
+		}
+		h := &dayHours{}
+		h.avg, h.has = d.ClockHourAverages()
+		for j, ok := range h.has {
+			if ok {
+				all = append(all, h.avg[j])
+			}
+		}
+		hours[i] = h
+	}
+	metrics := cs.calculateMetrics(all)
+	metrics.svgWidth = len(days) * 24 * (cs.BarWidth + cs.BarSpacing)
+
+	var sb strings.Builder
+	fmt.Fprintf(&sb, `<svg viewBox="0 0 %d %s" style="max-width:%dpx">`,
+		metrics.svgWidth, fmtFloat(metrics.svgHeight), metrics.svgWidth)
+	sb.WriteString("<g>")
+	cs.writeGrid(&sb, metrics, currency)
+	for i, h := range hours {
+		fmt.Fprintf(&sb, `<text x="%d" y="%s" text-anchor="middle" class="font-mono text-xs dark:fill-gray-100">%s</text>`,
+			cs.calculateTextX(i*24+12), fmtFloat(cs.calculateLabelTextY(metrics)), dates[i].Format("Mon 2.1."))
+		if h == nil {
+			continue
+		}
+		for hour, ok := range h.has {
+			if !ok {
+				continue
+			}
+			price := h.avg[hour]
+			cls := "fill-gray-500"
+			if price < 0 {
+				cls = "fill-green-600"
+			}
+			fmt.Fprintf(&sb, `<rect x="%d" y="%s" width="%d" height="%s" class="%s"><title>%s</title></rect>`,
+				cs.calculateBarX(i*24+hour),
+				fmtFloat(cs.calculateBarY(price, metrics)),
+				cs.BarWidth,
+				fmtFloat(cs.calculateBarHeight(price, metrics)),
+				cls,
+				html.EscapeString(fmt.Sprintf("%s %02d:00 — %.2f %s", dates[i].Format("Mon 2006-01-02"), hour,
+					cs.Labels.Convert(price, currency), currency.ShortLabel())),
+			)
+		}
+	}
+	cs.writeZeroLine(&sb, metrics)
+	sb.WriteString("</g></svg>")
+	return sb.String()
+}
+
+// RenderGrouped draws primary and secondary as thinner side-by-side bars in
+// each slot: primary on the left colored by color, secondary on the right
+// filled with secondaryClass. Both share one scale; the price text shows the
+// primary value above the taller bar.
+func (cs ChartSettings) RenderGrouped(primary, secondary []float32, labels []string, color func(index int, price float32) string, secondaryClass string, currency Currency) string {
+	metrics := cs.calculateMetrics(append(append([]float32(nil), primary...), secondary...))
+	metrics.svgWidth = len(primary) * (cs.BarWidth + cs.BarSpacing)
+
+	var sb strings.Builder
+	fmt.Fprintf(&sb, `<svg viewBox="0 0 %d %s" style="max-width:%dpx">`,
+		metrics.svgWidth, fmtFloat(metrics.svgHeight), metrics.svgWidth)
+	sb.WriteString("<g>")
+	cs.writeGrid(&sb, metrics, currency)
+	for hour, price := range primary {
+		second := price
+		if hour < len(secondary) {
+			second = secondary[hour]
+		}
+		leftX, leftW, rightX, rightW := cs.calculatePairedBarX(hour)
+		fmt.Fprintf(&sb, `<rect x="%d" y="%s" width="%d" height="%s" class="%s" data-idx="%d"><title>%s</title></rect>`,
+			leftX, fmtFloat(cs.calculateBarY(price, metrics)), leftW,
+			fmtFloat(cs.calculateBarHeight(price, metrics)), color(hour, price), hour,
+			html.EscapeString(cs.barTitle(hour, len(primary), price, currency)))
+		fmt.Fprintf(&sb, `<rect x="%d" y="%s" width="%d" height="%s" class="%s"><title>%s</title></rect>`,
+			rightX, fmtFloat(cs.calculateBarY(second, metrics)), rightW,
+			fmtFloat(cs.calculateBarHeight(second, metrics)), secondaryClass,
+			html.EscapeString(cs.barTitle(hour, len(primary), second, currency)))
+		priceStr := cs.priceLabel(price, currency)
+		fmt.Fprintf(&sb, `<text x="%d" y="%s" text-anchor="middle" class="font-mono text-xs dark:fill-gray-300">%s</text>`,
+			cs.calculateTextX(hour),
+			fmtFloat(cs.calculatePriceTextY(max(price, second), metrics)),
+			html.EscapeString(priceStr),
+		)
+		if label, ok := slotLabel(labels, hour, len(primary)); ok {
+			fmt.Fprintf(&sb, `<text x="%d" y="%s" text-anchor="middle" class="font-mono text-xs dark:fill-gray-100">%s</text>`,
+				cs.calculateTextX(hour),
+				fmtFloat(cs.calculateLabelTextY(metrics)),
+				html.EscapeString(label),
+			)
+		}
+	}
+	cs.writeZeroLine(&sb, metrics)
+	sb.WriteString("</g></svg>")
+	return sb.String()
+}
+
+// barTitle is the native tooltip of quarter bar idx out of n: the quarter's
+// time range and the price to two decimals. Bars past the first day wrap
+// around midnight.
+func (cs ChartSettings) barTitle(idx, n int, price float32, currency Currency) string {
+	hour, minute := SlotClock(idx, n)
+	return fmt.Sprintf("%02d:%02d–%02d:%02d — %.2f %s", hour%24, minute, hour%24, minute+14,
+		cs.Labels.Convert(price, currency), currency.ShortLabel())
+}
+
+// priceLabel formats the text drawn above a bar.
+func (cs ChartSettings) priceLabel(price float32, currency Currency) string {
+	precision := cs.Labels.Precision
+	if precision < 0 {
+		precision = 0
+		if currency == CurrencyCzk {
+			precision = 1
+		}
+	}
+	s := fmt.Sprintf("%.*f", precision, cs.Labels.Convert(price, currency))
+	if cs.Labels.Unit != "" {
+		s += " " + cs.Labels.Unit
+	}
+	return s
+}
+
 func fmtFloat(f float32) string {
 	// Print without trailing zeros, mimicking Rust's f32 Display.
 	s := fmt.Sprintf("%g", f)
 	return s
 }
 
-// EvaluateAllInChart renders a chart visualizing condition results across the context.
-func (c Condition) EvaluateAllInChart(ctx *EvaluateContext) string {
+// ChartPalette selects the bar fills used for true/false condition results.
+type ChartPalette int
+
+const (
+	PaletteDefault    ChartPalette = iota // green/red
+	PaletteColorBlind                     // blue/orange, distinguishable with red-green color blindness
+)
+
+// ParsePalette maps the ?palette= query value to a ChartPalette. Unknown
+// values fall back to the default palette.
+func ParsePalette(s string) ChartPalette {
+	if s == "cb" {
+		return PaletteColorBlind
+	}
+	return PaletteDefault
+}
+
+func (p ChartPalette) fills() (match, miss string) {
+	if p == PaletteColorBlind {
+		return "fill-blue-600", "fill-orange-500"
+	}
+	return "fill-green-600", "fill-red-600"
+}
+
+// EvaluateAllInChart renders a chart visualizing condition results across the
+// context. Bars are labelled T/F in addition to the palette colors so the
+// result never depends on color alone.
+func (c Condition) EvaluateAllInChart(ctx *EvaluateContext, palette ChartPalette) string {
 	results := c.EvaluateAll(ctx)
 	labels := make([]string, len(results))
 	for i, r := range results {
@@ -169,20 +448,51 @@ func (c Condition) EvaluateAllInChart(ctx *EvaluateContext) string {
 			labels[i] = "F"
 		}
 	}
+	match, miss := palette.fills()
 	chart := DefaultChartSettings()
 	return chart.Render(ctx.Prices.Prices, labels, func(index int, _ float32) string {
 		if results[index] {
-			return "fill-green-600"
+			return match
 		}
-		return "fill-red-600"
+		return miss
 	}, CurrencyEur)
 }
 
-func formatPrice(price float32, currency Currency) string {
-	s := fmt.Sprintf("%.2f", currency.Convert(price))
-	idx := strings.Index(s, ".")
-	return fmt.Sprintf(`%s<span class="text-neutral-500 text-sm">.%s</span>`,
-		s[:idx], s[idx+1:])
+func formatPrice(price float32, currency Currency, f PriceFormat) string {
+	s := fmt.Sprintf("%.*f", max(f.Precision, 0), f.Convert(price, currency))
+	// A tiny negative price rounds to zero; show it without the sign.
+	if strings.Trim(s, "-0.") == "" {
+		s = strings.TrimPrefix(s, "-")
+	}
+	if idx := strings.Index(s, "."); idx >= 0 {
+		s = fmt.Sprintf(`%s<span class="text-neutral-500 text-sm">.%s</span>`, s[:idx], s[idx+1:])
+	}
+	if f.Unit != "" {
+		s += " " + html.EscapeString(f.Unit)
+	}
+	return s
+}
+
+// NegativeBanner summarizes the quarters priced below zero, or returns ""
+// when there are none.
+func NegativeBanner(prices []float32, currency Currency, f PriceFormat) string {
+	count := 0
+	var lowest float32
+	for _, p := range prices {
+		if p < 0 {
+			count++
+			lowest = min(lowest, p)
+		}
+	}
+	if count == 0 {
+		return ""
+	}
+	noun := "quarter-hours"
+	if count == 1 {
+		noun = "quarter-hour"
+	}
+	return fmt.Sprintf(`<p class="mb-2 text-green-700 dark:text-green-400">Negative prices in %d %s, lowest %s %s</p>`,
+		count, noun, formatPrice(lowest, currency, f), html.EscapeString(currency.ShortLabel()))
 }
 
 // Link returns an anchor tag with the underline+hover style.
@@ -192,9 +502,10 @@ func Link(url, text string) string {
 }
 
 // RenderTable renders the 24x4 table of quarter-hour prices for a single day.
+// Quarters of a partial day that OTE has not published yet are shown as pending.
 // Active-hour highlighting is applied client-side by the page script so it stays
 // in sync with the wall clock as time passes.
-func (d *DayPrices) RenderTable(dist *Distribution, currency Currency, includeDist bool) string {
+func (d *DayPrices) RenderTable(dist *Distribution, currency Currency, includeDist bool, f PriceFormat) string {
 	totalPrices := d.TotalPrices(dist)
 	var displayPrices []float32
 	if includeDist {
@@ -203,35 +514,29 @@ func (d *DayPrices) RenderTable(dist *Distribution, currency Currency, includeDi
 		displayPrices = d.Prices
 	}
 
-	minIdx, _ := CheapestHour(displayPrices)
-	maxIdx, _ := ExpensiveHour(displayPrices)
+	minIdx, _, _ := CheapestHour(displayPrices)
+	maxIdx, _, _ := ExpensiveHour(displayPrices)
 
 	var sb strings.Builder
-	hours := (len(displayPrices) + 3) / 4
+	total := len(displayPrices) + d.Pending
+	hours := (total + 3) / 4
 	sb.WriteString("<table>")
 	sb.WriteString(`<tr><th class="text-right px-4">Hour</th><th class="px-4">:00</th><th class="px-4">:15</th><th class="px-4">:30</th><th class="px-4">:45</th></tr>`)
 	for hour := 0; hour < hours; hour++ {
 		sb.WriteString("<tr>")
-		fmt.Fprintf(&sb, `<td class="text-right font-mono font-bold px-4">%d</td>`, hour)
+		label, _ := SlotClock(hour*4, total)
+		fmt.Fprintf(&sb, `<td class="text-right font-mono font-bold px-4">%d</td>`, label)
 		for q := 0; q < 4; q++ {
 			idx := hour*4 + q
-			if idx >= len(displayPrices) {
+			if idx >= total {
 				sb.WriteString(`<td></td>`)
 				continue
 			}
-			price := displayPrices[idx]
-			classes := []string{"text-right", "font-mono", "px-4"}
-			if idx == minIdx {
-				classes = append(classes, "bg-green-100", "dark:bg-green-900")
-			}
-			if idx == maxIdx {
-				classes = append(classes, "bg-red-100", "dark:bg-red-900")
-			}
-			if price < 0 {
-				classes = append(classes, "text-green-700")
+			if idx >= len(displayPrices) {
+				sb.WriteString(`<td class="text-right font-mono px-4 text-neutral-500">pending</td>`)
+				continue
 			}
-			fmt.Fprintf(&sb, `<td class="%s" data-idx="%d">%s</td>`,
-				strings.Join(classes, " "), idx, formatPrice(price, currency))
+			sb.WriteString(priceCell(idx, displayPrices[idx], minIdx, maxIdx, currency, f))
 		}
 		sb.WriteString("</tr>")
 	}
@@ -239,6 +544,117 @@ func (d *DayPrices) RenderTable(dist *Distribution, currency Currency, includeDi
 	return sb.String()
 }
 
+// RenderTableByPrice lists one row per quarter hour, ascending by the price
+// including distribution (what is actually paid), with the time kept in the
+// first column. Pending quarters follow at the end. Cells keep the data-idx
+// and min/max highlighting of the chronological table.
+func (d *DayPrices) RenderTableByPrice(dist *Distribution, currency Currency, includeDist bool, f PriceFormat) string {
+	totalPrices := d.TotalPrices(dist)
+	displayPrices := d.Prices
+	if includeDist {
+		displayPrices = totalPrices
+	}
+
+	minIdx, _, _ := CheapestHour(displayPrices)
+	maxIdx, _, _ := ExpensiveHour(displayPrices)
+
+	order := make([]int, len(totalPrices))
+	for i := range order {
+		order[i] = i
+	}
+	sort.SliceStable(order, func(a, b int) bool { return totalPrices[order[a]] < totalPrices[order[b]] })
+
+	var sb strings.Builder
+	sb.WriteString("<table>")
+	sb.WriteString(`<tr><th class="text-right px-4">Time</th><th class="px-4">Price</th></tr>`)
+	for _, idx := range order {
+		hour, minute := SlotClock(idx, d.Slots())
+		fmt.Fprintf(&sb, `<tr><td class="text-right font-mono font-bold px-4">%02d:%02d</td>`, hour, minute)
+		sb.WriteString(priceCell(idx, displayPrices[idx], minIdx, maxIdx, currency, f))
+		sb.WriteString("</tr>")
+	}
+	for idx := len(displayPrices); idx < d.Slots(); idx++ {
+		hour, minute := SlotClock(idx, d.Slots())
+		fmt.Fprintf(&sb, `<tr><td class="text-right font-mono font-bold px-4">%02d:%02d</td>`, hour, minute)
+		sb.WriteString(`<td class="text-right font-mono px-4 text-neutral-500">pending</td></tr>`)
+	}
+	sb.WriteString("</table>")
+	return sb.String()
+}
+
+// RenderNow calls out the price of quarter idx, the current one when viewing
+// today: the market price, the total including distribution and whether it is
+// the day's cheapest or most expensive quarter. It is empty when idx has no
+// published price.
+func (d *DayPrices) RenderNow(idx int, dist *Distribution, currency Currency, f PriceFormat) string {
+	if idx < 0 || idx >= len(d.Prices) {
+		return ""
+	}
+	hour, minute := SlotClock(idx, d.Slots())
+	var sb strings.Builder
+	fmt.Fprintf(&sb, `<p class="text-2xl my-4">Now %02d:%02d: <span class="font-bold">%s</span> %s, with distribution <span class="font-bold">%s</span>`,
+		hour%24, minute, formatPrice(d.Prices[idx], currency, f), html.EscapeString(currency.ShortLabel()),
+		formatPrice(d.TotalPrices(dist)[idx], currency, f))
+	if minIdx, _, _ := CheapestHour(d.Prices); idx == minIdx {
+		sb.WriteString(` <span class="text-green-700 dark:text-green-400">· cheapest of the day</span>`)
+	} else if maxIdx, _, _ := ExpensiveHour(d.Prices); idx == maxIdx {
+		sb.WriteString(` <span class="text-red-700 dark:text-red-400">· most expensive of the day</span>`)
+	}
+	sb.WriteString(`</p>`)
+	return sb.String()
+}
+
+// RenderPriceRank shows where the current price ranks among today's quarter
+// hours, as a sentence and a bar filled by the share it is cheaper than.
+func RenderPriceRank(price, percent float32) string {
+	return fmt.Sprintf(`<div class="my-4"><p>Current price <span class="font-bold">%.2f</span> EUR/MWh is cheaper than <span class="font-bold">%.0f%%</span> of today's quarter hours</p>`+
+		`<div class="w-64 h-2 bg-neutral-200 rounded"><div class="h-2 bg-green-500 rounded" style="width:%.0f%%"></div></div></div>`,
+		price, percent, percent)
+}
+
+// RenderStats is the day's min, average, median and max of the market prices
+// and of the totals including distribution, styled like the table cells.
+func (d *DayPrices) RenderStats(dist *Distribution, currency Currency, f PriceFormat) string {
+	market, ok := NewPriceStats(d.Prices)
+	if !ok {
+		return ""
+	}
+	total, _ := NewPriceStats(d.TotalPrices(dist))
+
+	var sb strings.Builder
+	sb.WriteString(`<table class="mb-4">`)
+	sb.WriteString(`<tr><th></th><th class="px-4">Min</th><th class="px-4">Avg</th><th class="px-4">Median</th><th class="px-4">Max</th></tr>`)
+	for _, row := range []struct {
+		label string
+		stats PriceStats
+	}{{"Market", market}, {"Total", total}} {
+		fmt.Fprintf(&sb, `<tr><th class="text-right px-4">%s</th>`, row.label)
+		for _, v := range []float32{row.stats.Min, row.stats.Avg, row.stats.Median, row.stats.Max} {
+			fmt.Fprintf(&sb, `<td class="text-right font-mono px-4">%s</td>`, formatPrice(v, currency, f))
+		}
+		sb.WriteString("</tr>")
+	}
+	sb.WriteString("</table>")
+	return sb.String()
+}
+
+// priceCell renders one table cell, highlighting the cheapest and most
+// expensive quarter and negative prices.
+func priceCell(idx int, price float32, minIdx, maxIdx int, currency Currency, f PriceFormat) string {
+	classes := []string{"text-right", "font-mono", "px-4"}
+	if idx == minIdx {
+		classes = append(classes, "bg-green-100", "dark:bg-green-900")
+	}
+	if idx == maxIdx {
+		classes = append(classes, "bg-red-100", "dark:bg-red-900")
+	}
+	if price < 0 {
+		classes = append(classes, "text-green-700")
+	}
+	return fmt.Sprintf(`<td class="%s" data-idx="%d">%s</td>`,
+		strings.Join(classes, " "), idx, formatPrice(price, currency, f))
+}
+
 // RenderHTML returns the HTML representation of the condition tree.
 func (c Condition) RenderHTML() string {
 	switch c.Kind {
@@ -262,15 +678,82 @@ func (c Condition) RenderHTML() string {
 		}
 		sb.WriteString("</ul></div>")
 		return sb.String()
+	case CondExactlyOne:
+		var sb strings.Builder
+		sb.WriteString(`<div class="ml-4">EXACTLY ONE<ul>`)
+		for _, child := range c.Children {
+			sb.WriteString("<li>")
+			sb.WriteString(child.RenderHTML())
+			sb.WriteString("</li>")
+		}
+		sb.WriteString("</ul></div>")
+		return sb.String()
+	case CondAtLeast:
+		var sb strings.Builder
+		fmt.Fprintf(&sb, `<div class="ml-4">AT LEAST %d OF<ul>`, c.AtLeast)
+		for _, child := range c.Children {
+			sb.WriteString("<li>")
+			sb.WriteString(child.RenderHTML())
+			sb.WriteString("</li>")
+		}
+		sb.WriteString("</ul></div>")
+		return sb.String()
 	case CondNot:
 		return `<div class="ml-4">NOT` + c.Inner.RenderHTML() + `</div>`
 	case CondPrice:
-		return fmt.Sprintf(`<div class="ml-4">Price: %s</div>`, fmt.Sprintf("%g", c.Price))
+		return fmt.Sprintf(`<div class="ml-4">Price at most: %g</div>`, c.Price)
+	case CondPriceAbove:
+		return fmt.Sprintf(`<div class="ml-4">Price above: %g</div>`, c.Price)
+	case CondBelowAverage:
+		return fmt.Sprintf(`<div class="ml-4">Below average: price under %g%% of the day's mean</div>`, c.Factor*100)
 	case CondHours:
 		return fmt.Sprintf(`<div class="ml-4">Hours: %d - %d</div>`, c.HoursMin, c.HoursMax)
+	case CondTimeRange:
+		return fmt.Sprintf(`<div class="ml-4">Time: %s - %s</div>`, clockTime(c.TimeRange.From), clockTime(c.TimeRange.To))
 	case CondCheap:
 		return fmt.Sprintf(`<div class="ml-4">Cheap: %d cheapiest hours in hours %d - %d</div>`,
 			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondTariff:
+		return fmt.Sprintf(`<div class="ml-4">Tariff: %s distribution hours</div>`, tariffName(c.High))
+	case CondDailyBudget:
+		return fmt.Sprintf(`<div class="ml-4">Daily budget: %d cheapest hours of the day</div>`, c.Budget)
+	case CondCheapQuarters:
+		return fmt.Sprintf(`<div class="ml-4">Cheap quarters: %d cheapest 15-minute slots in %s - %s</div>`,
+			c.Quarters.Count, quarterTime(c.Quarters.From), quarterTime(c.Quarters.To))
+	case CondTrend:
+		verb := "fell"
+		if c.Trend.Rising {
+			verb = "rose"
+		}
+		return fmt.Sprintf(`<div class="ml-4">Trend: price %s by at least %g over the last %d hours</div>`,
+			verb, c.Trend.By, c.Trend.Hours)
+	case CondComment:
+		return fmt.Sprintf(`<div class="ml-4"><span class="italic text-neutral-500">// %s</span>%s</div>`,
+			html.EscapeString(c.Note), c.Inner.RenderHTML())
+	case CondOffPeakCheapest:
+		return fmt.Sprintf(`<div class="ml-4">Off-peak: %d cheapest hours overnight (%02d:00 - %02d:00)</div>`,
+			c.Budget, OffPeakFrom, OffPeakTo)
+	case CondExpensive:
+		return fmt.Sprintf(`<div class="ml-4">Expensive: %d most expensive hours in hours %d - %d</div>`,
+			c.Expensive.Hours, c.Expensive.From, c.Expensive.To)
+	case CondDayOfWeek:
+		return fmt.Sprintf(`<div class="ml-4">Days: %s</div>`, strings.Join(dayNames(c.Days), ", "))
+	case CondCheapestBlock:
+		return fmt.Sprintf(`<div class="ml-4">Cheapest block: %d consecutive hours in hours %d - %d</div>`,
+			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondFirstCheapHour:
+		return fmt.Sprintf(`<div class="ml-4">First of the %d cheapest hours in hours %d - %d</div>`,
+			c.Cheap.Hours, c.Cheap.From, c.Cheap.To)
+	case CondPercentile:
+		return fmt.Sprintf(`<div class="ml-4">Percentile: price at or below the %d%% percentile of hours %d - %d</div>`,
+			c.Percentile.Value, c.Percentile.From, c.Percentile.To)
+	case CondCheapestAhead:
+		return fmt.Sprintf(`<div class="ml-4">Cheapest ahead: among the %d cheapest hours of the next %d hours</div>`,
+			c.Ahead.Hours, c.Ahead.Horizon)
+	case CondRising:
+		return fmt.Sprintf(`<div class="ml-4">Rising: price up by more than %g since an hour ago</div>`, c.Delta)
+	case CondFalling:
+		return fmt.Sprintf(`<div class="ml-4">Falling: price down by more than %g since an hour ago</div>`, c.Delta)
 	}
 	return ""
 }
@@ -360,14 +843,18 @@ func RenderCalendar(year int, month time.Month, loc *time.Location, selectedDate
 	return sb.String()
 }
 
-// RenderCheapForm renders the GET form for editing the Cheap fields.
-func RenderCheapForm(cc *CheapCondition) string {
+// RenderCheapForm renders the GET form for editing the Cheap fields. A
+// non-empty date is kept as a hidden field so the form stays on that day.
+func RenderCheapForm(cc *CheapCondition, date string) string {
 	actual := cc
 	if actual == nil {
 		actual = &CheapCondition{Hours: 1, From: 0, To: 24}
 	}
 	var sb strings.Builder
 	sb.WriteString(`<form method="GET" class="flex space-x-2 items-center">`)
+	if date != "" {
+		fmt.Fprintf(&sb, `<input type="hidden" name="date" value="%s">`, html.EscapeString(date))
+	}
 	sb.WriteString(`<label for="cheap_hours">Cheap Hours:</label>`)
 	fmt.Fprintf(&sb, `<input type="number" id="cheap_hours" name="hours" value="%d" min="1" max="24" step="1" class="w-16 p-1 border rounded">`, actual.Hours)
 	sb.WriteString(`<label for="cheap_from">From:</label>`)
@@ -1,6 +1,12 @@
 package webserver
 
-import "testing"
+import (
+	"math"
+	"slices"
+	"strconv"
+	"strings"
+	"testing"
+)
 
 func TestFormatPriceRoundsCarryToNextInteger(t *testing.T) {
 	cases := []struct {
@@ -14,10 +20,13 @@ func TestFormatPriceRoundsCarryToNextInteger(t *testing.T) {
 		{"below carry", 2.99, `2<span class="text-neutral-500 text-sm">.99</span>`},
 		{"zero", 0, `0<span class="text-neutral-500 text-sm">.00</span>`},
 		{"negative no carry", -2.5, `-2<span class="text-neutral-500 text-sm">.50</span>`},
+		{"negative below one", -0.5, `-0<span class="text-neutral-500 text-sm">.50</span>`},
+		{"negative whole", -10.0, `-10<span class="text-neutral-500 text-sm">.00</span>`},
+		{"negative rounds to zero", -0.001, `0<span class="text-neutral-500 text-sm">.00</span>`},
 	}
 	for _, c := range cases {
 		t.Run(c.name, func(t *testing.T) {
-			got := formatPrice(c.price, CurrencyEur)
+			got := formatPrice(c.price, CurrencyEur, DefaultTableFormat())
 			if got != c.want {
 				t.Fatalf("formatPrice(%v): got %q want %q", c.price, got, c.want)
 			}
@@ -25,6 +34,17 @@ func TestFormatPriceRoundsCarryToNextInteger(t *testing.T) {
 	}
 }
 
+func TestNegativeBanner(t *testing.T) {
+	if got := NegativeBanner([]float32{0, 5, 10}, CurrencyEur, DefaultTableFormat()); got != "" {
+		t.Errorf("no negatives: got %q", got)
+	}
+	got := NegativeBanner([]float32{3, -0.5, 8, -12.25, 0}, CurrencyEur, DefaultTableFormat())
+	want := `Negative prices in 2 quarter-hours, lowest -12<span class="text-neutral-500 text-sm">.25</span> EUR/MWh</p>`
+	if !strings.Contains(got, want) {
+		t.Errorf("got %q, want it to contain %q", got, want)
+	}
+}
+
 func TestChartSettingsWithPricesNegativeZeroPositive(t *testing.T) {
 	cs := DefaultChartSettings()
 	prices := []float32{-10, 0, 10}
@@ -55,3 +75,276 @@ func TestChartSettingsWithPricesNegativeZeroPositive(t *testing.T) {
 		t.Fatalf("bar y 10: got %v want 15.0", cs.calculateBarY(10, m))
 	}
 }
+
+func TestRender_ZeroLineAndGridlines(t *testing.T) {
+	cs := DefaultChartSettings()
+	svg := cs.Render([]float32{-10, 0, 10}, nil, func(int, float32) string { return "fill-gray-500" }, CurrencyEur)
+	// zeroOffset is 165 for this set, see TestChartSettingsWithPricesNegativeZeroPositive.
+	if !strings.Contains(svg, `<line x1="0" y1="165" x2="75" y2="165" class="stroke-gray-500"></line>`) {
+		t.Errorf("missing zero line: %s", svg)
+	}
+	// A step of 10 puts gridlines at +10 (y 15) and -10 (y 315).
+	for _, want := range []string{`<line x1="0" y1="15" x2="75" y2="15"`, `<line x1="0" y1="315" x2="75" y2="315"`, `>10</text>`, `>-10</text>`} {
+		if !strings.Contains(svg, want) {
+			t.Errorf("missing %s", want)
+		}
+	}
+
+	for raw, want := range map[float32]float32{6.7: 10, 1.5: 2, 0.4: 0.5, 300: 500} {
+		if got := niceStep(raw); got != want {
+			t.Errorf("niceStep(%v): got %v, want %v", raw, got, want)
+		}
+	}
+}
+
+func TestEvaluateAllInChart_ColorBlindPalette(t *testing.T) {
+	ctx := setupCtx()
+	cond := Condition{Kind: CondHours, HoursMin: 0, HoursMax: 1}
+
+	svg := cond.EvaluateAllInChart(ctx, ParsePalette("cb"))
+	for _, cls := range []string{"fill-blue-600", "fill-orange-500"} {
+		if !strings.Contains(svg, cls) {
+			t.Errorf("color-blind chart missing %s", cls)
+		}
+	}
+	for _, cls := range []string{"fill-green-600", "fill-red-600"} {
+		if strings.Contains(svg, cls) {
+			t.Errorf("color-blind chart should not use %s", cls)
+		}
+	}
+	// T/F labels are kept regardless of palette.
+	if !strings.Contains(svg, ">T</text>") || !strings.Contains(svg, ">F</text>") {
+		t.Error("chart lost its T/F text labels")
+	}
+
+	if svg := cond.EvaluateAllInChart(ctx, ParsePalette("")); !strings.Contains(svg, "fill-green-600") {
+		t.Error("default palette should keep green for matches")
+	}
+}
+
+func TestRenderGrouped_PairedBarPositions(t *testing.T) {
+	cs := DefaultChartSettings() // bar width 24, spacing 1
+	raw := []float32{10, 20}
+	total := []float32{30, 40}
+	svg := cs.RenderGrouped(raw, total, nil, func(int, float32) string { return "fill-gray-500" }, "fill-blue-300", CurrencyEur)
+
+	for _, want := range []string{
+		`<rect x="0" y=`, `<rect x="12" y=`, // slot 0: left half, right half
+		`<rect x="25" y=`, `<rect x="37" y=`, // slot 1 starts after bar + spacing
+	} {
+		if !strings.Contains(svg, want) {
+			t.Errorf("missing %s in %s", want, svg)
+		}
+	}
+	if got := strings.Count(svg, `width="12"`); got != 4 {
+		t.Errorf("expected 4 half-width bars, got %d", got)
+	}
+	if got := strings.Count(svg, "fill-blue-300"); got != 2 {
+		t.Errorf("expected 2 secondary bars, got %d", got)
+	}
+	// Width is per slot, not per bar.
+	if !strings.Contains(svg, `viewBox="0 0 50 `) {
+		t.Errorf("unexpected viewBox in %s", svg)
+	}
+}
+
+func TestFormatPrice_PrecisionAndUnit(t *testing.T) {
+	cases := []struct {
+		precision int
+		unit      string
+		want      string
+	}{
+		{0, "", `88`},
+		{1, "", `87<span class="text-neutral-500 text-sm">.5</span>`},
+		{2, "€/MWh", `87<span class="text-neutral-500 text-sm">.46</span> €/MWh`},
+	}
+	for _, c := range cases {
+		got := formatPrice(87.456, CurrencyEur, PriceFormat{Precision: c.precision, Unit: c.unit})
+		if got != c.want {
+			t.Errorf("precision %d: got %q want %q", c.precision, got, c.want)
+		}
+	}
+}
+
+func TestChartPriceLabel_PrecisionAndUnit(t *testing.T) {
+	cs := DefaultChartSettings()
+	if got := cs.priceLabel(87.456, CurrencyEur); got != "87" {
+		t.Errorf("EUR default: got %q", got)
+	}
+	if got := cs.priceLabel(1000, CurrencyCzk); got != "24.3" {
+		t.Errorf("CZK default: got %q", got)
+	}
+	for precision, want := range map[int]string{0: "87 €/MWh", 1: "87.5 €/MWh", 2: "87.46 €/MWh"} {
+		cs.Labels = PriceFormat{Precision: precision, Unit: CurrencyEur.UnitSuffix()}
+		if got := cs.priceLabel(87.456, CurrencyEur); got != want {
+			t.Errorf("precision %d: got %q want %q", precision, got, want)
+		}
+	}
+	svg := cs.Render([]float32{87.456}, nil, func(int, float32) string { return "fill-gray-500" }, CurrencyEur)
+	if !strings.Contains(svg, ">87.46 €/MWh</text>") {
+		t.Errorf("chart text missing formatted label: %s", svg)
+	}
+}
+
+func TestChartBarTitle_TimeRangeAndExactPrice(t *testing.T) {
+	prices := make([]float32, 96)
+	prices[56] = 87.4321 // 14:00
+	svg := DefaultChartSettings().Render(prices, nil, func(int, float32) string { return "fill-gray-500" }, CurrencyEur)
+	if !strings.Contains(svg, `data-idx="56"><title>14:00–14:14 — 87.43 EUR/MWh</title></rect>`) {
+		t.Errorf("bar 56 missing tooltip: %s", svg)
+	}
+	if !strings.Contains(svg, ">87</text>") {
+		t.Errorf("the bar text should stay rounded")
+	}
+	if n := strings.Count(svg, "<title>"); n != 96 {
+		t.Errorf("got %d tooltips, want 96", n)
+	}
+}
+
+func TestRenderTableByPrice_CheapestFirst(t *testing.T) {
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 100 + float32(i)
+	}
+	prices[10] = 5 // 02:30
+	d := &DayPrices{Prices: prices}
+	dist := DefaultDistribution()
+
+	out := d.RenderTableByPrice(&dist, CurrencyEur, false, DefaultTableFormat())
+	rows := strings.Split(out, "<tr>")
+	// rows[0] is "<table>", rows[1] the header.
+	first := rows[2]
+	if !strings.Contains(first, ">02:30</td>") || !strings.Contains(first, `data-idx="10"`) {
+		t.Fatalf("first row is not the cheapest quarter: %s", first)
+	}
+	if !strings.Contains(first, "bg-green-100") {
+		t.Errorf("cheapest row lost its highlight: %s", first)
+	}
+	if len(rows) != 2+96 {
+		t.Errorf("rows: got %d, want 96", len(rows)-2)
+	}
+	if d.Prices[10] != 5 || d.Prices[0] != 100 {
+		t.Error("sorting mutated DayPrices")
+	}
+}
+
+func TestEmptyPrices_NoPanicOrNaN(t *testing.T) {
+	if idx, _, ok := CheapestHour(nil); ok || idx != -1 {
+		t.Errorf("CheapestHour(nil): got %d, %v", idx, ok)
+	}
+	if idx, _, ok := ExpensiveHour([]float32{}); ok || idx != -1 {
+		t.Errorf("ExpensiveHour(empty): got %d, %v", idx, ok)
+	}
+
+	cs := DefaultChartSettings()
+	m := cs.calculateMetrics(nil)
+	if m.scale != 0 || m.zeroOffset != cs.Height+15 {
+		t.Errorf("metrics: got %+v", m)
+	}
+	if svg := cs.Render(nil, nil, priceBarColor(nil), CurrencyEur); strings.Contains(svg, "NaN") {
+		t.Errorf("empty chart contains NaN: %s", svg)
+	}
+
+	dist := DefaultDistribution()
+	d := &DayPrices{}
+	if out := d.RenderTable(&dist, CurrencyEur, true, DefaultTableFormat()); strings.Contains(out, "data-idx") {
+		t.Errorf("empty table rendered cells: %s", out)
+	}
+	if out := d.RenderTableByPrice(&dist, CurrencyEur, true, DefaultTableFormat()); strings.Contains(out, "data-idx") {
+		t.Errorf("empty sorted table rendered cells: %s", out)
+	}
+}
+
+func TestNaNAndFlatPrices_NoPanicOrNaN(t *testing.T) {
+	nan := float32(math.NaN())
+	prices := []float32{5, nan, -2, 9}
+	if idx, p, ok := CheapestHour(prices); !ok || idx != 2 || p != -2 {
+		t.Errorf("CheapestHour: got %d, %v, %v", idx, p, ok)
+	}
+	if idx, p, ok := ExpensiveHour(prices); !ok || idx != 3 || p != 9 {
+		t.Errorf("ExpensiveHour: got %d, %v, %v", idx, p, ok)
+	}
+	if idx, _, ok := CheapestHour([]float32{nan, nan}); ok || idx != -1 {
+		t.Errorf("CheapestHour(all NaN): got %d, %v", idx, ok)
+	}
+
+	cs := DefaultChartSettings()
+	for _, prices := range [][]float32{prices, {nan}, {0, 0, 0}, {-4, -4}} {
+		if svg := cs.Render(prices, nil, priceBarColor(prices), CurrencyEur); strings.Contains(svg, `="NaN"`) || strings.Contains(svg, "Inf") {
+			t.Errorf("%v: chart has non-finite geometry: %s", prices, svg)
+		}
+	}
+}
+
+func TestRenderStats_KnownDay(t *testing.T) {
+	d := &DayPrices{Prices: make([]float32, 24)}
+	for i := range d.Prices {
+		d.Prices[i] = float32(24 - i) // 24..1, unsorted on purpose
+	}
+	dist := Distribution{HighPrice: 10, LowPrice: 10}
+	out := d.RenderStats(&dist, CurrencyEur, DefaultTableFormat())
+
+	cell := func(v string) string {
+		whole, frac, _ := strings.Cut(v, ".")
+		return `<td class="text-right font-mono px-4">` + whole + `<span class="text-neutral-500 text-sm">.` + frac + `</span></td>`
+	}
+	for _, want := range []string{
+		`<th class="text-right px-4">Market</th>` + cell("1.00") + cell("12.50") + cell("12.50") + cell("24.00"),
+		`<th class="text-right px-4">Total</th>` + cell("11.00") + cell("22.50") + cell("22.50") + cell("34.00"),
+	} {
+		if !strings.Contains(out, want) {
+			t.Errorf("stats are missing %q:\n%s", want, out)
+		}
+	}
+
+	if st, _ := NewPriceStats([]float32{5, 1, 9}); st.Median != 5 {
+		t.Errorf("odd median: got %v, want 5", st.Median)
+	}
+	if _, ok := NewPriceStats(nil); ok {
+		t.Error("empty prices should have no stats")
+	}
+	if out := (&DayPrices{}).RenderStats(&dist, CurrencyEur, DefaultTableFormat()); out != "" {
+		t.Errorf("empty day rendered stats: %s", out)
+	}
+}
+
+func TestRenderTable_DSTDays(t *testing.T) {
+	dist := DefaultDistribution()
+	hoursFrom := func(hours ...int) []string {
+		var out []string
+		for _, h := range hours {
+			out = append(out, strconv.Itoa(h))
+		}
+		for h := 3; h < 24; h++ {
+			out = append(out, strconv.Itoa(h))
+		}
+		return out
+	}
+	for _, c := range []struct {
+		quarters int
+		want     []string
+		at2am    int
+	}{
+		{92, hoursFrom(0, 1), 0},        // spring: 02:00-02:59 does not exist
+		{100, hoursFrom(0, 1, 2, 2), 2}, // autumn: 02:00-02:59 happens twice
+	} {
+		d := &DayPrices{Prices: make([]float32, c.quarters)}
+		out := d.RenderTable(&dist, CurrencyEur, false, DefaultTableFormat())
+		var got []string
+		for _, row := range strings.Split(out, "<tr>")[2:] {
+			label := row[strings.Index(row, ">")+1 : strings.Index(row, "</td>")]
+			got = append(got, label)
+		}
+		if !slices.Equal(got, c.want) {
+			t.Errorf("%d quarters: row labels %v", c.quarters, got)
+		}
+		if n := strings.Count(out, `data-idx="`); n != c.quarters {
+			t.Errorf("%d quarters: got %d cells", c.quarters, n)
+		}
+
+		sorted := d.RenderTableByPrice(&dist, CurrencyEur, false, DefaultTableFormat())
+		if n := strings.Count(sorted, ">02:00</td>"); n != c.at2am {
+			t.Errorf("%d quarters: got %d 02:00 rows in sorted table, want %d", c.quarters, n, c.at2am)
+		}
+	}
+}
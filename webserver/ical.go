@@ -0,0 +1,131 @@
+package webserver
+
+import (
+	"errors"
+	"fmt"
+	"net/http"
+	"strconv"
+	"strings"
+	"time"
+)
+
+// pragueVTimezone defines the TZID the feed's events refer to, with the EU
+// summer time rules.
+const pragueVTimezone = "BEGIN:VTIMEZONE\r\n" +
+	"TZID:Europe/Prague\r\n" +
+	"BEGIN:DAYLIGHT\r\n" +
+	"TZOFFSETFROM:+0100\r\n" +
+	"TZOFFSETTO:+0200\r\n" +
+	"TZNAME:CEST\r\n" +
+	"DTSTART:19700329T020000\r\n" +
+	"RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n" +
+	"END:DAYLIGHT\r\n" +
+	"BEGIN:STANDARD\r\n" +
+	"TZOFFSETFROM:+0200\r\n" +
+	"TZOFFSETTO:+0100\r\n" +
+	"TZNAME:CET\r\n" +
+	"DTSTART:19701025T030000\r\n" +
+	"RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n" +
+	"END:STANDARD\r\n" +
+	"END:VTIMEZONE\r\n"
+
+// priceBlock is a run of consecutive matched quarters of one day.
+type priceBlock struct {
+	Start, End time.Time // End is exclusive
+	AvgPrice   float32
+}
+
+// matchedBlocks joins consecutive true results into blocks. day is the Prague
+// midnight results[0] starts at; slot times follow real time so DST days come
+// out right.
+func matchedBlocks(day time.Time, prices []float32, results []bool) []priceBlock {
+	var blocks []priceBlock
+	for i := 0; i < len(results); {
+		if !results[i] {
+			i++
+			continue
+		}
+		j := i
+		var sum float32
+		for ; j < len(results) && results[j]; j++ {
+			sum += prices[j]
+		}
+		blocks = append(blocks, priceBlock{
+			Start:    day.Add(time.Duration(i) * 15 * time.Minute),
+			End:      day.Add(time.Duration(j) * 15 * time.Minute),
+			AvgPrice: sum / float32(j-i),
+		})
+		i = j
+	}
+	return blocks
+}
+
+// writeCheapCalendar renders blocks as a VCALENDAR with one VEVENT each.
+func writeCheapCalendar(sb *strings.Builder, blocks []priceBlock, stamp time.Time) {
+	const local = "20060102T150405"
+	sb.WriteString("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//OTE CR Price Checker//Cheap hours//EN\r\nCALSCALE:GREGORIAN\r\n")
+	sb.WriteString(pragueVTimezone)
+	for _, b := range blocks {
+		sb.WriteString("BEGIN:VEVENT\r\n")
+		fmt.Fprintf(sb, "UID:cheap-%s@ote\r\n", b.Start.UTC().Format("20060102T150405Z"))
+		fmt.Fprintf(sb, "DTSTAMP:%s\r\n", stamp.UTC().Format("20060102T150405Z"))
+		fmt.Fprintf(sb, "DTSTART;TZID=Europe/Prague:%s\r\n", b.Start.Format(local))
+		fmt.Fprintf(sb, "DTEND;TZID=Europe/Prague:%s\r\n", b.End.Format(local))
+		fmt.Fprintf(sb, "SUMMARY:Cheap electricity (%.0f EUR/MWh)\r\n", b.AvgPrice)
+		sb.WriteString("END:VEVENT\r\n")
+	}
+	sb.WriteString("END:VCALENDAR\r\n")
+}
+
+// routeCheapICS serves today's cheap hours as an iCalendar feed calendar apps
+// can subscribe to: ?hours=3&from=0&to=24 select them like the cheap
+// condition does, and each run of matched quarters becomes one event.
+func routeCheapICS(state *AppState, w http.ResponseWriter, r *http.Request) {
+	q := r.URL.Query()
+	cheap := CheapCondition{Hours: 3, From: 0, To: 24}
+	for _, p := range []struct {
+		name string
+		dst  *uint8
+	}{{"hours", &cheap.Hours}, {"from", &cheap.From}, {"to", &cheap.To}} {
+		if v := q.Get(p.name); v != "" {
+			n, err := strconv.ParseUint(v, 10, 8)
+			if err != nil {
+				http.Error(w, fmt.Sprintf("invalid %s: %q", p.name, v), http.StatusBadRequest)
+				return
+			}
+			*p.dst = uint8(n)
+		}
+	}
+	cond := Condition{Kind: CondCheap, Cheap: cheap}
+	if err := cond.Validate(); err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	prices, err := state.LoadPrices(date)
+	if errors.Is(err, ErrNoPrices) {
+		http.Error(w, "no prices for "+date.Format("2006-01-02"), http.StatusNotFound)
+		return
+	} else if err != nil {
+		w.Header().Set("Retry-After", "60")
+		http.Error(w, "prices could not be loaded from OTE", http.StatusServiceUnavailable)
+		return
+	}
+	ctx, start := state.DayContext(date)
+	if ctx == nil {
+		http.Error(w, "no prices for "+date.Format("2006-01-02"), http.StatusNotFound)
+		return
+	}
+	results := cond.EvaluateAll(ctx)
+	results = results[start:min(start+len(prices.Prices), len(results))]
+
+	var sb strings.Builder
+	writeCheapCalendar(&sb, matchedBlocks(date, prices.Prices, results), state.Clock.Now())
+	w.Header().Set("Content-Type", "text/calendar; charset=utf-8")
+	w.Write([]byte(sb.String()))
+}
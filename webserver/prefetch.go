@@ -0,0 +1,86 @@
+package webserver
+
+import (
+	"context"
+	"log"
+	"time"
+)
+
+// PrefetchDelay is how long after NextDayPricesHour the prefetcher asks OTE
+// for tomorrow, leaving the publisher a moment to finish.
+const PrefetchDelay = 5 * time.Minute
+
+// Backoff bounds for retrying a day OTE has not served yet.
+var (
+	prefetchRetryMin = time.Minute
+	prefetchRetryMax = 30 * time.Minute
+)
+
+// RunPrefetcher warms the cache in the background so the first visitor after
+// publication does not wait on OTE. It loads today immediately, tomorrow once
+// it is due, and then sleeps until the next publication. It returns when ctx
+// is cancelled.
+func (s *AppState) RunPrefetcher(ctx context.Context) {
+	loc := PragueLocation()
+	for {
+		now := s.Clock.Now().In(loc)
+		if !s.prefetchPass(ctx, now) {
+			return
+		}
+		select {
+		case <-ctx.Done():
+			return
+		case <-time.After(nextPrefetch(now).Sub(now)):
+		}
+	}
+}
+
+// prefetchPass loads every day due at now, retrying each until it succeeds.
+// It reports false if ctx was cancelled first.
+func (s *AppState) prefetchPass(ctx context.Context, now time.Time) bool {
+	for _, day := range prefetchDays(now) {
+		if !s.prefetchWithRetry(ctx, day) {
+			return false
+		}
+	}
+	return true
+}
+
+// prefetchDays returns today's date, plus tomorrow once its prices are
+// published.
+func prefetchDays(now time.Time) []time.Time {
+	today := time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, now.Location())
+	days := []time.Time{today}
+	if now.Hour() >= NextDayPricesHour {
+		days = append(days, today.AddDate(0, 0, 1))
+	}
+	return days
+}
+
+// nextPrefetch returns the next publication time plus PrefetchDelay that is
+// strictly after now.
+func nextPrefetch(now time.Time) time.Time {
+	at := time.Date(now.Year(), now.Month(), now.Day(), NextDayPricesHour, 0, 0, 0, now.Location()).Add(PrefetchDelay)
+	if !at.After(now) {
+		at = time.Date(now.Year(), now.Month(), now.Day()+1, NextDayPricesHour, 0, 0, 0, now.Location()).Add(PrefetchDelay)
+	}
+	return at
+}
+
+// prefetchWithRetry fetches day through GetPrices with exponential backoff.
+// A partial day counts as a failure so it is retried until complete.
+func (s *AppState) prefetchWithRetry(ctx context.Context, day time.Time) bool {
+	wait := prefetchRetryMin
+	for {
+		if prices, ok := s.GetPrices(day); ok && prices.Pending == 0 {
+			return true
+		}
+		log.Printf("prefetch %s failed, retrying in %v", day.Format("2006-01-02"), wait)
+		select {
+		case <-ctx.Done():
+			return false
+		case <-time.After(wait):
+		}
+		wait = min(wait*2, prefetchRetryMax)
+	}
+}
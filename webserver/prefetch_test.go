@@ -0,0 +1,116 @@
+package webserver
+
+import (
+	"context"
+	"errors"
+	"sync"
+	"testing"
+	"time"
+)
+
+func TestNextPrefetch(t *testing.T) {
+	loc, _ := time.LoadLocation("Europe/Prague")
+	cases := []struct {
+		now, want string
+	}{
+		{"2026-05-10 09:00", "2026-05-10 14:05"},
+		{"2026-05-10 14:04", "2026-05-10 14:05"},
+		{"2026-05-10 14:05", "2026-05-11 14:05"},
+		{"2026-05-10 23:30", "2026-05-11 14:05"},
+	}
+	for _, c := range cases {
+		now, _ := time.ParseInLocation("2006-01-02 15:04", c.now, loc)
+		if got := nextPrefetch(now).Format("2006-01-02 15:04"); got != c.want {
+			t.Errorf("%s: got %s want %s", c.now, got, c.want)
+		}
+	}
+}
+
+func TestPrefetchPass_TomorrowOnlyAfterPublication(t *testing.T) {
+	for _, c := range []struct {
+		now  string
+		want []string
+	}{
+		{"2026-05-10 13:59", []string{"2026-05-10"}},
+		{"2026-05-10 14:05", []string{"2026-05-10", "2026-05-11"}},
+	} {
+		t.Run(c.now, func(t *testing.T) {
+			state := openTestState(t)
+			state.Clock = pragueClock(t, c.now)
+			var mu sync.Mutex
+			var requested []string
+			cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+				mu.Lock()
+				defer mu.Unlock()
+				requested = append(requested, reportDate)
+				return fixedPrices(96), true
+			})
+			defer cleanup()
+
+			if !state.prefetchPass(context.Background(), state.Clock.Now()) {
+				t.Fatal("pass should complete")
+			}
+			if len(requested) != len(c.want) {
+				t.Fatalf("requested %v, want %v", requested, c.want)
+			}
+			for i := range c.want {
+				if requested[i] != c.want[i] {
+					t.Fatalf("requested %v, want %v", requested, c.want)
+				}
+			}
+		})
+	}
+}
+
+func TestPrefetchWithRetry_RetriesUntilPublished(t *testing.T) {
+	prevMin, prevMax := prefetchRetryMin, prefetchRetryMax
+	prefetchRetryMin, prefetchRetryMax = time.Millisecond, 4*time.Millisecond
+	defer func() { prefetchRetryMin, prefetchRetryMax = prevMin, prevMax }()
+
+	state := openTestState(t)
+	calls := 0
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		calls++
+		return fixedPrices(96), calls >= 3 // not published for the first two tries
+	})
+	defer cleanup()
+
+	day := pragueClock(t, "2026-05-11 00:00").T
+	if !state.prefetchWithRetry(context.Background(), day) {
+		t.Fatal("expected prefetch to succeed")
+	}
+	if calls != 3 {
+		t.Errorf("calls: got %d, want 3", calls)
+	}
+}
+
+func TestPrefetchWithRetry_StopsWhenCancelled(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return nil, false })
+	defer cleanup()
+
+	ctx, cancel := context.WithCancel(context.Background())
+	cancel()
+	if state.prefetchWithRetry(ctx, pragueClock(t, "2026-05-11 00:00").T) {
+		t.Error("cancelled prefetch of an unpublished day should report false")
+	}
+}
+
+func TestPrefetchPass_PopulatesCacheForTomorrow(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 14:05")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-05-11": fixedPrices(96)}
+
+	if !state.prefetchPass(context.Background(), state.Clock.Now()) {
+		t.Fatal("pass should complete")
+	}
+	has, err := state.db.HasDay("2026-05-11")
+	if err != nil || !has {
+		t.Fatalf("tomorrow should be stored after the pass: has=%v err=%v", has, err)
+	}
+	// The first visitor is now served from the DB.
+	state.Source = failingSource{errors.New("OTE must not be asked again")}
+	if _, ok := state.GetPrices(pragueClock(t, "2026-05-11 00:00").T); !ok {
+		t.Error("tomorrow should be served from the warmed cache")
+	}
+}
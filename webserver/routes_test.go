@@ -1,15 +1,30 @@
 package webserver
 
 import (
+	"bufio"
+	"bytes"
 	"compress/gzip"
+	"context"
+	"encoding/csv"
 	"encoding/json"
+	"encoding/xml"
+	"errors"
 	"fmt"
+	"html"
+	"image/png"
 	"io"
+	"log"
+	"math"
+	"net"
 	"net/http"
 	"net/http/httptest"
+	"net/url"
+	"os"
 	"path/filepath"
+	"slices"
 	"strconv"
 	"strings"
+	"sync"
 	"testing"
 	"time"
 
@@ -17,30 +32,6 @@ import (
 	"github.com/MichalKalita/ote/storage"
 )
 
-// buildTestHandler wires the same mux + compression that StartWebServer builds,
-// but returns a http.Handler suitable for httptest.NewRecorder. Real handlers,
-// real middleware, real state.
-func buildTestHandler(state *AppState) http.Handler {
-	mux := http.NewServeMux()
-	mux.HandleFunc("/", func(w http.ResponseWriter, r *http.Request) {
-		if r.URL.Path != "/" {
-			http.NotFound(w, r)
-			return
-		}
-		routeGetRoot(state, w, r)
-	})
-	mux.HandleFunc("/optimizer", func(w http.ResponseWriter, r *http.Request) {
-		routeGetOptimizer(state, w, r)
-	})
-	mux.HandleFunc("/opt", func(w http.ResponseWriter, r *http.Request) {
-		routeGetOpt(state, w, r)
-	})
-	mux.HandleFunc("/consumption", func(w http.ResponseWriter, r *http.Request) {
-		routeConsumption(state, w, r)
-	})
-	return compressionMiddleware(mux)
-}
-
 // openTestState opens a real DB in t.TempDir() and wires AppState.
 func openTestState(t *testing.T) *AppState {
 	t.Helper()
@@ -53,6 +44,27 @@ func openTestState(t *testing.T) *AppState {
 	return NewAppState(db)
 }
 
+// fakeSource is a PriceSource serving canned prices by Prague date
+// (YYYY-MM-DD), with no network access. Missing dates fail like OTE does.
+type fakeSource map[string][]float32
+
+func (f fakeSource) Fetch(date time.Time) ([]storage.Quarter, error) {
+	loc, err := time.LoadLocation("Europe/Prague")
+	if err != nil {
+		return nil, err
+	}
+	day := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, loc)
+	prices, ok := f[day.Format("2006-01-02")]
+	if !ok {
+		return nil, dataloader.ErrPriceDataNotFound
+	}
+	quarters := make([]storage.Quarter, len(prices))
+	for i, p := range prices {
+		quarters[i] = storage.Quarter{Ts: day.Add(time.Duration(i) * 15 * time.Minute).UTC(), Price: p}
+	}
+	return quarters, nil
+}
+
 // startOTEFixture spins up a httptest server that responds to OTE-style requests
 // with `quartersFor(reportDate) → price slice`. Returns count of HTTP hits.
 func startOTEFixture(t *testing.T, quartersFor func(reportDate string) ([]float32, bool)) (cleanup func(), hits *int) {
@@ -99,6 +111,16 @@ func fixedPrices(n int) []float32 {
 	return out
 }
 
+// dayLen returns how many quarters OTE publishes for reportDate, so fixtures
+// serving "today" stay valid on DST switch days.
+func dayLen(reportDate string) int {
+	d, err := time.Parse("2006-01-02", reportDate)
+	if err != nil {
+		return 96
+	}
+	return dataloader.QuartersInDay(d)
+}
+
 // readBody decompresses gzip if Content-Encoding said so; the compression
 // middleware always picks gzip when the client advertises it.
 func readBody(t *testing.T, resp *http.Response) string {
@@ -132,7 +154,7 @@ func TestRoute_Root_DayAlreadyInDB_DoesNotFetch(t *testing.T) {
 			Price: float32(i),
 		}
 	}
-	if err := state.db.SaveQuarters(quarters); err != nil {
+	if err := state.db.SaveQuartersAt(quarters, state.Clock.Now()); err != nil {
 		t.Fatalf("seed: %v", err)
 	}
 
@@ -146,7 +168,7 @@ func TestRoute_Root_DayAlreadyInDB_DoesNotFetch(t *testing.T) {
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -169,7 +191,7 @@ func TestRoute_Root_DayNotInDB_FetchesAndPersists(t *testing.T) {
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -225,7 +247,7 @@ func TestRoute_Root_DSTSpringDay_Renders92Quarters_And23HourRows(t *testing.T) {
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-03-29", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -272,7 +294,7 @@ func TestRoute_Root_DSTAutumnDay_Renders100Quarters_And25HourRows(t *testing.T)
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2025-10-26", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -309,7 +331,7 @@ func TestRoute_Root_NormalDay_Renders96Quarters_And24HourRows(t *testing.T) {
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -347,7 +369,7 @@ func TestRoute_Root_FetchFailureReturns404(t *testing.T) {
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -375,12 +397,34 @@ func TestRoute_Root_FetchFailureReturns404(t *testing.T) {
 	}
 }
 
+func TestRoute_Root_BadDateIs400(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = failingSource{errors.New("must not fetch")}
+
+	for target, want := range map[string]string{
+		"/?date=not-a-date": `invalid date &#34;not-a-date&#34;: use the YYYY-MM-DD format, e.g. 2026-05-10`,
+		"/?date=2026-5-10":  "use the YYYY-MM-DD format",
+		"/?date=9999-01-01": "date 9999-01-01 is out of range",
+		"/?date=1900-01-01": "date 1900-01-01 is out of range",
+	} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		if rr.Code != http.StatusBadRequest {
+			t.Errorf("%s: got %d, want 400", target, rr.Code)
+		}
+		if body := readBody(t, rr.Result()); !strings.Contains(body, want) {
+			t.Errorf("%s: body is missing %q", target, want)
+		}
+	}
+}
+
 func TestRoute_Root_UnknownPathReturns404(t *testing.T) {
 	state := openTestState(t)
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/does-not-exist", nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -394,7 +438,7 @@ func TestRoute_Root_CompressionMiddleware_GzipRoundTrip(t *testing.T) {
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
 	req.Header.Set("Accept-Encoding", "gzip")
 	rr := httptest.NewRecorder()
@@ -410,17 +454,40 @@ func TestRoute_Root_CompressionMiddleware_GzipRoundTrip(t *testing.T) {
 	}
 }
 
+func TestRoute_CompressionCoversSVGAndJSON(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+
+	for _, c := range []struct{ target, want string }{
+		{"/chart.svg?date=2026-05-10", "<svg"},
+		{"/api/prices?date=2026-05-10", `"date":"2026-05-10"`},
+	} {
+		req := httptest.NewRequest(http.MethodGet, c.target, nil)
+		req.Header.Set("Accept-Encoding", "gzip")
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, req)
+		if got := rr.Header().Get("Content-Encoding"); got != "gzip" {
+			t.Errorf("%s: Content-Encoding %q, want gzip", c.target, got)
+			continue
+		}
+		if body := readBody(t, rr.Result()); !strings.Contains(body, c.want) {
+			t.Errorf("%s: decompressed body missing %q", c.target, c.want)
+		}
+	}
+}
+
 func TestRoute_Opt_EvaluatesCondition(t *testing.T) {
 	state := openTestState(t)
 
-	// /opt depends on yesterday/today via ExpressionContext (time.Now). We seed
+	// /opt depends on yesterday/today via ExpressionContext (state.Clock). We seed
 	// any date the fixture is asked for.
-	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) {
-		return fixedPrices(96), true
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(dayLen(reportDate)), true
 	})
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	// `[{"price":1000}]` — true if current price ≤ 1000. Fixture prices are 0..95, so true.
 	req := httptest.NewRequest(http.MethodGet, `/opt?exp=[{"price":1000}]`, nil)
 	rr := httptest.NewRecorder()
@@ -440,7 +507,7 @@ func TestRoute_Opt_RejectsMalformedExpressionGracefully(t *testing.T) {
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	req := httptest.NewRequest(http.MethodGet, `/opt?exp=not-json`, nil)
 	rr := httptest.NewRecorder()
 	handler.ServeHTTP(rr, req)
@@ -454,12 +521,89 @@ func TestRoute_Opt_RejectsMalformedExpressionGracefully(t *testing.T) {
 	}
 }
 
+func TestRoute_Opt_BacktestsOnDate(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	// On the seeded day only 03:00 and 20:00 are cheap; today is a ramp.
+	past := make([]float32, 96)
+	for i := range past {
+		past[i] = 100
+		if h := i / 4; h == 3 || h == 20 {
+			past[i] = 1
+		}
+	}
+	state.Source = fakeSource{"2026-05-03": past, "2026-05-10": fixedPrices(96)}
+	exp := "exp=" + url.QueryEscape(`[{"cheap":{"hours":2,"from":0,"to":24}}]`)
+	get := func(path string) *httptest.ResponseRecorder {
+		t.Helper()
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, path, nil))
+		return rr
+	}
+
+	rr := get("/opt?date=2026-05-03&" + exp)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("/opt: status %d: %s", rr.Code, rr.Body.String())
+	}
+	lines := strings.Split(strings.TrimSpace(rr.Body.String()), "\n")
+	if len(lines) != 24 {
+		t.Fatalf("/opt: got %d lines, want 24", len(lines))
+	}
+	for h, line := range lines {
+		if want := fmt.Sprintf("%02d:00 %v", h, h == 3 || h == 20); line != want {
+			t.Errorf("/opt hour %d: got %q, want %q", h, line, want)
+		}
+	}
+
+	rr = get("/optimizer?date=2026-05-03&" + exp)
+	body := readBody(t, rr.Result())
+	for _, want := range []string{
+		`<td class="px-2">03:00</td><td class="px-2">true</td>`,
+		`<td class="px-2">12:00</td><td class="px-2">false</td>`,
+		`<input type="hidden" name="date" value="2026-05-03">`,
+	} {
+		if !strings.Contains(body, want) {
+			t.Errorf("/optimizer: body missing %q", want)
+		}
+	}
+
+	for _, c := range []struct {
+		query string
+		want  int
+	}{{"date=2026-05-04&", http.StatusNotFound}, {"date=yesterday&", http.StatusBadRequest}} {
+		for _, path := range []string{"/opt?", "/optimizer?"} {
+			if rr := get(path + c.query + exp); rr.Code != c.want {
+				t.Errorf("%s%s: status %d, want %d", path, c.query, rr.Code, c.want)
+			}
+		}
+	}
+}
+
+func TestRoute_Opt_RejectsOutOfRangeFields(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(dayLen(reportDate)), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	for _, path := range []string{"/opt", "/optimizer"} {
+		req := httptest.NewRequest(http.MethodGet, path+`?exp=[{"cheap":{"hours":0,"from":0,"to":24}}]`, nil)
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, req)
+		body := readBody(t, rr.Result())
+		if want := "Invalid expression: cheap: hours must be between 1 and 24, got 0"; body != want {
+			t.Errorf("%s: got %q, want %q", path, body, want)
+		}
+	}
+}
+
 func TestRoute_Root_QueryParamsRoundTripIntoLinks(t *testing.T) {
 	state := openTestState(t)
 	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
 	defer cleanup()
 
-	handler := buildTestHandler(state)
+	handler := newHandler(state)
 	// Ask for CZK + distribution on; the rendered HTML must echo these in nav links.
 	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&cur=czk&dist=true", nil)
 	rr := httptest.NewRecorder()
@@ -490,3 +634,2174 @@ func TestRoute_Root_QueryParamsRoundTripIntoLinks(t *testing.T) {
 		t.Errorf("Currency.String() drift: %s", s)
 	}
 }
+
+func TestRoute_Root_ConditionOverlay(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+	handler := newHandler(state)
+
+	get := func(target string) string {
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d", target, rr.Code)
+		}
+		return readBody(t, rr.Result())
+	}
+
+	body := get("/?date=2026-05-10&exp=" + url.QueryEscape(`[{"hours":[0,5]}]`))
+	// Hours 0..5 are quarters 0..23.
+	if !strings.Contains(body, `class="fill-green-600" data-idx="10"`) || !strings.Contains(body, `class="fill-green-600" data-idx="23"`) {
+		t.Errorf("matched quarters should be green")
+	}
+	if !strings.Contains(body, `class="fill-gray-500" data-idx="24"`) || !strings.Contains(body, `class="fill-gray-500" data-idx="95"`) {
+		t.Errorf("unmatched quarters should be gray, including the most expensive one")
+	}
+
+	body = get("/?date=2026-05-10")
+	if !strings.Contains(body, `class="fill-gray-500" data-idx="10"`) || !strings.Contains(body, `class="fill-red-600" data-idx="95"`) {
+		t.Errorf("without exp the chart keeps the cheapest/most expensive colors")
+	}
+
+	body = get("/?date=2026-05-10&exp=" + url.QueryEscape(`[{"cheap":{"hours":0,"from":0,"to":24}}]`))
+	if !strings.Contains(body, "Invalid expression: cheap: hours must be between 1 and 24, got 0") {
+		t.Errorf("an invalid exp should be reported on the page")
+	}
+}
+
+func TestRoute_APIValidate(t *testing.T) {
+	state := openTestState(t)
+	// Validation must not reach OTE; the fixture only counts hits.
+	cleanup, hits := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+	handler := newHandler(state)
+
+	cases := []struct {
+		name      string
+		exp       string
+		valid     bool
+		errSubstr string
+		position  bool
+	}{
+		{"valid", `[{price:120},{hours:[0,10]}]`, true, "", false},
+		{"syntax", `[{price:120`, false, "", true},
+		{"from out of range", `[{cheap:{hours:2,from:25,to:6}}]`, false, "from must be between 0 and 23", false},
+	}
+	for _, c := range cases {
+		t.Run(c.name, func(t *testing.T) {
+			req := httptest.NewRequest(http.MethodGet, "/api/validate?exp="+url.QueryEscape(c.exp), nil)
+			rr := httptest.NewRecorder()
+			handler.ServeHTTP(rr, req)
+
+			if rr.Code != http.StatusOK {
+				t.Fatalf("status: got %d, want 200", rr.Code)
+			}
+			if ct := rr.Header().Get("Content-Type"); ct != "application/json" {
+				t.Errorf("Content-Type: got %q", ct)
+			}
+			var got validateResponse
+			if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+				t.Fatalf("decode: %v", err)
+			}
+			if got.Valid != c.valid {
+				t.Fatalf("valid: got %v want %v (error %q)", got.Valid, c.valid, got.Error)
+			}
+			if !strings.Contains(got.Error, c.errSubstr) {
+				t.Errorf("error: got %q, want substring %q", got.Error, c.errSubstr)
+			}
+			if (got.Position != nil) != c.position {
+				t.Errorf("position present: got %v want %v", got.Position != nil, c.position)
+			}
+		})
+	}
+	if *hits != 0 {
+		t.Errorf("validation triggered %d OTE fetches; expected none", *hits)
+	}
+}
+
+func TestRoute_ChartSVG_StandaloneDocument(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/chart.svg?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if ct := rr.Header().Get("Content-Type"); ct != "image/svg+xml" {
+		t.Errorf("Content-Type: got %q, want image/svg+xml", ct)
+	}
+	body := readBody(t, rr.Result())
+	doc, ok := strings.CutPrefix(body, `<?xml version="1.0" encoding="UTF-8"?>`+"\n")
+	if !ok || !strings.HasPrefix(doc, `<svg xmlns="http://www.w3.org/2000/svg"`) {
+		t.Fatalf("body is not a standalone SVG document: %q", abbreviate(body))
+	}
+	if n := strings.Count(doc, "<rect"); n != 96 {
+		t.Errorf("got %d bars, want 96", n)
+	}
+	if !strings.Contains(doc, ".fill-green-600{") {
+		t.Errorf("bar colors should be styled inside the document")
+	}
+	dec := xml.NewDecoder(strings.NewReader(body))
+	for {
+		if _, err := dec.Token(); err == io.EOF {
+			break
+		} else if err != nil {
+			t.Fatalf("not well-formed XML: %v", err)
+		}
+	}
+}
+
+func TestRoute_ChartSVG_SizeFromQuery(t *testing.T) {
+	state := openTestState(t)
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+	for _, c := range []struct {
+		query, want string
+	}{
+		{"", `width="2400" height="330" viewBox="0 0 2400 330"`},
+		{"&height=400&bar_width=10", `width="1056" height="430" viewBox="0 0 1056 430"`},
+		{"&height=5000&bar_width=1", `width="288" height="1030" viewBox="0 0 288 1030"`}, // clamped
+		{"&height=10&bar_width=500", `width="6240" height="80" viewBox="0 0 6240 80"`},   // clamped
+		{"&height=tall&bar_width=", `width="2400" height="330" viewBox="0 0 2400 330"`}, // ignored
+	} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/chart.svg?date=2026-05-10"+c.query, nil))
+		if body := readBody(t, rr.Result()); !strings.Contains(body, c.want) {
+			t.Errorf("%q: want %s in %q", c.query, c.want, abbreviate(body))
+		}
+	}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&height=200&bar_width=8", nil))
+	if body := readBody(t, rr.Result()); !strings.Contains(body, `<svg viewBox="0 0 864 230"`) {
+		t.Errorf("root chart should follow the size parameters")
+	}
+}
+
+func TestRoute_ChartPNG_ReturnsScaledPNGForKnownDay(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/chart.png?date=2026-05-10&scale=2", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if ct := rr.Header().Get("Content-Type"); ct != "image/png" {
+		t.Errorf("Content-Type: got %q, want image/png", ct)
+	}
+	body := readBody(t, rr.Result())
+	if !strings.HasPrefix(body, "\x89PNG\r\n\x1a\n") {
+		t.Fatalf("body does not start with the PNG signature: %q", abbreviate(body))
+	}
+	img, err := png.Decode(strings.NewReader(body))
+	if err != nil {
+		t.Fatalf("decode PNG: %v", err)
+	}
+	cs := DefaultChartSettings()
+	if got, want := img.Bounds().Dx(), 2*96*(cs.BarWidth+cs.BarSpacing); got != want {
+		t.Errorf("width: got %d, want %d at scale=2", got, want)
+	}
+	// 2026-05-10 is in the past relative to the test run, so it is cacheable.
+	if cc := rr.Header().Get("Cache-Control"); !strings.Contains(cc, "max-age") {
+		t.Errorf("past-day PNG should be cacheable, Cache-Control=%q", cc)
+	}
+}
+
+func TestRoute_Root_PartialDayLenient_RendersPendingAndSkipsCache(t *testing.T) {
+	dataloader.AllowPartialDays = true
+	t.Cleanup(func() { dataloader.AllowPartialDays = false })
+	state := openTestState(t)
+
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		if reportDate == "2026-05-10" {
+			return fixedPrices(20), true // mid-publish: only 20 of 96 quarters
+		}
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	body := readBody(t, rr.Result())
+	if got := countTableCellsByDataIdx(body); got != 20 {
+		t.Errorf("expected 20 published quarter cells, got %d", got)
+	}
+	if got := strings.Count(body, ">pending</td>"); got != 76 {
+		t.Errorf("expected 76 pending cells, got %d", got)
+	}
+	if !strings.Contains(body, "has not published 76 quarter-hours") {
+		t.Error("missing partial-day notice")
+	}
+	// A partial day must not be cached, or the rest would never be fetched.
+	has, err := state.db.HasDay("2026-05-10")
+	if err != nil {
+		t.Fatalf("HasDay: %v", err)
+	}
+	if has {
+		t.Error("partial day was persisted")
+	}
+}
+
+func TestRoute_Root_PartialDayStrict_Returns404(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		if reportDate == "2026-05-10" {
+			return fixedPrices(20), true
+		}
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusNotFound {
+		t.Errorf("strict mode should reject a short series; got %d", rr.Code)
+	}
+}
+
+func TestRoute_APIBestStart_PicksCheapestContiguousRun(t *testing.T) {
+	state := openTestState(t)
+	// Flat 100 with a 2-hour trough at 03:00–05:00, plus a single cheaper
+	// quarter at 20:00 that a 2-hour run cannot benefit from as much.
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) {
+		p := make([]float32, 96)
+		for i := range p {
+			p[i] = 100
+		}
+		for i := 12; i < 20; i++ {
+			p[i] = 10
+		}
+		p[80] = -50
+		return p, true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/best-start?date=2026-05-10&duration=2&from=0&to=24", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200: %s", rr.Code, rr.Body.String())
+	}
+	var got bestStartResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("decode: %v", err)
+	}
+	if got.StartIndex != 12 {
+		t.Errorf("start_index: got %d, want 12 (03:00)", got.StartIndex)
+	}
+	loc, _ := time.LoadLocation("Europe/Prague")
+	if want := time.Date(2026, 5, 10, 3, 0, 0, 0, loc); !got.Start.Equal(want) {
+		t.Errorf("start: got %v, want %v", got.Start, want)
+	}
+	if got.AvgPrice != 10 {
+		t.Errorf("avg_price: got %v, want 10", got.AvgPrice)
+	}
+
+	// Restricting the window to the evening moves the answer next to the dip.
+	req = httptest.NewRequest(http.MethodGet, "/api/best-start?date=2026-05-10&duration=1&from=18&to=24", nil)
+	rr = httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("decode: %v", err)
+	}
+	if got.StartIndex > 80 || got.StartIndex+4 <= 80 {
+		t.Errorf("1h run in 18–24 should cover quarter 80, got start_index %d", got.StartIndex)
+	}
+}
+
+func TestRoute_APIBestStart_RejectsDurationLongerThanWindow(t *testing.T) {
+	state := openTestState(t)
+	cleanup, hits := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/best-start?date=2026-05-10&duration=5&from=20&to=24", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	if rr.Code != http.StatusBadRequest {
+		t.Fatalf("status: got %d, want 400", rr.Code)
+	}
+	if *hits != 0 {
+		t.Error("invalid parameters should be rejected before fetching prices")
+	}
+}
+
+func TestRoute_APIExport_StreamsPerDayWithErrorMarkers(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		if reportDate == "2026-05-11" {
+			return nil, false // upstream failure mid-range
+		}
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	srv := httptest.NewServer(newHandler(state))
+	defer srv.Close()
+	resp, err := http.Get(srv.URL + "/api/export?from=2026-05-10&to=2026-05-12")
+	if err != nil {
+		t.Fatalf("GET: %v", err)
+	}
+	defer resp.Body.Close()
+	if ct := resp.Header.Get("Content-Type"); ct != "application/x-ndjson" {
+		t.Errorf("Content-Type: got %q", ct)
+	}
+
+	// Consume the body line by line as a streaming client would.
+	sc := bufio.NewScanner(resp.Body)
+	sc.Buffer(make([]byte, 64*1024), 1024*1024)
+	var lines []exportLine
+	for sc.Scan() {
+		var l exportLine
+		if err := json.Unmarshal(sc.Bytes(), &l); err != nil {
+			t.Fatalf("line %d is not JSON: %v", len(lines)+1, err)
+		}
+		lines = append(lines, l)
+	}
+	if err := sc.Err(); err != nil {
+		t.Fatalf("scan: %v", err)
+	}
+
+	if len(lines) != 3 {
+		t.Fatalf("expected 3 day lines, got %d", len(lines))
+	}
+	if lines[0].Date != "2026-05-10" || len(lines[0].Prices) != 96 {
+		t.Errorf("first line: %+v", lines[0])
+	}
+	if lines[1].Date != "2026-05-11" || lines[1].Error == "" || lines[1].Prices != nil {
+		t.Errorf("failed day should be an error marker, got %+v", lines[1])
+	}
+	if lines[2].Date != "2026-05-12" || len(lines[2].Prices) != 96 {
+		t.Errorf("stream must continue after an error, got %+v", lines[2])
+	}
+}
+
+func TestRoute_APIExport_RejectsInvertedRange(t *testing.T) {
+	state := openTestState(t)
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/export?from=2026-05-12&to=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusBadRequest {
+		t.Errorf("status: got %d, want 400", rr.Code)
+	}
+}
+
+func TestRoute_APIPrices_ReturnsDay(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/prices?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+	var resp pricesResponse
+	if err := json.NewDecoder(rr.Body).Decode(&resp); err != nil {
+		t.Fatalf("decode: %v", err)
+	}
+	if resp.Date != "2026-05-10" || len(resp.Prices) != 96 || len(resp.TotalPrices) != 96 {
+		t.Fatalf("got date %q, %d prices, %d totals", resp.Date, len(resp.Prices), len(resp.TotalPrices))
+	}
+	if resp.Cheapest != 0 || resp.Expensive != 95 {
+		t.Errorf("indexes: got cheapest %d, expensive %d", resp.Cheapest, resp.Expensive)
+	}
+	// 10:00 is a high-tariff hour in the default distribution.
+	if got := resp.TotalPrices[40]; got < 65.61 || got > 65.63 {
+		t.Errorf("total at 10:00: got %v", got)
+	}
+}
+
+func TestRoute_APIPrices_MissingDay404(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return nil, false
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/prices?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusNotFound {
+		t.Fatalf("status: got %d, want 404", rr.Code)
+	}
+	var resp errorResponse
+	if err := json.NewDecoder(rr.Body).Decode(&resp); err != nil || resp.Error == "" {
+		t.Errorf("error body: %+v, %v", resp, err)
+	}
+}
+
+func TestRoute_Perf_ReportsCacheSize(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	perf := func() perfResponse {
+		t.Helper()
+		start := time.Now()
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/perf", nil))
+		if elapsed := time.Since(start); elapsed > time.Second {
+			t.Errorf("/perf took %v", elapsed)
+		}
+		if rr.Code != http.StatusOK {
+			t.Fatalf("status: got %d", rr.Code)
+		}
+		var resp perfResponse
+		if err := json.NewDecoder(rr.Body).Decode(&resp); err != nil {
+			t.Fatalf("decode: %v", err)
+		}
+		return resp
+	}
+
+	if resp := perf(); resp.CachedDays != 0 || resp.LastFetchMs != nil {
+		t.Errorf("fresh state: got %+v", resp)
+	}
+	loc, _ := time.LoadLocation("Europe/Prague")
+	for _, d := range []int{9, 10} {
+		state.GetPrices(time.Date(2026, 5, d, 0, 0, 0, 0, loc))
+	}
+	resp := perf()
+	if resp.CachedDays != 2 {
+		t.Errorf("cached_days: got %d, want 2", resp.CachedDays)
+	}
+	if resp.LastFetchMs == nil || resp.UptimeSeconds <= 0 {
+		t.Errorf("after fetching: got %+v", resp)
+	}
+}
+
+func TestRoute_Healthz_OKWithoutUpstream(t *testing.T) {
+	state := openTestState(t)
+	cleanup, hits := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/healthz", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+	if body := strings.TrimSpace(rr.Body.String()); body != `{"status":"ok"}` {
+		t.Errorf("body: got %s", body)
+	}
+	if *hits != 0 {
+		t.Errorf("healthz contacted OTE %d times", *hits)
+	}
+}
+
+func TestRoute_Readyz_ReadyOnceTodayIsCached(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	cleanup, hits := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	probe := func() int {
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/readyz", nil))
+		return rr.Code
+	}
+	if code := probe(); code != http.StatusServiceUnavailable {
+		t.Errorf("before caching: got %d, want 503", code)
+	}
+	if *hits != 0 {
+		t.Errorf("readyz contacted OTE %d times", *hits)
+	}
+	state.GetPrices(state.Clock.Now())
+	if code := probe(); code != http.StatusOK {
+		t.Errorf("after caching: got %d, want 200", code)
+	}
+}
+
+func TestRoute_APIPricesCSV_OneRowPerQuarter(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/prices.csv?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+	if ct := rr.Header().Get("Content-Type"); !strings.HasPrefix(ct, "text/csv") {
+		t.Errorf("Content-Type: got %q", ct)
+	}
+	if cd := rr.Header().Get("Content-Disposition"); !strings.Contains(cd, "ote-prices-2026-05-10.csv") {
+		t.Errorf("Content-Disposition: got %q", cd)
+	}
+
+	rows, err := csv.NewReader(rr.Body).ReadAll()
+	if err != nil {
+		t.Fatalf("csv: %v", err)
+	}
+	if strings.Join(rows[0], ",") != "time,market_eur_mwh,total_eur_mwh" {
+		t.Fatalf("header: got %v", rows[0])
+	}
+	if len(rows) != 1+96 {
+		t.Fatalf("expected 96 data rows, got %d", len(rows)-1)
+	}
+	// 10:00 is a high-tariff hour in the default distribution.
+	if got := rows[1+40]; got[0] != "2026-05-10T10:00:00+02:00" || got[1] != "40.00" || got[2] != "65.62" {
+		t.Errorf("10:00 row: got %v", got)
+	}
+}
+
+func TestRoute_ExportCSV_ExactBytes(t *testing.T) {
+	state := openTestState(t)
+	state.Distribution = Distribution{HighHours: []byte{10}, HighPrice: 20, LowPrice: 5}
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		prices := make([]float32, 96)
+		for i := range prices {
+			prices[i] = 10
+		}
+		return prices, true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/export.csv?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+
+	var want strings.Builder
+	want.WriteString("time,market_eur_mwh,total_eur_mwh\n")
+	for i := range 96 {
+		total := "15.00"
+		if i/4 == 10 {
+			total = "30.00"
+		}
+		fmt.Fprintf(&want, "2026-05-10T%02d:%02d:00+02:00,10.00,%s\n", i/4, i%4*15, total)
+	}
+	if got := rr.Body.String(); got != want.String() {
+		t.Errorf("body mismatch:\ngot:\n%s\nwant:\n%s", got, want.String())
+	}
+}
+
+func TestRoute_APIPricesCSV_MissingDay404(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return nil, false
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/api/prices.csv?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusNotFound {
+		t.Errorf("status: got %d, want 404", rr.Code)
+	}
+}
+
+// pragueClock pins state.Clock to the given Prague-local wall time.
+func pragueClock(t *testing.T, s string) FixedClock {
+	t.Helper()
+	loc, err := time.LoadLocation("Europe/Prague")
+	if err != nil {
+		t.Fatalf("LoadLocation: %v", err)
+	}
+	now, err := time.ParseInLocation("2006-01-02 15:04", s, loc)
+	if err != nil {
+		t.Fatalf("parse %q: %v", s, err)
+	}
+	return FixedClock{T: now}
+}
+
+func TestRoute_Root_TomorrowLinkedOnlyAfterPublication(t *testing.T) {
+	for _, c := range []struct {
+		now      string
+		wantLink bool
+	}{
+		{"2026-05-10 13:59", false},
+		{"2026-05-10 14:00", true},
+	} {
+		t.Run(c.now, func(t *testing.T) {
+			state := openTestState(t)
+			state.Clock = pragueClock(t, c.now)
+			cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+				return fixedPrices(dayLen(reportDate)), true
+			})
+			defer cleanup()
+
+			handler := newHandler(state)
+			req := httptest.NewRequest(http.MethodGet, "/", nil)
+			rr := httptest.NewRecorder()
+			handler.ServeHTTP(rr, req)
+			if rr.Code != http.StatusOK {
+				t.Fatalf("status: got %d", rr.Code)
+			}
+			body := readBody(t, rr.Result())
+			if !strings.Contains(body, "OTE prices 2026-05-10") {
+				t.Errorf("root should default to the clock's day")
+			}
+			if got := strings.Contains(body, "date=2026-05-11&amp;"); got != c.wantLink {
+				t.Errorf("tomorrow linked: got %v want %v", got, c.wantLink)
+			}
+		})
+	}
+}
+
+func TestExpressionContext_FetchesTomorrowOnlyAfterPublication(t *testing.T) {
+	for _, c := range []struct {
+		now      string
+		wantDays int
+	}{
+		{"2026-05-10 13:59", 2},
+		{"2026-05-10 14:00", 3},
+	} {
+		t.Run(c.now, func(t *testing.T) {
+			state := openTestState(t)
+			state.Clock = pragueClock(t, c.now)
+			var mu sync.Mutex // days are fetched concurrently
+			var requested []string
+			cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+				mu.Lock()
+				defer mu.Unlock()
+				requested = append(requested, reportDate)
+				return fixedPrices(96), true
+			})
+			defer cleanup()
+
+			ctx := state.ExpressionContext()
+			if ctx == nil {
+				t.Fatal("expected a context")
+			}
+			if len(requested) != c.wantDays {
+				t.Errorf("fetched days: got %v, want %d", requested, c.wantDays)
+			}
+			if len(ctx.Prices.Prices) != c.wantDays*96 {
+				t.Errorf("prices: got %d, want %d", len(ctx.Prices.Prices), c.wantDays*96)
+			}
+		})
+	}
+}
+
+func TestExpressionContext_IndexesCurrentQuarter(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 13:20")
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	ctx := state.ExpressionContext()
+	if ctx == nil {
+		t.Fatal("expected a context")
+	}
+	// Yesterday's 96 quarters come first; 13:20 is quarter 53 of today.
+	if ctx.Prices.NowIndex != 96+53 {
+		t.Errorf("NowIndex: got %d, want %d", ctx.Prices.NowIndex, 96+53)
+	}
+	if ctx.SlotsPerHour != 4 {
+		t.Errorf("SlotsPerHour: got %d, want 4", ctx.SlotsPerHour)
+	}
+}
+
+func TestSetTimeZone_MovesTodayAndCurrentQuarter(t *testing.T) {
+	prev := Location
+	t.Cleanup(func() { Location = prev })
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		prices := fixedPrices(96)
+		if reportDate == "2026-05-11" {
+			prices[2] = 777 // 00:30, the price in force
+		}
+		return prices, true
+	})
+	defer cleanup()
+	// 00:30 in Prague is still 22:30 the day before in UTC. The zone picks
+	// today, but the price in force is the Prague 00:30 one either way.
+	clock := pragueClock(t, "2026-05-11 00:30")
+	req := httptest.NewRequest("GET", "/", nil)
+
+	for _, c := range []struct {
+		tz        string
+		wantToday string
+		wantIndex int
+	}{
+		{DefaultTimeZone, "2026-05-11", 96 + 2},
+		{"UTC", "2026-05-10", 96 + 96 + 2},
+	} {
+		t.Run(c.tz, func(t *testing.T) {
+			if err := SetTimeZone(c.tz); err != nil {
+				t.Fatalf("SetTimeZone: %v", err)
+			}
+			if _, today, _ := requestDate(req, clock); today.Format("2006-01-02") != c.wantToday {
+				t.Errorf("today: got %s, want %s", today.Format("2006-01-02"), c.wantToday)
+			}
+			state := openTestState(t)
+			state.Clock = clock
+			ctx := state.ExpressionContext()
+			if ctx == nil {
+				t.Fatal("expected a context")
+			}
+			if ctx.Prices.NowIndex != c.wantIndex {
+				t.Errorf("NowIndex: got %d, want %d", ctx.Prices.NowIndex, c.wantIndex)
+			} else if p := ctx.Prices.Prices[ctx.Prices.NowIndex]; p != 777 {
+				t.Errorf("price now: got %v, want the 00:30 Prague price 777", p)
+			}
+
+			rr := httptest.NewRecorder()
+			newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/", nil))
+			if body := readBody(t, rr.Result()); strings.Contains(body, "Now 22:30") {
+				t.Errorf("root page headlines the 22:30 price of the day before")
+			}
+		})
+	}
+	if err := SetTimeZone("Mars/Olympus"); err == nil {
+		t.Error("expected an error for an unknown zone")
+	}
+}
+
+func TestGetPrices_RefetchesExpiredCurrentDays(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 15:00")
+	cleanup, hits := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	loc, _ := time.LoadLocation("Europe/Prague")
+	yesterday := time.Date(2026, 5, 9, 0, 0, 0, 0, loc)
+	tomorrow := time.Date(2026, 5, 11, 0, 0, 0, 0, loc)
+	for _, day := range []time.Time{yesterday, tomorrow, yesterday, tomorrow} {
+		if _, ok := state.GetPrices(day); !ok {
+			t.Fatalf("GetPrices(%s) failed", day.Format("2006-01-02"))
+		}
+	}
+	if *hits != 2 {
+		t.Fatalf("fresh days should come from the DB: got %d fetches, want 2", *hits)
+	}
+
+	// Age both days past the TTL.
+	old := state.Clock.Now().Add(-DefaultCacheTTL - time.Minute)
+	for _, day := range []time.Time{yesterday, tomorrow} {
+		quarters, err := state.db.GetDay(state.db.PragueDate(day))
+		if err != nil {
+			t.Fatalf("GetDay: %v", err)
+		}
+		if err := state.db.SaveQuartersAt(quarters, old); err != nil {
+			t.Fatalf("SaveQuartersAt: %v", err)
+		}
+	}
+	state.GetPrices(yesterday)
+	if *hits != 2 {
+		t.Errorf("a past day must not be refetched: got %d fetches", *hits)
+	}
+	state.GetPrices(tomorrow)
+	if *hits != 3 {
+		t.Errorf("an expired future day must be refetched: got %d fetches, want 3", *hits)
+	}
+	state.GetPrices(tomorrow)
+	if *hits != 3 {
+		t.Errorf("the refetch should reset the expiry: got %d fetches", *hits)
+	}
+}
+
+func TestDistributionFromEnv(t *testing.T) {
+	env := func(m map[string]string) func(string) string {
+		return func(k string) string { return m[k] }
+	}
+
+	dist, err := DistributionFromEnv(env(nil))
+	if err != nil {
+		t.Fatalf("defaults: %v", err)
+	}
+	if def := DefaultDistribution(); !slices.Equal(dist.HighHours, def.HighHours) || dist.HighPrice != def.HighPrice || dist.LowPrice != def.LowPrice {
+		t.Errorf("defaults: got %+v, want %+v", dist, def)
+	}
+
+	dist, err = DistributionFromEnv(env(map[string]string{
+		"OTE_DIST_HIGH_HOURS": "7, 8,20",
+		"OTE_DIST_HIGH_CZK":   "1000",
+		"OTE_DIST_LOW_CZK":    "250",
+		"OTE_DIST_RATE":       "25",
+	}))
+	if err != nil {
+		t.Fatalf("custom: %v", err)
+	}
+	if !slices.Equal(dist.HighHours, []byte{7, 8, 20}) || dist.HighPrice != 40 || dist.LowPrice != 10 {
+		t.Errorf("custom: got %+v", dist)
+	}
+
+	for _, bad := range []map[string]string{
+		{"OTE_DIST_HIGH_HOURS": "10,24"},
+		{"OTE_DIST_HIGH_HOURS": "10,,12"},
+		{"OTE_DIST_HIGH_HOURS": "ten"},
+		{"OTE_DIST_HIGH_HOURS": "10,10"},
+		{"OTE_DIST_HIGH_CZK": "-1"},
+		{"OTE_DIST_LOW_CZK": "cheap"},
+		{"OTE_DIST_RATE": "0"},
+	} {
+		if _, err := DistributionFromEnv(env(bad)); err == nil {
+			t.Errorf("%v: expected an error", bad)
+		}
+	}
+}
+
+func TestEurMWhToCzkKWh(t *testing.T) {
+	// 100 EUR/MWh at 25 CZK/EUR is 2500 CZK/MWh, i.e. 2.5 CZK/kWh.
+	if got := EurMWhToCzkKWh(100, 25); got != 2.5 {
+		t.Errorf("got %v, want 2.5", got)
+	}
+	if got := EurMWhToCzkKWh(-40, 25); got != -1 {
+		t.Errorf("negative price: got %v, want -1", got)
+	}
+	if got := CurrencyCzk.ConvertAt(100, 25); got != 2.5 {
+		t.Errorf("ConvertAt czk: got %v, want 2.5", got)
+	}
+	if got := CurrencyEur.ConvertAt(100, 25); got != 100 {
+		t.Errorf("ConvertAt eur: got %v, want 100", got)
+	}
+}
+
+func TestRoute_Root_CurrencyParam(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&currency=czk&dist=true", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+
+	body := readBody(t, rr.Result())
+	if !strings.Contains(body, "CZK/kWh") || !strings.Contains(body, "Change to EUR") {
+		t.Errorf("currency=czk should render CZK prices")
+	}
+}
+
+func TestDayPrices_HourlyAverage(t *testing.T) {
+	d := &DayPrices{Prices: []float32{1, 2, 3, 4, 10, 10, 10, 10, 6, 8}}
+	got := d.HourlyAverage()
+	want := []float32{2.5, 10, 7}
+	if !slices.Equal(got, want) {
+		t.Fatalf("got %v, want %v", got, want)
+	}
+
+	// A full day ramp 0..95 averages to 1.5, 5.5, ... 93.5.
+	got = (&DayPrices{Prices: fixedPrices(96)}).HourlyAverage()
+	if len(got) != 24 {
+		t.Fatalf("full day: got %d hours, want 24", len(got))
+	}
+	for h, v := range got {
+		if want := float32(h*4) + 1.5; v != want {
+			t.Errorf("hour %d: got %v, want %v", h, v, want)
+		}
+	}
+}
+
+func TestCheaperThan_KnownOrdering(t *testing.T) {
+	prices := []float32{30, 10, 20, 40, float32(math.NaN())}
+	for _, c := range []struct {
+		price, want float32
+	}{
+		{10, 75}, {20, 50}, {25, 50}, {40, 0}, {5, 100},
+	} {
+		if got, ok := CheaperThan(prices, c.price); !ok || got != c.want {
+			t.Errorf("price %v: got %v %v, want %v", c.price, got, ok, c.want)
+		}
+	}
+	if _, ok := CheaperThan(nil, 10); ok {
+		t.Error("no prices should not rank")
+	}
+}
+
+func TestRoute_Optimizer_ListsFavoritesWithDecisions(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	favorites, err := ParseFavorites(`[
+		{"name": "Always", "exp": "[{\"price\":1000}]"},
+		{"name": "Never", "exp": "[{\"price\":-1000}]"}
+	]`)
+	if err != nil {
+		t.Fatalf("ParseFavorites: %v", err)
+	}
+	state.Favorites = favorites
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/optimizer", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+	body := readBody(t, rr.Result())
+	for _, want := range []string{
+		`>Always</a> <span class="font-mono">true</span>`,
+		`>Never</a> <span class="font-mono">false</span>`,
+		`href="/optimizer?exp=` + html.EscapeString(url.QueryEscape(`[{"price":1000}]`)) + `"`,
+		// 12:00 costs 48 on the 0..95 ramp; 47 of 96 quarters cost more.
+		`<span class="font-bold">48.00</span> EUR/MWh is cheaper than <span class="font-bold">49%</span>`,
+	} {
+		if !strings.Contains(body, want) {
+			t.Errorf("body missing %q", want)
+		}
+	}
+}
+
+func TestParseFavorites_RejectsInvalidExpression(t *testing.T) {
+	if _, err := ParseFavorites(`[{"name":"Bad","exp":"[{\"hours\":[5,2]}]"}]`); err == nil {
+		t.Error("expected invalid hours to be rejected")
+	}
+	if _, err := ParseFavorites(`[{"exp":"[]"}]`); err == nil {
+		t.Error("expected missing name to be rejected")
+	}
+}
+
+func TestRoute_HTMLPages_SetContentTypeAndStatus(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), reportDate != "2026-05-01"
+	})
+	defer cleanup()
+
+	handler := newHandler(state)
+	for _, c := range []struct {
+		method, path string
+		status       int
+	}{
+		{http.MethodGet, "/", http.StatusOK},
+		{http.MethodGet, "/?date=2026-05-01", http.StatusNotFound},
+		{http.MethodGet, "/optimizer", http.StatusOK},
+		{http.MethodGet, "/consumption", http.StatusOK},
+		{http.MethodPost, "/consumption", http.StatusBadRequest},
+	} {
+		t.Run(c.method+" "+c.path, func(t *testing.T) {
+			req := httptest.NewRequest(c.method, c.path, nil)
+			rr := httptest.NewRecorder()
+			handler.ServeHTTP(rr, req)
+			if rr.Code != c.status {
+				t.Errorf("status: got %d, want %d", rr.Code, c.status)
+			}
+			if ct := rr.Header().Get("Content-Type"); ct != "text/html; charset=utf-8" {
+				t.Errorf("Content-Type: got %q", ct)
+			}
+		})
+	}
+}
+
+func TestRoute_Root_WhatIfOverrides(t *testing.T) {
+	state := openTestState(t)
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(96), true
+	})
+	defer cleanup()
+	handler := newHandler(state)
+
+	// Quarter 0 costs 0 EUR/MWh and 00:00 is a low-tariff hour, so its total is
+	// exactly low_price; converted at rate 20 that is 100*20/1000 = 2 CZK/kWh.
+	req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&dist=true&cur=czk&rate=20&low_price=100&high_price=300", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d", rr.Code)
+	}
+	body := readBody(t, rr.Result())
+	if !strings.Contains(body, `data-idx="0">2<span class="text-neutral-500 text-sm">.00</span>`) {
+		t.Errorf("quarter 0 not priced with overrides")
+	}
+	if state.Distribution.LowPrice == 100 {
+		t.Error("override leaked into the shared distribution")
+	}
+
+	for _, q := range []string{"rate=0", "rate=-5", "high_price=abc", "low_price=NaN", "rate=Inf"} {
+		req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&"+q, nil)
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, req)
+		if rr.Code != http.StatusBadRequest {
+			t.Errorf("%s: status got %d, want 400", q, rr.Code)
+		}
+	}
+}
+
+func TestRoute_CardPNG_ReturnsSocialCardForKnownDay(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-12 09:00")
+	cleanup, _ := startOTEFixture(t, func(string) ([]float32, bool) { return fixedPrices(96), true })
+	defer cleanup()
+
+	handler := newHandler(state)
+	req := httptest.NewRequest(http.MethodGet, "/card.png?date=2026-05-10", nil)
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if ct := rr.Header().Get("Content-Type"); ct != "image/png" {
+		t.Errorf("Content-Type: got %q, want image/png", ct)
+	}
+	img, err := png.Decode(strings.NewReader(readBody(t, rr.Result())))
+	if err != nil {
+		t.Fatalf("decode PNG: %v", err)
+	}
+	if b := img.Bounds(); b.Dx() != CardWidth || b.Dy() != CardHeight {
+		t.Errorf("size: got %v", b)
+	}
+	if cc := rr.Header().Get("Cache-Control"); !strings.Contains(cc, "max-age") {
+		t.Errorf("past-day card should be cacheable, Cache-Control=%q", cc)
+	}
+
+	// The day page advertises the card to link previews, on the public
+	// origin whatever Host the client sent.
+	req = httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
+	req.Host = "evil.example"
+	req.Header.Set("X-Forwarded-Proto", "https")
+	rr = httptest.NewRecorder()
+	handler.ServeHTTP(rr, req)
+	body := readBody(t, rr.Result())
+	if !strings.Contains(body, `<meta property="og:image" content="https://ota.kalita.cz/card.png?date=2026-05-10&amp;cur=eur">`) {
+		t.Errorf("root page is missing the og:image tag on the public origin")
+	}
+}
+
+func TestRoute_Root_MarksCheapestOnlyOnOtherDays(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 15:00")
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		prices := fixedPrices(96)
+		prices[50] = -5
+		return prices, true
+	})
+	defer cleanup()
+	handler := newHandler(state)
+
+	marked := `class="fill-blue-600" data-idx="50"`
+	for _, c := range []struct {
+		path string
+		want bool
+	}{
+		{"/?date=2026-05-11&mark=cheapest", true}, // tomorrow, already published
+		{"/?date=2026-05-09&mark=cheapest", true},
+		{"/?mark=cheapest", false}, // today: the live marker applies
+		{"/?date=2026-05-11", false},
+	} {
+		req := httptest.NewRequest(http.MethodGet, c.path, nil)
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, req)
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d", c.path, rr.Code)
+		}
+		body := readBody(t, rr.Result())
+		if got := strings.Contains(body, marked); got != c.want {
+			t.Errorf("%s: cheapest marked = %v, want %v", c.path, got, c.want)
+		}
+	}
+}
+
+// The marked bar is the given index itself, with no hour offset, and slot 0
+// is markable.
+func TestWithMarker_MarksExactIndex(t *testing.T) {
+	prices := fixedPrices(96)
+	base := func(int, float32) string { return "fill-gray-500" }
+	for _, idx := range []int{0, 1, 56, 95} {
+		color := withMarker(base, idx)
+		for i, p := range prices {
+			want := "fill-gray-500"
+			if i == idx {
+				want = "fill-blue-600"
+			}
+			if got := color(i, p); got != want {
+				t.Fatalf("marker %d: bar %d got %s, want %s", idx, i, got, want)
+			}
+		}
+	}
+	if got := withMarker(base, -1)(0, 0); got != "fill-gray-500" {
+		t.Errorf("a negative index should mark nothing, bar 0 got %s", got)
+	}
+}
+
+func TestGradientBarColor_CheapestGreenPriciestRed(t *testing.T) {
+	prices := []float32{40, 10, 30, 90, 60, 100}
+	color := gradientBarColor(prices)
+	if got := color(1, prices[1]); got != gradientClasses[0] {
+		t.Errorf("cheapest bar: got %s, want %s", got, gradientClasses[0])
+	}
+	if got := color(5, prices[5]); got != gradientClasses[len(gradientClasses)-1] {
+		t.Errorf("priciest bar: got %s, want %s", got, gradientClasses[len(gradientClasses)-1])
+	}
+	if got := color(2, prices[2]); got != "fill-green-400" {
+		t.Errorf("30 of 10..100: got %s, want fill-green-400", got)
+	}
+	if got := gradientBarColor([]float32{7, 7})(0, 7); got != gradientClasses[0] {
+		t.Errorf("flat day: got %s", got)
+	}
+
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/?date=2026-05-10&colors=gradient", nil))
+	body := readBody(t, rr.Result())
+	for _, cls := range gradientClasses {
+		if !strings.Contains(body, `class="`+cls+`" data-idx=`) {
+			t.Errorf("gradient chart has no %s bar", cls)
+		}
+	}
+}
+
+func TestRoutes_NoServerErrorsOnSeededState(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	cleanup, _ := startOTEFixture(t, func(reportDate string) ([]float32, bool) {
+		return fixedPrices(dayLen(reportDate)), true
+	})
+	defer cleanup()
+	if _, ok := state.GetPrices(state.Clock.Now()); !ok {
+		t.Fatal("seeding today's prices failed")
+	}
+
+	handler := newHandler(state)
+	for _, rt := range routes(state) {
+		req := httptest.NewRequest(http.MethodGet, rt.path+"?date=2026-05-10", nil)
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, req)
+		if rr.Code >= 500 {
+			t.Errorf("%s: status %d: %s", rt.path, rr.Code, abbreviate(rr.Body.String()))
+		}
+	}
+}
+
+func TestRoute_Root_RendersPricesFromInjectedSource(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	prices := fixedPrices(96)
+	prices[56] = 87.43
+	state.Source = fakeSource{"2026-05-10": prices}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	body := readBody(t, rr.Result())
+	if !strings.Contains(body, `data-idx="56">87<span class="text-neutral-500 text-sm">.43</span></td>`) {
+		t.Errorf("table is missing the injected 14:00 price")
+	}
+	if !strings.Contains(body, `data-idx="95">95<span class="text-neutral-500 text-sm">.00</span></td>`) {
+		t.Errorf("table is missing the injected 23:45 price")
+	}
+}
+
+// failingSource is a PriceSource for an unreachable OTE.
+type failingSource struct{ err error }
+
+func (f failingSource) Fetch(time.Time) ([]storage.Quarter, error) { return nil, f.err }
+
+func TestRoute_Root_OutageIs503AndMissingDayIs404(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	handler := newHandler(state)
+	get := func() *httptest.ResponseRecorder {
+		rr := httptest.NewRecorder()
+		handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil))
+		return rr
+	}
+
+	state.Source = failingSource{&dataloader.ExhaustedRetriesError{Attempts: 4, Last: errors.New("connection refused")}}
+	rr := get()
+	if rr.Code != http.StatusServiceUnavailable {
+		t.Errorf("outage: got %d, want 503", rr.Code)
+	}
+	if rr.Header().Get("Retry-After") == "" {
+		t.Error("outage: missing Retry-After")
+	}
+	if body := readBody(t, rr.Result()); !strings.Contains(body, "try again in a minute") {
+		t.Error("outage: missing retry hint")
+	}
+
+	state.Source = fakeSource{}
+	rr = get()
+	if rr.Code != http.StatusNotFound {
+		t.Errorf("unpublished day: got %d, want 404", rr.Code)
+	}
+	if body := readBody(t, rr.Result()); !strings.Contains(body, "Prices may not be published yet") {
+		t.Error("unpublished day: missing explanation")
+	}
+	if _, err := state.LoadPrices(state.Clock.Now()); !errors.Is(err, ErrNoPrices) {
+		t.Errorf("LoadPrices: got %v, want ErrNoPrices", err)
+	}
+}
+
+func TestRoute_CheapICS_OneEventPerMatchedBlock(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 08:00")
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 100
+	}
+	// The three cheapest hours: 02:00–04:00 and 20:00–21:00.
+	for i := 8; i < 16; i++ {
+		prices[i] = 10
+	}
+	for i := 80; i < 84; i++ {
+		prices[i] = 20
+	}
+	state.Source = fakeSource{"2026-05-10": prices}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/cheap.ics?hours=3&from=0&to=24", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if ct := rr.Header().Get("Content-Type"); !strings.HasPrefix(ct, "text/calendar") {
+		t.Errorf("Content-Type: got %q", ct)
+	}
+	body := readBody(t, rr.Result())
+	if n := strings.Count(body, "BEGIN:VEVENT"); n != 2 {
+		t.Fatalf("got %d events, want 2:\n%s", n, body)
+	}
+	for _, want := range []string{
+		"DTSTART;TZID=Europe/Prague:20260510T020000\r\nDTEND;TZID=Europe/Prague:20260510T040000\r\nSUMMARY:Cheap electricity (10 EUR/MWh)\r\n",
+		"DTSTART;TZID=Europe/Prague:20260510T200000\r\nDTEND;TZID=Europe/Prague:20260510T210000\r\nSUMMARY:Cheap electricity (20 EUR/MWh)\r\n",
+	} {
+		if !strings.Contains(body, want) {
+			t.Errorf("missing event %q", want)
+		}
+	}
+
+	rr = httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/cheap.ics?hours=0", nil))
+	if rr.Code != http.StatusBadRequest {
+		t.Errorf("hours=0: got %d, want 400", rr.Code)
+	}
+}
+
+func TestRoute_HA(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+	get := func(target string) (int, map[string]any) {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		var body map[string]any
+		if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &body); err != nil {
+			t.Fatalf("%s: body is not JSON: %v", target, err)
+		}
+		return rr.Code, body
+	}
+
+	code, body := get("/ha?exp=" + url.QueryEscape("[{hours:[10,14]}]"))
+	if code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", code)
+	}
+	if body["state"] != "on" || body["matched"] != true || body["current_price"] != 48.0 {
+		t.Errorf("got %v", body)
+	}
+	if body["next_change"] != "2026-05-10T15:00:00+02:00" {
+		t.Errorf("next_change: got %v", body["next_change"])
+	}
+
+	code, body = get("/ha?exp=" + url.QueryEscape("[{hours:[10,14]"))
+	if code != http.StatusBadRequest || body["error"] == nil {
+		t.Errorf("bad expression: got %d %v", code, body)
+	}
+}
+
+func TestRoute_Hours_PriceCondition(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 100
+	}
+	prices[0], prices[5], prices[58] = 10, 20, 30 // 00:00, 01:15 and 14:30
+	state.Source = fakeSource{"2026-05-10": prices}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/hours?exp="+url.QueryEscape("[{price:50}]"), nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	var got hoursResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if !slices.Equal(got.MatchedHours, []int{0, 1, 14}) {
+		t.Errorf("matched_hours: got %v, want [0 1 14]", got.MatchedHours)
+	}
+	if len(got.Results) != 96 || !got.Results[58] || got.Results[57] {
+		t.Errorf("results: got %d entries, 14:15 %v, 14:30 %v", len(got.Results), got.Results[57], got.Results[58])
+	}
+
+	rr = httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/hours?exp="+url.QueryEscape("[{price:"), nil))
+	if rr.Code != http.StatusBadRequest {
+		t.Errorf("parse error: got %d, want 400", rr.Code)
+	}
+}
+
+func TestRoute_CORSOnAPIOnly(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+	serve := func(method, target string) *httptest.ResponseRecorder {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(method, target, nil))
+		return rr
+	}
+
+	rr := serve(http.MethodGet, "/api/prices?date=2026-05-10")
+	if rr.Code != http.StatusOK || rr.Header().Get("Access-Control-Allow-Origin") != "*" {
+		t.Errorf("API: got %d, Allow-Origin %q", rr.Code, rr.Header().Get("Access-Control-Allow-Origin"))
+	}
+	rr = serve(http.MethodOptions, "/api/prices")
+	if rr.Code != http.StatusNoContent || rr.Header().Get("Access-Control-Allow-Methods") != "GET" {
+		t.Errorf("preflight: got %d, Allow-Methods %q", rr.Code, rr.Header().Get("Access-Control-Allow-Methods"))
+	}
+	if got := serve(http.MethodGet, "/?date=2026-05-10").Header().Get("Access-Control-Allow-Origin"); got != "" {
+		t.Errorf("HTML page should have no CORS header, got %q", got)
+	}
+
+	state.CORSOrigin = "https://dash.example"
+	rr = serve(http.MethodGet, "/api/prices?date=2026-05-10")
+	if rr.Header().Get("Access-Control-Allow-Origin") != "https://dash.example" || rr.Header().Get("Vary") != "Origin" {
+		t.Errorf("configured origin: got headers %v", rr.Header())
+	}
+}
+
+func TestServeUntil_FinishesInFlightRequest(t *testing.T) {
+	ln, err := net.Listen("tcp", "127.0.0.1:0")
+	if err != nil {
+		t.Fatalf("listen: %v", err)
+	}
+	started := make(chan struct{})
+	srv := &http.Server{Handler: http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		close(started)
+		time.Sleep(100 * time.Millisecond)
+		io.WriteString(w, "done")
+	})}
+	ctx, cancel := context.WithCancel(context.Background())
+	served := make(chan error, 1)
+	go func() { served <- serveUntil(ctx, srv, ln) }()
+
+	got := make(chan string, 1)
+	go func() {
+		resp, err := http.Get("http://" + ln.Addr().String())
+		if err != nil {
+			got <- err.Error()
+			return
+		}
+		defer resp.Body.Close()
+		b, _ := io.ReadAll(resp.Body)
+		got <- string(b)
+	}()
+	<-started
+	cancel()
+
+	select {
+	case err := <-served:
+		if err != nil {
+			t.Errorf("serveUntil: %v", err)
+		}
+	case <-time.After(5 * time.Second):
+		t.Fatal("serveUntil did not return after cancel")
+	}
+	if body := <-got; body != "done" {
+		t.Errorf("in-flight request: got %q, want done", body)
+	}
+}
+
+func TestLogRequests_LogsMethodPathAndStatus(t *testing.T) {
+	var buf bytes.Buffer
+	log.SetOutput(&buf)
+	t.Cleanup(func() { log.SetOutput(os.Stderr) })
+
+	rr := httptest.NewRecorder()
+	newHandler(openTestState(t)).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/healthz", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	rr = httptest.NewRecorder()
+	newHandler(openTestState(t)).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/does-not-exist", nil))
+
+	for _, want := range []string{"GET /healthz 200 ", "GET /does-not-exist 404 "} {
+		if !strings.Contains(buf.String(), want) {
+			t.Errorf("log is missing %q:\n%s", want, buf.String())
+		}
+	}
+}
+
+func TestRoute_Compare_TwoSeededDays(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 14:00")
+	flat := make([]float32, 96)
+	for i := range flat {
+		flat[i] = 50
+	}
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-05-11": flat}
+	get := func(target string) (int, string) {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		return rr.Code, readBody(t, rr.Result())
+	}
+
+	code, body := get("/compare")
+	if code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", code)
+	}
+	if n := strings.Count(body, "<svg"); n != 2 {
+		t.Errorf("got %d charts, want 2", n)
+	}
+	for _, want := range []string{
+		`<td class="px-2">00:00</td><td class="px-2 text-right">1.50</td><td class="px-2 text-right">50.00</td><td class="px-2 text-right text-red-700 dark:text-red-400">+48.50</td>`,
+		`<td class="px-2">23:00</td><td class="px-2 text-right">93.50</td><td class="px-2 text-right">50.00</td><td class="px-2 text-right text-green-700 dark:text-green-400">-43.50</td>`,
+	} {
+		if !strings.Contains(body, want) {
+			t.Errorf("difference table is missing %q", want)
+		}
+	}
+
+	code, body = get("/compare?a=2026-05-10&b=2026-05-12")
+	if code != http.StatusOK || strings.Count(body, "<svg") != 1 || !strings.Contains(body, "No prices for this date") {
+		t.Errorf("one day missing: got %d, %d charts", code, strings.Count(body, "<svg"))
+	}
+	if code, _ = get("/compare?a=2026-05-12&b=2026-05-13"); code != http.StatusNotFound {
+		t.Errorf("both days missing: got %d, want 404", code)
+	}
+}
+
+func TestRoute_Week_BarsForSeededDaysOnly(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-13 12:00")
+	state.Source = fakeSource{
+		"2026-05-11": fixedPrices(96),
+		"2026-05-12": fixedPrices(96),
+		"2026-05-14": fixedPrices(96),
+	}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/week", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	body := readBody(t, rr.Result())
+	if !strings.Contains(body, "Week from 2026-05-11") {
+		t.Error("default start should be this week's Monday")
+	}
+	if n := strings.Count(body, "<rect"); n != 3*24 {
+		t.Errorf("got %d bars, want %d", n, 3*24)
+	}
+	if !strings.Contains(body, "Tue 2026-05-12 10:00 — 41.50 EUR/MWh") {
+		t.Error("missing hourly average bar for Tuesday 10:00")
+	}
+	if n := strings.Count(body, "not published"); n != 4 {
+		t.Errorf("got %d missing days, want 4", n)
+	}
+}
+
+// countingSource wraps a PriceSource, counting fetches and the most that ran
+// at once.
+type countingSource struct {
+	src              PriceSource
+	mu               sync.Mutex
+	fetched          []string
+	inFlight, maxRun int
+}
+
+func (c *countingSource) Fetch(date time.Time) ([]storage.Quarter, error) {
+	c.mu.Lock()
+	c.fetched = append(c.fetched, date.Format("2006-01-02"))
+	c.inFlight++
+	c.maxRun = max(c.maxRun, c.inFlight)
+	c.mu.Unlock()
+	time.Sleep(10 * time.Millisecond)
+	defer func() {
+		c.mu.Lock()
+		c.inFlight--
+		c.mu.Unlock()
+	}()
+	return c.src.Fetch(date)
+}
+
+func TestPricesRange_OrderedMixOfCachedAndFetched(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-20 12:00")
+	src := &countingSource{src: fakeSource{
+		"2026-05-09": fixedPrices(96),
+		"2026-05-10": fixedPrices(96),
+		"2026-05-11": fixedPrices(96),
+		"2026-05-12": fixedPrices(96),
+	}}
+	state.Source = src
+	loc := state.Clock.Now().Location()
+	day := func(d int) time.Time { return time.Date(2026, 5, d, 0, 0, 0, 0, loc) }
+
+	for _, d := range []int{10, 12} {
+		if _, ok := state.GetPrices(day(d)); !ok {
+			t.Fatalf("seeding 2026-05-%d failed", d)
+		}
+	}
+	src.fetched = nil
+
+	got := state.PricesRange(day(9), day(13))
+	if len(got) != 5 {
+		t.Fatalf("got %d days, want 5", len(got))
+	}
+	for i, d := range got {
+		if !d.Date.Equal(day(9 + i)) {
+			t.Errorf("entry %d: date %s, want 2026-05-%02d", i, d.Date.Format("2006-01-02"), 9+i)
+		}
+		if wantPrices := i < 4; (d.Prices != nil) != wantPrices {
+			t.Errorf("%s: has prices %v, want %v", d.Date.Format("2006-01-02"), d.Prices != nil, wantPrices)
+		}
+	}
+	slices.Sort(src.fetched)
+	if want := []string{"2026-05-09", "2026-05-11", "2026-05-13"}; !slices.Equal(src.fetched, want) {
+		t.Errorf("fetched %v, want only the uncached days %v", src.fetched, want)
+	}
+
+	src.maxRun = 0
+	state.PricesRange(day(20), day(31))
+	if src.maxRun > DefaultFetchLimit {
+		t.Errorf("%d fetches ran at once, limit is %d", src.maxRun, DefaultFetchLimit)
+	}
+	if got := state.PricesRange(day(2), day(1)); len(got) != 0 {
+		t.Errorf("reversed range: got %d days", len(got))
+	}
+}
+
+func TestRoute_Normalize_MessyJSON5ToCanonical(t *testing.T) {
+	handler := newHandler(openTestState(t))
+	messy := "[ {price: 120.0,},\n {'hours': [0, 10]} , ]"
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/normalize?exp="+url.QueryEscape(messy), nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200: %s", rr.Code, rr.Body.String())
+	}
+	var got normalizeResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if want := `[{"price":120},{"hours":[0,10]}]`; got.Exp != want {
+		t.Errorf("exp: got %s, want %s", got.Exp, want)
+	}
+	if got.Expression != "AND(price<=120, hours[0..10])" {
+		t.Errorf("expression: got %q", got.Expression)
+	}
+	original, _ := ParseCondition(messy)
+	if again, err := ParseCondition(got.Exp); err != nil || !again.Equal(original) {
+		t.Errorf("canonical form does not round-trip: %v", err)
+	}
+
+	rr = httptest.NewRecorder()
+	handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/normalize?exp="+url.QueryEscape("[{price: }]"), nil))
+	if rr.Code != http.StatusBadRequest || !strings.Contains(rr.Body.String(), `"error"`) {
+		t.Errorf("parse error: got %d %s", rr.Code, rr.Body.String())
+	}
+}
+
+func TestGetPrices_BurstRespectsFetchLimit(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-31 12:00")
+	state.SetFetchLimit(2)
+	seeded := fakeSource{}
+	for d := 1; d <= 20; d++ {
+		seeded[fmt.Sprintf("2026-05-%02d", d)] = fixedPrices(96)
+	}
+	src := &countingSource{src: seeded}
+	state.Source = src
+	loc := state.Clock.Now().Location()
+
+	burst := func() {
+		var wg sync.WaitGroup
+		for d := 1; d <= 20; d++ {
+			wg.Add(1)
+			go func(d int) {
+				defer wg.Done()
+				if _, ok := state.GetPrices(time.Date(2026, 5, d, 0, 0, 0, 0, loc)); !ok {
+					t.Errorf("2026-05-%02d: no prices", d)
+				}
+			}(d)
+		}
+		wg.Wait()
+	}
+	burst()
+	if src.maxRun > 2 {
+		t.Errorf("%d fetches ran at once, limit is 2", src.maxRun)
+	}
+	if len(src.fetched) != 20 {
+		t.Errorf("got %d fetches, want 20", len(src.fetched))
+	}
+
+	// Cached days skip the limiter and OTE altogether.
+	burst()
+	if len(src.fetched) != 20 {
+		t.Errorf("cached burst fetched again: %d fetches", len(src.fetched))
+	}
+}
+
+func TestGetPrices_ConcurrentRequestsShareOneFetch(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	src := &countingSource{src: fakeSource{"2026-05-11": fixedPrices(96)}}
+	state.Source = src
+	day := time.Date(2026, 5, 11, 0, 0, 0, 0, state.Clock.Now().Location())
+
+	var wg sync.WaitGroup
+	for range 10 {
+		wg.Add(1)
+		go func() {
+			defer wg.Done()
+			if prices, ok := state.GetPrices(day); !ok || len(prices.Prices) != 96 {
+				t.Error("a waiting request did not get the shared prices")
+			}
+		}()
+	}
+	wg.Wait()
+	if len(src.fetched) != 1 {
+		t.Errorf("got %d fetches for one date, want 1", len(src.fetched))
+	}
+}
+
+func TestRoute_Root_CurrentPriceHeadline(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 14:07")
+	state.Distribution = Distribution{HighPrice: 20, LowPrice: 20}
+	prices := fixedPrices(96)
+	prices[56] = 87.43
+	state.Source = fakeSource{"2026-05-10": prices, "2026-05-09": fixedPrices(96)}
+	get := func(target string) string {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		return readBody(t, rr.Result())
+	}
+
+	want := `Now 14:00: <span class="font-bold">87<span class="text-neutral-500 text-sm">.43</span></span> EUR/MWh, with distribution <span class="font-bold">107<span class="text-neutral-500 text-sm">.43</span></span>`
+	if body := get("/"); !strings.Contains(body, want) {
+		t.Errorf("today is missing the current price %q", want)
+	}
+	if body := get("/?date=2026-05-09"); strings.Contains(body, `<p class="text-2xl my-4">Now`) {
+		t.Error("another day should not show a current price")
+	}
+
+	state.Clock = pragueClock(t, "2026-05-10 23:50")
+	if body := get("/"); !strings.Contains(body, "· most expensive of the day") {
+		t.Error("23:45 should be flagged as the most expensive quarter")
+	}
+}
+
+func TestRoute_APIRaw_PassesThroughAllLines(t *testing.T) {
+	srv := httptest.NewServer(http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		if r.URL.Query().Get("report_date") != "2026-05-10" {
+			http.Error(w, "no data", http.StatusNotFound)
+			return
+		}
+		fmt.Fprint(w, `{"data":{"dataLine":[`+
+			`{"title":"15min price (EUR/MWh)","point":[{"y":80.5},{"y":-3}]},`+
+			`{"title":"Volume (MWh)","point":[{"y":1200},{"y":1350},{"y":990}]}]}}`)
+	}))
+	defer srv.Close()
+	prev := dataloader.BaseURL
+	dataloader.BaseURL = srv.URL
+	defer func() { dataloader.BaseURL = prev }()
+	handler := newHandler(openTestState(t))
+
+	rr := httptest.NewRecorder()
+	handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/api/raw?date=2026-05-10", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200: %s", rr.Code, rr.Body.String())
+	}
+	var got rawResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if got.Date != "2026-05-10" || len(got.Lines) != 2 {
+		t.Fatalf("got %+v", got)
+	}
+	if p := got.Lines["15min price (EUR/MWh)"]; !slices.Equal(p, []float32{80.5, -3}) {
+		t.Errorf("price line: got %v", p)
+	}
+	if v := got.Lines["Volume (MWh)"]; !slices.Equal(v, []float32{1200, 1350, 990}) {
+		t.Errorf("volume line: got %v", v)
+	}
+
+	rr = httptest.NewRecorder()
+	handler.ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/api/raw?date=2026-05-11", nil))
+	if rr.Code != http.StatusNotFound {
+		t.Errorf("unpublished day: got %d, want 404", rr.Code)
+	}
+}
+
+func TestRoute_Schedule_OneEntryPerHour(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-03-29": fixedPrices(92)}
+	schedule := func(query string) []scheduleEntry {
+		t.Helper()
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/schedule?"+query, nil))
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d: %s", query, rr.Code, rr.Body.String())
+		}
+		var got []scheduleEntry
+		if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+			t.Fatalf("body is not JSON: %v", err)
+		}
+		return got
+	}
+
+	// Quarters 0..10 cost at most 10, so hours 0, 1 and 2 are on.
+	exp := "exp=" + url.QueryEscape("[{price:10}]")
+	got := schedule(exp)
+	if len(got) != 24 {
+		t.Fatalf("got %d entries, want 24", len(got))
+	}
+	for h, e := range got {
+		if e.Hour != h || e.On != (h <= 2) {
+			t.Errorf("hour %d: got %+v", h, e)
+		}
+	}
+	if got[0].Price != 1.5 || got[0].Time.Format(time.RFC3339) != "2026-05-10T00:00:00+02:00" {
+		t.Errorf("first entry: got %+v", got[0])
+	}
+
+	dst := schedule(exp + "&date=2026-03-29")
+	if len(dst) != 23 || dst[2].Hour != 3 || dst[2].Time.Format(time.RFC3339) != "2026-03-29T03:00:00+02:00" {
+		t.Errorf("spring DST day: got %d entries, third %+v", len(dst), dst[2])
+	}
+}
+
+func TestRoute_Battery_PicksChargeAndDischargeHours(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	hourly := make([]float32, 24)
+	for h := range hourly {
+		hourly[h] = 80
+	}
+	copy(hourly[2:], []float32{10, 12, 11, 13, 40})
+	hourly[18], hourly[19], hourly[20] = 200, 180, 150
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = hourly[i/4]
+	}
+	state.Source = fakeSource{"2026-05-10": prices}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/battery?charge_hours=4&discharge_hours=2", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d: %s", rr.Code, rr.Body.String())
+	}
+	var got batteryResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if !slices.Equal(got.ChargeHours, []int{2, 3, 4, 5}) || !slices.Equal(got.DischargeHours, []int{18, 19}) {
+		t.Errorf("hours: charge %v, discharge %v", got.ChargeHours, got.DischargeHours)
+	}
+	if got.ChargePrice != 11.5 || got.DischargePrice != 190 || got.Arbitrage != 178.5 {
+		t.Errorf("prices: got %+v", got)
+	}
+
+	for _, bad := range []string{"charge_hours=0", "discharge_hours=x", "charge_hours=20&discharge_hours=5"} {
+		rr = httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/battery?"+bad, nil))
+		if rr.Code != http.StatusBadRequest {
+			t.Errorf("%s: got %d, want 400", bad, rr.Code)
+		}
+	}
+}
+
+func TestRoute_Root_NegotiatesJSONByAccept(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96)}
+	get := func(accept string) *httptest.ResponseRecorder {
+		req := httptest.NewRequest(http.MethodGet, "/?date=2026-05-10", nil)
+		if accept != "" {
+			req.Header.Set("Accept", accept)
+		}
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, req)
+		return rr
+	}
+
+	rr := get("application/json")
+	if rr.Code != http.StatusOK || !strings.HasPrefix(rr.Header().Get("Content-Type"), "application/json") {
+		t.Fatalf("json: got %d %q", rr.Code, rr.Header().Get("Content-Type"))
+	}
+	var got pricesResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if got.Date != "2026-05-10" || len(got.Prices) != 96 || got.Expensive != 95 {
+		t.Errorf("json body: got date %s, %d prices, expensive %d", got.Date, len(got.Prices), got.Expensive)
+	}
+
+	for _, accept := range []string{"", "text/html", "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8", "application/json;q=0.5, text/html"} {
+		rr := get(accept)
+		if rr.Code != http.StatusOK || !strings.HasPrefix(rr.Header().Get("Content-Type"), "text/html") {
+			t.Errorf("%q: got %d %q", accept, rr.Code, rr.Header().Get("Content-Type"))
+		}
+		if !strings.Contains(readBody(t, rr.Result()), "OTE prices 2026-05-10") {
+			t.Errorf("%q: expected the HTML page", accept)
+		}
+	}
+}
+
+func TestRoute_APITodayCheapestAndExpensive(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Distribution = Distribution{HighHours: []byte{20}, HighPrice: 30, LowPrice: 10}
+	prices := make([]float32, 96)
+	for i := range prices {
+		prices[i] = 50
+	}
+	copy(prices[3*4:], []float32{40, 42, 44, 46}) // 03:00 averages 43
+	copy(prices[20*4:], []float32{90, 100, 110, 120})
+	state.Source = fakeSource{"2026-05-10": prices}
+	get := func(path string) (int, todayHourResponse) {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, path, nil))
+		var got todayHourResponse
+		if rr.Code == http.StatusOK {
+			if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+				t.Fatalf("%s: body is not JSON: %v", path, err)
+			}
+		}
+		return rr.Code, got
+	}
+
+	if code, got := get("/api/today/cheapest"); code != http.StatusOK || got.Time.Format(time.RFC3339) != "2026-05-10T03:00:00+02:00" ||
+		(todayHourResponse{Hour: got.Hour, Price: got.Price, TotalPrice: got.TotalPrice}) != (todayHourResponse{Hour: 3, Price: 43, TotalPrice: 53}) {
+		t.Errorf("cheapest: got %d %+v", code, got)
+	}
+	if code, got := get("/api/today/expensive"); code != http.StatusOK || got.Time.Format(time.RFC3339) != "2026-05-10T20:00:00+02:00" ||
+		(todayHourResponse{Hour: got.Hour, Price: got.Price, TotalPrice: got.TotalPrice}) != (todayHourResponse{Hour: 20, Price: 105, TotalPrice: 135}) {
+		t.Errorf("expensive: got %d %+v", code, got)
+	}
+
+	state.Clock = pragueClock(t, "2026-05-11 12:00") // not published
+	if code, _ := get("/api/today/cheapest"); code != http.StatusNotFound {
+		t.Errorf("unpublished day: got %d, want 404", code)
+	}
+}
+
+func TestExpressionContext_PartialDayPastLastPublishedQuarter(t *testing.T) {
+	state := openTestState(t)
+	// 14:00 is quarter 56, but only 20 quarters of today are published.
+	state.Clock = pragueClock(t, "2026-05-10 14:00")
+	state.Source = fakeSource{"2026-05-09": fixedPrices(96), "2026-05-10": fixedPrices(20)}
+	if ctx := state.ExpressionContext(); ctx != nil {
+		t.Fatalf("got a context at index %d of %d prices, want nil", ctx.Prices.NowIndex, len(ctx.Prices.Prices))
+	}
+
+	exp := "exp=" + url.QueryEscape("[{price:1000}]")
+	for _, c := range []struct {
+		path string
+		want int
+	}{{"/opt?", http.StatusOK}, {"/optimizer?", http.StatusOK}, {"/ha?", http.StatusServiceUnavailable}, {"/?", http.StatusOK}} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, c.path+exp, nil))
+		if rr.Code != c.want {
+			t.Errorf("%s: status %d, want %d: %s", c.path, rr.Code, c.want, abbreviate(rr.Body.String()))
+		}
+	}
+}
+
+func TestFetchShared_PanicReleasesWaiters(t *testing.T) {
+	state := openTestState(t)
+	started, release := make(chan struct{}), make(chan struct{})
+	go func() {
+		defer func() { recover() }()
+		state.fetchShared("2026-05-10", func() (*DayPrices, error) {
+			close(started)
+			<-release
+			panic("source blew up")
+		})
+	}()
+	<-started
+	waited := make(chan error, 1)
+	go func() {
+		_, err := state.fetchShared("2026-05-10", func() (*DayPrices, error) { return nil, errors.New("ran its own fetch") })
+		waited <- err
+	}()
+	time.Sleep(20 * time.Millisecond) // let the second request start waiting
+	close(release)
+
+	select {
+	case err := <-waited:
+		if !errors.Is(err, errFetchAborted) {
+			t.Errorf("waiter: got %v, want errFetchAborted", err)
+		}
+	case <-time.After(time.Second):
+		t.Fatal("waiter still blocked after the fetch panicked")
+	}
+	prices, err := state.fetchShared("2026-05-10", func() (*DayPrices, error) { return &DayPrices{Prices: fixedPrices(96)}, nil })
+	if err != nil || len(prices.Prices) != 96 {
+		t.Errorf("refetch after the panic: got %v, %v", prices, err)
+	}
+}
+
+func TestRoute_APIBestStart_DSTDayWindowFollowsWallClock(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	// 2026-03-29 skips 02:00, so 04:00 is quarter 12, not 16.
+	prices := make([]float32, 92)
+	for i := range prices {
+		prices[i] = 100
+	}
+	for i := 12; i < 16; i++ {
+		prices[i] = 10
+	}
+	state.Source = fakeSource{"2026-03-29": prices}
+	get := func(query string) bestStartResponse {
+		t.Helper()
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/api/best-start?date=2026-03-29&duration=1&"+query, nil))
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d: %s", query, rr.Code, rr.Body.String())
+		}
+		var got bestStartResponse
+		if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+			t.Fatalf("decode: %v", err)
+		}
+		return got
+	}
+
+	got := get("from=4&to=24")
+	if got.StartIndex != 12 || got.Start.Format(time.RFC3339) != "2026-03-29T04:00:00+02:00" || got.AvgPrice != 10 {
+		t.Errorf("from 04:00: got %+v, want the 04:00 trough", got)
+	}
+	if got := get("from=0&to=4"); got.StartIndex+4 > 12 || got.AvgPrice != 100 {
+		t.Errorf("until 04:00: got %+v, the window must end before the trough", got)
+	}
+}
+
+func TestRoute_BadDateIs400OnImageAndJSONRoutes(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-10 12:00")
+	state.Source = failingSource{errors.New("must not fetch")}
+
+	for _, target := range []string{
+		"/chart.svg?date=garbage",
+		"/chart.svg?date=2076-05-10",
+		"/api/prices?date=garbage",
+		"/api/prices?date=2076-05-10",
+	} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, target, nil))
+		if rr.Code != http.StatusBadRequest {
+			t.Errorf("%s: got %d, want 400", target, rr.Code)
+		}
+		body := readBody(t, rr.Result())
+		if !strings.Contains(body, "YYYY-MM-DD") && !strings.Contains(body, "out of range") {
+			t.Errorf("%s: body does not explain the date: %s", target, abbreviate(body))
+		}
+		if strings.HasPrefix(target, "/api/") && !strings.Contains(body, `"error"`) {
+			t.Errorf("%s: want a JSON error, got %s", target, abbreviate(body))
+		}
+	}
+}
+
+func TestRoute_Battery_DSTDayTimesAndParamOrder(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-10-25 12:00")
+	// 2026-10-25 has 25 hours; hourly averages 2 and 3 are both 02:00.
+	prices := make([]float32, 100)
+	for i := range prices {
+		prices[i] = 80
+	}
+	for i := 8; i < 16; i++ {
+		prices[i] = 5
+	}
+	state.Source = fakeSource{"2026-10-25": prices}
+	get := func(query string) *httptest.ResponseRecorder {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/battery?"+query, nil))
+		return rr
+	}
+
+	rr := get("charge_hours=2&discharge_hours=1")
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d: %s", rr.Code, rr.Body.String())
+	}
+	var got batteryResponse
+	if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+		t.Fatalf("body is not JSON: %v", err)
+	}
+	if !slices.Equal(got.ChargeHours, []int{2, 2}) || len(got.ChargeTimes) != 2 {
+		t.Fatalf("charge: got %v %v", got.ChargeHours, got.ChargeTimes)
+	}
+	if a, b := got.ChargeTimes[0].Format(time.RFC3339), got.ChargeTimes[1].Format(time.RFC3339); a != "2026-10-25T02:00:00+02:00" || b != "2026-10-25T02:00:00+01:00" {
+		t.Errorf("charge times: got %s and %s", a, b)
+	}
+
+	// With both counts invalid, charge_hours is always the one reported.
+	for range 10 {
+		rr := get("discharge_hours=x&charge_hours=0")
+		if body := rr.Body.String(); rr.Code != http.StatusBadRequest || !strings.Contains(body, "charge_hours must be") || strings.Contains(body, "discharge_hours must be") {
+			t.Fatalf("got %d %s, want the charge_hours error", rr.Code, body)
+		}
+	}
+}
+
+func TestRoute_APITodayHour_DSTDayRepeatedHourHasOffset(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-10-25 12:00")
+	prices := make([]float32, 100) // 25 hours, 02:00 twice
+	for i := range prices {
+		prices[i] = 50
+	}
+	copy(prices[2*4:], []float32{90, 90, 90, 90}) // first 02:00
+	copy(prices[3*4:], []float32{10, 10, 10, 10}) // second 02:00
+	state.Source = fakeSource{"2026-10-25": prices}
+
+	for path, want := range map[string]string{
+		"/api/today/cheapest":  "2026-10-25T02:00:00+01:00",
+		"/api/today/expensive": "2026-10-25T02:00:00+02:00",
+	} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, path, nil))
+		var got todayHourResponse
+		if err := json.Unmarshal([]byte(readBody(t, rr.Result())), &got); err != nil {
+			t.Fatalf("%s: body is not JSON: %v", path, err)
+		}
+		if rr.Code != http.StatusOK || got.Hour != 2 || got.Time.Format(time.RFC3339) != want {
+			t.Errorf("%s: got %d hour %d time %s, want hour 2 at %s", path, rr.Code, got.Hour, got.Time.Format(time.RFC3339), want)
+		}
+	}
+}
+
+func TestRoute_ChartPNG_PastDayServedFromCache(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-11 12:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-05-11": fixedPrices(96)}
+	get := func(path string) string {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, path, nil))
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d, want 200", path, rr.Code)
+		}
+		return readBody(t, rr.Result())
+	}
+
+	first := get("/chart.png?date=2026-05-10&scale=2")
+	get("/chart.png?date=2026-05-11&scale=2") // today is not cached
+	if got, ok := state.pngs.get(pngCacheKey("2026-05-10", 2)); !ok || string(got) != first {
+		t.Fatalf("past day should be cached after the first render")
+	}
+	if _, ok := state.pngs.get(pngCacheKey("2026-05-11", 2)); ok {
+		t.Errorf("today's chart must not be cached")
+	}
+
+	state.Source = failingSource{errors.New("must not fetch")}
+	state.pngs.put(pngCacheKey("2026-05-10", 2), []byte("cached"))
+	if body := get("/chart.png?date=2026-05-10&scale=2"); body != "cached" {
+		t.Errorf("second request should come from the cache, got %q", abbreviate(body))
+	}
+	if _, ok := state.pngs.get(pngCacheKey("2026-05-10", 1)); ok {
+		t.Errorf("another scale must not share the entry")
+	}
+}
+
+func TestRoute_CardPNG_CachesCompletePastDaysPerCurrency(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-12 09:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-05-11": fixedPrices(20)}
+	get := func(path string) *httptest.ResponseRecorder {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, path, nil))
+		return rr
+	}
+
+	if rr := get("/card.png?date=2026-05-10&cur=czk"); rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if _, ok := state.pngs.get(cardCacheKey("2026-05-10", CurrencyCzk)); !ok {
+		t.Errorf("complete past card should be cached")
+	}
+	if _, ok := state.pngs.get(cardCacheKey("2026-05-10", CurrencyEur)); ok {
+		t.Errorf("another currency must not share the entry")
+	}
+
+	// 20 of 96 quarters: the card will change, so neither cache may keep it.
+	rr := get("/card.png?date=2026-05-11")
+	if rr.Code != http.StatusOK {
+		t.Fatalf("partial day: got %d, want 200", rr.Code)
+	}
+	if cc := rr.Header().Get("Cache-Control"); cc != "" {
+		t.Errorf("partial past day must not be cacheable, Cache-Control=%q", cc)
+	}
+	if _, ok := state.pngs.get(cardCacheKey("2026-05-11", CurrencyEur)); ok {
+		t.Errorf("partial day card was cached")
+	}
+
+	state.Source = failingSource{errors.New("OTE down")}
+	if rr := get("/card.png?date=2026-05-09"); rr.Code != http.StatusServiceUnavailable || rr.Header().Get("Retry-After") == "" {
+		t.Errorf("outage: got %d, want 503 with Retry-After", rr.Code)
+	}
+}
+
+func TestRoute_ChartPNG_PartialPastDayNotImmutable(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-12 09:00")
+	state.Source = fakeSource{"2026-05-11": fixedPrices(20)}
+
+	rr := httptest.NewRecorder()
+	newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/chart.png?date=2026-05-11", nil))
+	if rr.Code != http.StatusOK {
+		t.Fatalf("status: got %d, want 200", rr.Code)
+	}
+	if cc := rr.Header().Get("Cache-Control"); cc != "" {
+		t.Errorf("partial past day must not be cacheable, Cache-Control=%q", cc)
+	}
+}
+
+func TestRoute_ChartSVG_PartialPastDayNotImmutable(t *testing.T) {
+	state := openTestState(t)
+	state.Clock = pragueClock(t, "2026-05-12 09:00")
+	state.Source = fakeSource{"2026-05-10": fixedPrices(96), "2026-05-11": fixedPrices(20)}
+
+	for date, want := range map[string]bool{"2026-05-10": true, "2026-05-11": false} {
+		rr := httptest.NewRecorder()
+		newHandler(state).ServeHTTP(rr, httptest.NewRequest(http.MethodGet, "/chart.svg?date="+date, nil))
+		if rr.Code != http.StatusOK {
+			t.Fatalf("%s: status %d, want 200", date, rr.Code)
+		}
+		if cc := rr.Header().Get("Cache-Control"); strings.Contains(cc, "immutable") != want {
+			t.Errorf("%s: Cache-Control=%q, want cacheable %v", date, cc, want)
+		}
+	}
+}
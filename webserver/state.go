@@ -1,11 +1,16 @@
 package webserver
 
 import (
+	"errors"
 	"fmt"
 	"log"
 	"math"
+	"net/http"
+	"slices"
+	"strconv"
 	"strings"
 	"sync"
+	"sync/atomic"
 	"time"
 
 	"github.com/MichalKalita/ote/dataloader"
@@ -14,39 +19,173 @@ import (
 
 type DayPrices struct {
 	Prices []float32
+	// Pending counts trailing quarter-hours OTE has not published yet. It is
+	// non-zero only for partial days accepted via dataloader.AllowPartialDays.
+	Pending int
 }
 
-// CheapestHour returns the index and the value of the lowest price.
-func CheapestHour(prices []float32) (int, float32) {
-	idx := 0
-	min := float32(math.Inf(1))
+// CheapestHour returns the index and the value of the lowest price. NaN
+// prices are skipped; when no price is left it returns index -1 and false.
+func CheapestHour(prices []float32) (int, float32, bool) {
+	idx := -1
+	var min float32
 	for i, p := range prices {
-		if p < min {
+		if math.IsNaN(float64(p)) {
+			continue
+		}
+		if idx < 0 || p < min {
 			min = p
 			idx = i
 		}
 	}
-	return idx, min
+	return idx, min, idx >= 0
 }
 
-// ExpensiveHour returns the index and the value of the highest price.
-func ExpensiveHour(prices []float32) (int, float32) {
-	idx := 0
-	max := float32(math.Inf(-1))
+// ExpensiveHour returns the index and the value of the highest price. NaN
+// prices are skipped; when no price is left it returns index -1 and false.
+func ExpensiveHour(prices []float32) (int, float32, bool) {
+	idx := -1
+	var max float32
 	for i, p := range prices {
-		if p > max {
+		if math.IsNaN(float64(p)) {
+			continue
+		}
+		if idx < 0 || p > max {
 			max = p
 			idx = i
 		}
 	}
-	return idx, max
+	return idx, max, idx >= 0
+}
+
+// PriceStats summarizes a day's prices.
+type PriceStats struct {
+	Min, Max, Avg, Median float32
+}
+
+// NewPriceStats computes the stats of prices, skipping NaN. The median of an
+// even count is the mean of the two middle prices. It is false when no price
+// is left.
+func NewPriceStats(prices []float32) (PriceStats, bool) {
+	sorted := make([]float32, 0, len(prices))
+	var sum float32
+	for _, p := range prices {
+		if !math.IsNaN(float64(p)) {
+			sorted = append(sorted, p)
+			sum += p
+		}
+	}
+	n := len(sorted)
+	if n == 0 {
+		return PriceStats{}, false
+	}
+	slices.Sort(sorted)
+	median := sorted[n/2]
+	if n%2 == 0 {
+		median = (sorted[n/2-1] + sorted[n/2]) / 2
+	}
+	return PriceStats{Min: sorted[0], Max: sorted[n-1], Avg: sum / float32(n), Median: median}, true
+}
+
+// CheaperThan returns the percentage of prices strictly above price, i.e. how
+// much of the day price beats. NaN prices are skipped; it is false when no
+// price is left.
+func CheaperThan(prices []float32, price float32) (float32, bool) {
+	var n, above int
+	for _, p := range prices {
+		if math.IsNaN(float64(p)) {
+			continue
+		}
+		n++
+		if p > price {
+			above++
+		}
+	}
+	if n == 0 {
+		return 0, false
+	}
+	return float32(above) * 100 / float32(n), true
+}
+
+// CheapestBlock returns the start index and sum of the n consecutive prices
+// with the lowest sum, using a sliding window. It reports false when n is not
+// in 1..len(prices).
+func CheapestBlock(prices []float32, n int) (int, float32, bool) {
+	if n <= 0 || n > len(prices) {
+		return 0, 0, false
+	}
+	var sum float32
+	for _, p := range prices[:n] {
+		sum += p
+	}
+	bestIdx, bestSum := 0, sum
+	for i := n; i < len(prices); i++ {
+		sum += prices[i] - prices[i-n]
+		if sum < bestSum {
+			bestIdx, bestSum = i-n+1, sum
+		}
+	}
+	return bestIdx, bestSum, true
+}
+
+// SlotClock returns the Prague wall-clock time at which quarter idx starts on
+// a day of n quarters. The spring DST day (92 quarters) skips 02:00-02:59 and
+// the autumn one (100 quarters) repeats it.
+func SlotClock(idx, n int) (hour, minute int) {
+	switch {
+	case n == 92 && idx >= 8:
+		idx += 4
+	case n == 100 && idx >= 12:
+		idx -= 4
+	}
+	return idx / 4, idx % 4 * 15
+}
+
+// Slots is the number of quarter hours of the day, published or pending.
+func (d *DayPrices) Slots() int {
+	return len(d.Prices) + d.Pending
+}
+
+// HourlyAverage returns the mean of each hour's quarter-hour prices. A
+// trailing partial hour is averaged over the quarters it has.
+func (d *DayPrices) HourlyAverage() []float32 {
+	out := make([]float32, 0, (len(d.Prices)+3)/4)
+	for start := 0; start < len(d.Prices); start += 4 {
+		hour := d.Prices[start:min(start+4, len(d.Prices))]
+		var sum float32
+		for _, p := range hour {
+			sum += p
+		}
+		out = append(out, sum/float32(len(hour)))
+	}
+	return out
+}
+
+// ClockHourAverages returns the mean price of each wall-clock hour, so days
+// of different length line up: the hour DST skips has no value and the
+// repeated one averages both occurrences.
+func (d *DayPrices) ClockHourAverages() (avg [24]float32, has [24]bool) {
+	var counts [24]int
+	for i, p := range d.Prices {
+		h, _ := SlotClock(i, d.Slots())
+		avg[h] += p
+		counts[h]++
+	}
+	for h, n := range counts {
+		if n > 0 {
+			avg[h] /= float32(n)
+			has[h] = true
+		}
+	}
+	return avg, has
 }
 
 // TotalPrices returns prices including distribution surcharges.
 func (d *DayPrices) TotalPrices(dist *Distribution) []float32 {
 	out := make([]float32, len(d.Prices))
 	for i, price := range d.Prices {
-		hour := byte(i / 4)
+		h, _ := SlotClock(i, d.Slots())
+		hour := byte(h)
 		if containsByte(dist.HighHours, hour) {
 			out[i] = price + dist.HighPrice
 		} else {
@@ -85,51 +224,305 @@ func (d *Distribution) ByHours() [24]string {
 
 type AppState struct {
 	db           *storage.DB
+	Source       PriceSource // where GetPrices fetches days missing from the DB
 	Distribution Distribution
+	Clock        Clock
+	Favorites    []Favorite // shown on /optimizer, from OTE_FAVORITES
+	// CacheTTL is how long a stored day from today onward is trusted before
+	// GetPrices refetches it. Past days never change. Zero disables expiry.
+	CacheTTL time.Duration
+	// CORSOrigin is the Access-Control-Allow-Origin sent by the JSON API
+	// routes, from OTE_CORS_ORIGIN. Empty disables CORS headers.
+	CORSOrigin string
+	// PublicURL is the origin absolute links to the site start with, such as
+	// og:image and the optimizer's automation URL, from OTE_PUBLIC_URL. It
+	// is not taken from the client-controlled Host header.
+	PublicURL string
+
+	fetchSlots chan struct{} // bounds concurrent Source fetches, see SetFetchLimit
+	inflightMu sync.Mutex
+	inflight   map[string]*fetchCall // fetches running now, by Prague date
+	startedAt  time.Time
+	lastFetch  atomic.Int64 // duration of the latest OTE fetch in ns, 0 before the first
+	pngs       pngCache     // rendered past-day charts for /chart.png
+}
+
+// PriceSource fetches the quarter-hour prices of the Prague day containing
+// date, in the shape of dataloader.FetchData.
+type PriceSource interface {
+	Fetch(date time.Time) ([]storage.Quarter, error)
+}
+
+// OTESource is the PriceSource backed by the OTE website.
+type OTESource struct {
+	Client *http.Client // shared by all OTE fetches
 }
 
+func (o OTESource) Fetch(date time.Time) ([]storage.Quarter, error) {
+	return dataloader.FetchData(o.Client, date)
+}
+
+// DefaultCacheTTL is the default AppState.CacheTTL.
+const DefaultCacheTTL = 6 * time.Hour
+
+// DefaultPublicURL is the default AppState.PublicURL.
+const DefaultPublicURL = "https://ota.kalita.cz"
+
 const NextDayPricesHour = 14
 
+// Built-in distribution tariff. Distributors quote it in CZK/MWh; the rate
+// converts it to the EUR/MWh of the market prices.
+const (
+	DefaultDistHighCZK float32 = 648
+	DefaultDistLowCZK  float32 = 438
+	DefaultDistRate    float32 = 25.29
+)
+
+var defaultDistHighHours = []byte{10, 12, 14, 17}
+
+// DefaultDistribution returns the built-in distribution tariff.
+func DefaultDistribution() Distribution {
+	return Distribution{
+		HighHours: append([]byte(nil), defaultDistHighHours...),
+		HighPrice: DefaultDistHighCZK / DefaultDistRate,
+		LowPrice:  DefaultDistLowCZK / DefaultDistRate,
+	}
+}
+
+// DistributionFromEnv builds the tariff from OTE_DIST_HIGH_HOURS (comma list
+// of hours 0..23), OTE_DIST_HIGH_CZK and OTE_DIST_LOW_CZK (CZK/MWh) and
+// OTE_DIST_RATE (CZK per EUR). Unset or empty variables keep the built-in
+// values. getenv is os.Getenv outside tests.
+func DistributionFromEnv(getenv func(string) string) (Distribution, error) {
+	hours := append([]byte(nil), defaultDistHighHours...)
+	if v := getenv("OTE_DIST_HIGH_HOURS"); v != "" {
+		hours = hours[:0]
+		for _, part := range strings.Split(v, ",") {
+			h, err := strconv.ParseUint(strings.TrimSpace(part), 10, 8)
+			if err != nil || h > 23 {
+				return Distribution{}, fmt.Errorf("OTE_DIST_HIGH_HOURS: %q is not an hour between 0 and 23", part)
+			}
+			if slices.Contains(hours, byte(h)) {
+				return Distribution{}, fmt.Errorf("OTE_DIST_HIGH_HOURS: hour %d is listed twice", h)
+			}
+			hours = append(hours, byte(h))
+		}
+	}
+
+	values := map[string]float32{
+		"OTE_DIST_HIGH_CZK": DefaultDistHighCZK,
+		"OTE_DIST_LOW_CZK":  DefaultDistLowCZK,
+		"OTE_DIST_RATE":     DefaultDistRate,
+	}
+	for name := range values {
+		v := getenv(name)
+		if v == "" {
+			continue
+		}
+		f, err := strconv.ParseFloat(v, 32)
+		if err != nil || math.IsNaN(f) || math.IsInf(f, 0) || f < 0 {
+			return Distribution{}, fmt.Errorf("%s: %q is not a non-negative number", name, v)
+		}
+		values[name] = float32(f)
+	}
+	rate := values["OTE_DIST_RATE"]
+	if rate == 0 {
+		return Distribution{}, fmt.Errorf("OTE_DIST_RATE: must be positive")
+	}
+	return Distribution{
+		HighHours: hours,
+		HighPrice: values["OTE_DIST_HIGH_CZK"] / rate,
+		LowPrice:  values["OTE_DIST_LOW_CZK"] / rate,
+	}, nil
+}
+
 func NewAppState(db *storage.DB) *AppState {
-	return &AppState{
-		db: db,
-		Distribution: Distribution{
-			HighHours: []byte{10, 12, 14, 17},
-			HighPrice: 648.0 / 25.29,
-			LowPrice:  438.0 / 25.29,
-		},
+	s := &AppState{
+		db:           db,
+		Source:       OTESource{Client: dataloader.NewClient()},
+		Distribution: DefaultDistribution(),
+		Clock:        SystemClock{},
+		CacheTTL:     DefaultCacheTTL,
+		CORSOrigin:   "*",
+		PublicURL:    DefaultPublicURL,
+		fetchSlots:   make(chan struct{}, DefaultFetchLimit),
+		inflight:     make(map[string]*fetchCall),
 	}
+	s.startedAt = s.Clock.Now()
+	return s
 }
 
-// GetPrices returns prices for the date. Reads from the DB; if absent, fetches
-// from OTE and persists. Returns (nil, false) on fetch error.
+// DefaultFetchLimit is how many OTE fetches may run at once unless
+// SetFetchLimit says otherwise.
+const DefaultFetchLimit = 4
+
+// SetFetchLimit bounds how many fetches from Source run at once; further
+// requests for uncached days queue. Call it before serving requests.
+func (s *AppState) SetFetchLimit(n int) {
+	s.fetchSlots = make(chan struct{}, max(n, 1))
+}
+
+// ErrNoPrices is wrapped by LoadPrices when OTE has not published the date,
+// as opposed to the fetch failing.
+var ErrNoPrices = errors.New("no prices published for this date")
+
+// GetPrices returns prices for the date. Reads from the DB; if absent or
+// expired, fetches from OTE and persists. An expired day whose refetch fails
+// is still served from the DB. Returns (nil, false) on fetch error. Freshly
+// fetched prices may be shared with concurrent requests, so do not modify
+// them.
 func (s *AppState) GetPrices(date time.Time) (*DayPrices, bool) {
+	prices, err := s.LoadPrices(date)
+	return prices, err == nil
+}
+
+// LoadPrices is GetPrices with the reason prices are missing: an error
+// wrapping ErrNoPrices when the date is not published (yet), any other error
+// when OTE is unreachable or the database failed.
+func (s *AppState) LoadPrices(date time.Time) (*DayPrices, error) {
 	pragueDate := s.db.PragueDate(date)
 
 	has, err := s.db.HasDay(pragueDate)
 	if err != nil {
 		log.Printf("HasDay(%s) error: %v", pragueDate, err)
-		return nil, false
+		return nil, err
 	}
 
-	if !has {
-		quarters, err := dataloader.FetchData(date)
-		if err != nil {
-			return nil, false
+	if !has || s.expired(pragueDate) {
+		return s.fetchShared(pragueDate, func() (*DayPrices, error) {
+			// A fetch that finished since HasDay above may have stored the day.
+			if now, err := s.db.HasDay(pragueDate); err == nil && now && !s.expired(pragueDate) {
+				return s.storedPrices(pragueDate)
+			}
+			return s.fetchPrices(date, pragueDate, has)
+		})
+	}
+
+	return s.storedPrices(pragueDate)
+}
+
+// fetchCall is one in-flight fetch that concurrent requests for the same
+// date wait on instead of fetching again.
+type fetchCall struct {
+	done   chan struct{}
+	prices *DayPrices
+	err    error
+}
+
+// errFetchAborted is what requests waiting on a shared fetch get when the
+// fetch panicked instead of returning.
+var errFetchAborted = errors.New("price fetch aborted")
+
+// fetchShared runs fetch for key unless a fetch for key is already running,
+// in which case it waits for that one and returns its result. All of them
+// get the same *DayPrices, which callers must not modify.
+func (s *AppState) fetchShared(key string, fetch func() (*DayPrices, error)) (*DayPrices, error) {
+	s.inflightMu.Lock()
+	if call, ok := s.inflight[key]; ok {
+		s.inflightMu.Unlock()
+		<-call.done
+		return call.prices, call.err
+	}
+	call := &fetchCall{done: make(chan struct{}), err: errFetchAborted}
+	s.inflight[key] = call
+	s.inflightMu.Unlock()
+	// Even if fetch panics, release the waiters and let key be fetched again.
+	defer func() {
+		s.inflightMu.Lock()
+		delete(s.inflight, key)
+		s.inflightMu.Unlock()
+		close(call.done)
+	}()
+
+	call.prices, call.err = fetch()
+	return call.prices, call.err
+}
+
+// fetchPrices loads date from Source and stores complete days. has says the
+// DB already holds the day, which is then served if the refetch fails.
+func (s *AppState) fetchPrices(date time.Time, pragueDate string, has bool) (*DayPrices, error) {
+	quarters, err := s.fetch(date)
+	if err != nil {
+		if has {
+			log.Printf("Refetching %s failed, serving cached prices: %v", pragueDate, err)
+			return s.storedPrices(pragueDate)
+		}
+		if notPublished(err) {
+			return nil, fmt.Errorf("%w: %v", ErrNoPrices, err)
 		}
-		if err := s.db.SaveQuarters(quarters); err != nil {
-			log.Printf("SaveQuarters(%s) error: %v", pragueDate, err)
-			return nil, false
+		return nil, err
+	}
+	if missing := dataloader.QuartersInDay(date) - len(quarters); missing > 0 {
+		if has {
+			return s.storedPrices(pragueDate)
 		}
-		return &DayPrices{Prices: quartersToPrices(quarters)}, true
+		// Partial day: serve what is published but keep it out of the DB so
+		// the next request fetches the complete series.
+		return &DayPrices{Prices: quartersToPrices(quarters), Pending: missing}, nil
+	}
+	if err := s.db.SaveQuartersAt(quarters, s.Clock.Now()); err != nil {
+		log.Printf("SaveQuartersAt(%s) error: %v", pragueDate, err)
+		return nil, err
 	}
+	return &DayPrices{Prices: quartersToPrices(quarters)}, nil
+}
+
+// fetch calls Source.Fetch within the fetch limit and records how long it
+// took for /perf.
+func (s *AppState) fetch(date time.Time) ([]storage.Quarter, error) {
+	s.fetchSlots <- struct{}{}
+	defer func() { <-s.fetchSlots }()
+	start := time.Now()
+	defer func() { s.lastFetch.Store(int64(max(time.Since(start), 1))) }()
+	return s.Source.Fetch(date)
+}
 
+// DataLines fetches every series OTE publishes for date, keyed by title, with
+// the client of an OTESource and within the fetch limit. Nothing is cached.
+func (s *AppState) DataLines(date time.Time) (map[string][]float32, error) {
+	client := &http.Client{Timeout: dataloader.DefaultHTTPTimeout}
+	if src, ok := s.Source.(OTESource); ok {
+		client = src.Client
+	}
+	s.fetchSlots <- struct{}{}
+	defer func() { <-s.fetchSlots }()
+	return dataloader.FetchDataLines(client, date)
+}
+
+// notPublished reports whether a fetch error means OTE has no complete
+// prices for the date, rather than OTE being unreachable.
+func notPublished(err error) bool {
+	var status *dataloader.UnexpectedStatusError
+	var size *dataloader.InvalidDataSizeError
+	return errors.Is(err, dataloader.ErrPriceDataNotFound) ||
+		errors.Is(err, dataloader.ErrDateBeforeQuarterHourly) ||
+		errors.As(err, &size) ||
+		(errors.As(err, &status) && status.Status == http.StatusNotFound)
+}
+
+// expired reports whether a stored day is due for a refetch: it is today or
+// later and was saved more than CacheTTL ago, or before fetch times were
+// recorded.
+func (s *AppState) expired(pragueDate string) bool {
+	now := s.Clock.Now()
+	if s.CacheTTL <= 0 || pragueDate < s.db.PragueDate(now) {
+		return false
+	}
+	fetchedAt, ok, err := s.db.FetchedAt(pragueDate)
+	if err != nil {
+		log.Printf("FetchedAt(%s) error: %v", pragueDate, err)
+		return false
+	}
+	return !ok || now.Sub(fetchedAt) > s.CacheTTL
+}
+
+func (s *AppState) storedPrices(pragueDate string) (*DayPrices, error) {
 	quarters, err := s.db.GetDay(pragueDate)
 	if err != nil {
 		log.Printf("GetDay(%s) error: %v", pragueDate, err)
-		return nil, false
+		return nil, err
 	}
-	return &DayPrices{Prices: quartersToPrices(quarters)}, true
+	return &DayPrices{Prices: quartersToPrices(quarters)}, nil
 }
 
 func quartersToPrices(quarters []storage.Quarter) []float32 {
@@ -182,59 +575,104 @@ func (s *AppState) MonthAverages(year int, month time.Month, loc *time.Location,
 }
 
 // ExpressionContext builds an EvaluateContext from yesterday/today (+tomorrow if late enough).
+// It is nil when the current quarter has no published price, e.g. on a
+// partial day.
 func (s *AppState) ExpressionContext() *EvaluateContext {
-	loc, err := time.LoadLocation("Europe/Prague")
-	if err != nil {
-		loc = time.UTC
-	}
-	now := time.Now().In(loc)
-	hour := now.Hour()
-	today := time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, loc)
-	yesterday := today.AddDate(0, 0, -1)
-	tomorrow := today.AddDate(0, 0, 1)
+	now, today, slot := localToday(s.Clock)
 
-	type fetchResult struct {
-		prices *DayPrices
-		ok     bool
+	prices, dayLens, offset, ok := s.daysAround(today, nextDayPublished(now, today))
+	if !ok || offset+slot < 0 || offset+slot >= len(prices) {
+		return nil
 	}
-	var wg sync.WaitGroup
-	var ysd, td, tmw fetchResult
 
-	wg.Add(2)
-	go func() {
-		defer wg.Done()
-		ysd.prices, ysd.ok = s.GetPrices(yesterday)
-	}()
-	go func() {
-		defer wg.Done()
-		td.prices, td.ok = s.GetPrices(today)
-	}()
-	if hour >= NextDayPricesHour {
-		wg.Add(1)
-		go func() {
-			defer wg.Done()
-			tmw.prices, tmw.ok = s.GetPrices(tomorrow)
-		}()
+	nowLocal := time.Date(now.Year(), now.Month(), now.Day(), now.Hour(), now.Minute(), now.Second(), now.Nanosecond(), time.UTC)
+	ctx := NewEvaluateContext(nowLocal, prices, offset+slot)
+	ctx.Distribution = &s.Distribution
+	ctx.SlotsPerHour = 4
+	ctx.Prices.DayLens = dayLens
+	return ctx
+}
+
+// DayContext builds an EvaluateContext positioned at the first quarter of
+// date, a Prague midnight. The neighbouring days are included when published
+// so windows crossing midnight see real prices. start indexes date's first
+// slot; ctx is nil when date has no prices.
+func (s *AppState) DayContext(date time.Time) (ctx *EvaluateContext, start int) {
+	now := s.Clock.Now().In(date.Location())
+	today := time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, date.Location())
+	next := date.AddDate(0, 0, 1)
+	withNext := !next.After(today) || (next.Equal(today.AddDate(0, 0, 1)) && now.Hour() >= NextDayPricesHour)
+
+	prices, dayLens, offset, ok := s.daysAround(date, withNext)
+	if !ok {
+		return nil, 0
 	}
-	wg.Wait()
+	midnight := time.Date(date.Year(), date.Month(), date.Day(), 0, 0, 0, 0, time.UTC)
+	ctx = NewEvaluateContext(midnight, prices, offset)
+	ctx.Distribution = &s.Distribution
+	ctx.SlotsPerHour = 4
+	ctx.Prices.DayLens = dayLens
+	return ctx, offset
+}
 
-	if !td.ok {
-		return nil
+// daysAround concatenates the prices of the day before day, day itself and,
+// with withNext, the day after, fetching them concurrently. offset is the
+// index of day's first slot. It is false when day itself is unavailable.
+// Pending quarters have no slot, so only a partial last day is kept: a
+// partial day before it would shift the following days off DayLens.
+func (s *AppState) daysAround(day time.Time, withNext bool) (prices []float32, dayLens []int, offset int, ok bool) {
+	last := day
+	if withNext {
+		last = day.AddDate(0, 0, 1)
 	}
+	days := s.PricesRange(day.AddDate(0, 0, -1), last)
+	prev, cur := days[0].Prices, days[1].Prices
 
-	var prices []float32
-	offset := 0
-	if ysd.ok {
-		prices = append(prices, ysd.prices.Prices...)
-		offset = 24
+	if cur == nil {
+		return nil, nil, 0, false
+	}
+	if prev != nil && prev.Pending == 0 {
+		prices = append(prices, prev.Prices...)
+		dayLens = append(dayLens, prev.Slots())
+		offset = len(prev.Prices)
 	}
-	prices = append(prices, td.prices.Prices...)
-	if tmw.ok {
-		prices = append(prices, tmw.prices.Prices...)
+	prices = append(prices, cur.Prices...)
+	dayLens = append(dayLens, cur.Slots())
+	if withNext && cur.Pending == 0 && days[2].Prices != nil {
+		prices = append(prices, days[2].Prices.Prices...)
+		dayLens = append(dayLens, days[2].Prices.Slots())
 	}
+	return prices, dayLens, offset, true
+}
 
-	nowLocal := time.Date(now.Year(), now.Month(), now.Day(), now.Hour(), now.Minute(), now.Second(), now.Nanosecond(), time.UTC)
-	return NewEvaluateContext(nowLocal, prices, hour+offset)
+// DatedPrices is one day of PricesRange. Prices is nil when the day is
+// unavailable.
+type DatedPrices struct {
+	Date   time.Time
+	Prices *DayPrices
+}
+
+// PricesRange loads every day from from to to inclusive, concurrently. Days
+// go through GetPrices, so cached ones are read from the DB and the uncached
+// ones queue for the fetch limit like any other request. The result is in
+// date order and empty when to is before from.
+func (s *AppState) PricesRange(from, to time.Time) []DatedPrices {
+	var out []DatedPrices
+	for d := from; !d.After(to); d = d.AddDate(0, 0, 1) {
+		out = append(out, DatedPrices{Date: d})
+	}
+	var wg sync.WaitGroup
+	for i := range out {
+		wg.Add(1)
+		go func(i int) {
+			defer wg.Done()
+			if prices, ok := s.GetPrices(out[i].Date); ok {
+				out[i].Prices = prices
+			}
+		}(i)
+	}
+	wg.Wait()
+	return out
 }
 
 type Currency int
@@ -244,14 +682,29 @@ const (
 	CurrencyCzk
 )
 
-const CurrencyRate float32 = 24.30
+// DefaultCurrencyRate is the built-in CZK per EUR for displayed prices.
+const DefaultCurrencyRate float32 = 24.30
+
+// CurrencyRate is the CZK per EUR used by Convert. main sets it from
+// OTE_CZK_RATE.
+var CurrencyRate = DefaultCurrencyRate
+
+// EurMWhToCzkKWh converts a price in EUR/MWh to CZK/kWh at rate CZK per EUR.
+func EurMWhToCzkKWh(price, rate float32) float32 {
+	return price * rate / 1000.0
+}
 
 func (c Currency) Convert(price float32) float32 {
+	return c.ConvertAt(price, CurrencyRate)
+}
+
+// ConvertAt converts like Convert but with an explicit EUR→CZK rate.
+func (c Currency) ConvertAt(price, rate float32) float32 {
 	switch c {
 	case CurrencyEur:
 		return price
 	case CurrencyCzk:
-		return price * CurrencyRate / 1000.0
+		return EurMWhToCzkKWh(price, rate)
 	}
 	return price
 }
@@ -266,6 +719,17 @@ func (c Currency) ShortLabel() string {
 	return ""
 }
 
+// UnitSuffix is the compact unit shown after prices when the user asks for it.
+func (c Currency) UnitSuffix() string {
+	switch c {
+	case CurrencyEur:
+		return "€/MWh"
+	case CurrencyCzk:
+		return "Kč/kWh"
+	}
+	return ""
+}
+
 func (c Currency) String() string {
 	switch c {
 	case CurrencyEur:
@@ -2,14 +2,24 @@ package webserver
 
 import (
 	"compress/gzip"
+	"context"
+	"encoding/csv"
+	"encoding/json"
+	"errors"
 	"fmt"
 	"html"
 	"io"
 	"log"
+	"math"
+	"net"
 	"net/http"
+	"net/url"
 	"os"
+	"os/signal"
+	"slices"
 	"strconv"
 	"strings"
+	"syscall"
 	"time"
 
 	"github.com/MichalKalita/ote/storage"
@@ -19,24 +29,41 @@ import (
 // StartWebServer starts the HTTP server on $PORT (default 3000).
 func StartWebServer(db *storage.DB) {
 	state := NewAppState(db)
-
-	mux := http.NewServeMux()
-	mux.HandleFunc("/", func(w http.ResponseWriter, r *http.Request) {
-		if r.URL.Path != "/" {
-			http.NotFound(w, r)
-			return
+	dist, err := DistributionFromEnv(os.Getenv)
+	if err != nil {
+		log.Fatal(err)
+	}
+	state.Distribution = dist
+	if v := os.Getenv("OTE_CACHE_TTL_HOURS"); v != "" {
+		hours, err := strconv.ParseFloat(v, 64)
+		if err != nil || hours < 0 || math.IsInf(hours, 0) {
+			log.Fatalf("OTE_CACHE_TTL_HOURS must be a non-negative number, got %q", v)
 		}
-		routeGetRoot(state, w, r)
-	})
-	mux.HandleFunc("/optimizer", func(w http.ResponseWriter, r *http.Request) {
-		routeGetOptimizer(state, w, r)
-	})
-	mux.HandleFunc("/opt", func(w http.ResponseWriter, r *http.Request) {
-		routeGetOpt(state, w, r)
-	})
-	mux.HandleFunc("/consumption", func(w http.ResponseWriter, r *http.Request) {
-		routeConsumption(state, w, r)
-	})
+		state.CacheTTL = time.Duration(hours * float64(time.Hour))
+	}
+	if v := os.Getenv("OTE_MAX_FETCHES"); v != "" {
+		n, err := strconv.Atoi(v)
+		if err != nil || n < 1 {
+			log.Fatalf("OTE_MAX_FETCHES must be a positive integer, got %q", v)
+		}
+		state.SetFetchLimit(n)
+	}
+	if v, ok := os.LookupEnv("OTE_CORS_ORIGIN"); ok {
+		state.CORSOrigin = v
+	}
+	if v := os.Getenv("OTE_PUBLIC_URL"); v != "" {
+		state.PublicURL = strings.TrimSuffix(v, "/")
+	}
+	if v := os.Getenv("OTE_FAVORITES"); v != "" {
+		favorites, err := ParseFavorites(v)
+		if err != nil {
+			log.Fatal(err)
+		}
+		state.Favorites = favorites
+	}
+	ctx, stop := signal.NotifyContext(context.Background(), os.Interrupt, syscall.SIGTERM)
+	defer stop()
+	go state.RunPrefetcher(ctx)
 
 	port := os.Getenv("PORT")
 	if port == "" {
@@ -44,16 +71,90 @@ func StartWebServer(db *storage.DB) {
 	}
 	addr := "0.0.0.0:" + port
 
-	srv := &http.Server{
-		Addr:    addr,
-		Handler: compressionMiddleware(mux),
+	ln, err := net.Listen("tcp", addr)
+	if err != nil {
+		log.Fatal(err)
 	}
+	srv := &http.Server{Handler: newHandler(state)}
 	fmt.Printf("Web server started on %s\n", addr)
-	if err := srv.ListenAndServe(); err != nil {
+	if err := serveUntil(ctx, srv, ln); err != nil {
 		log.Fatal(err)
 	}
 }
 
+// ShutdownTimeout bounds how long in-flight requests may take to finish once
+// the server is asked to stop.
+const ShutdownTimeout = 10 * time.Second
+
+// serveUntil serves on ln until ctx is cancelled, then stops accepting
+// connections and waits for in-flight requests to finish.
+func serveUntil(ctx context.Context, srv *http.Server, ln net.Listener) error {
+	errc := make(chan error, 1)
+	go func() { errc <- srv.Serve(ln) }()
+	select {
+	case err := <-errc:
+		return err
+	case <-ctx.Done():
+	}
+	log.Print("shutting down")
+	shutdownCtx, cancel := context.WithTimeout(context.Background(), ShutdownTimeout)
+	defer cancel()
+	if err := srv.Shutdown(shutdownCtx); err != nil {
+		return err
+	}
+	if err := <-errc; !errors.Is(err, http.ErrServerClosed) {
+		return err
+	}
+	return nil
+}
+
+// logRequests logs method, path, status and latency of every request.
+func logRequests(next http.Handler) http.Handler {
+	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
+		start := time.Now()
+		sw := &statusWriter{ResponseWriter: w, status: http.StatusOK}
+		next.ServeHTTP(sw, r)
+		log.Printf("%s %s %d %v", r.Method, r.URL.Path, sw.status, time.Since(start).Round(time.Microsecond))
+	})
+}
+
+// statusWriter remembers the status code written through it.
+type statusWriter struct {
+	http.ResponseWriter
+	status int
+}
+
+func (s *statusWriter) WriteHeader(code int) {
+	s.status = code
+	s.ResponseWriter.WriteHeader(code)
+}
+
+func (s *statusWriter) Flush() {
+	if f, ok := s.ResponseWriter.(http.Flusher); ok {
+		f.Flush()
+	}
+}
+
+// withCORS lets pages on origin call h with GET and answers its preflight
+// requests. An empty origin leaves h unchanged.
+func withCORS(origin string, h http.HandlerFunc) http.HandlerFunc {
+	if origin == "" {
+		return h
+	}
+	return func(w http.ResponseWriter, r *http.Request) {
+		w.Header().Set("Access-Control-Allow-Origin", origin)
+		if origin != "*" {
+			w.Header().Add("Vary", "Origin")
+		}
+		if r.Method == http.MethodOptions {
+			w.Header().Set("Access-Control-Allow-Methods", "GET")
+			w.WriteHeader(http.StatusNoContent)
+			return
+		}
+		h(w, r)
+	}
+}
+
 // compressionMiddleware applies br/gzip compression based on Accept-Encoding.
 func compressionMiddleware(next http.Handler) http.Handler {
 	return http.HandlerFunc(func(w http.ResponseWriter, r *http.Request) {
@@ -86,32 +187,110 @@ func (c *compressionWriter) Write(b []byte) (int, error) {
 	return c.w.Write(b)
 }
 
+// Flush pushes buffered compressed bytes to the client so streamed responses
+// stay incremental under compression.
+func (c *compressionWriter) Flush() {
+	if f, ok := c.w.(interface{ Flush() error }); ok {
+		f.Flush()
+	}
+	if f, ok := c.ResponseWriter.(http.Flusher); ok {
+		f.Flush()
+	}
+}
+
+// prefersJSON reports whether the Accept header ranks application/json above
+// text/html. A missing header, */* and browsers' lists all get HTML.
+func prefersJSON(accept string) bool {
+	var jsonQ, htmlQ float64
+	for _, part := range strings.Split(accept, ",") {
+		media, params, _ := strings.Cut(part, ";")
+		q := 1.0
+		for _, p := range strings.Split(params, ";") {
+			if k, v, ok := strings.Cut(strings.TrimSpace(p), "="); ok && k == "q" {
+				if f, err := strconv.ParseFloat(v, 64); err == nil {
+					q = f
+				}
+			}
+		}
+		switch strings.ToLower(strings.TrimSpace(media)) {
+		case "application/json":
+			jsonQ = max(jsonQ, q)
+		case "text/html":
+			htmlQ = max(htmlQ, q)
+		}
+	}
+	return jsonQ > 0 && jsonQ > htmlQ
+}
+
 func routeGetRoot(state *AppState, w http.ResponseWriter, r *http.Request) {
-	loc, err := time.LoadLocation("Europe/Prague")
-	if err != nil {
-		loc = time.UTC
+	// Scripts asking for JSON get the /api/prices document of the same day.
+	w.Header().Add("Vary", "Accept")
+	if prefersJSON(r.Header.Get("Accept")) {
+		routeAPIPrices(state, w, r)
+		return
 	}
-	now := time.Now().In(loc)
-	today := time.Date(now.Year(), now.Month(), now.Day(), 0, 0, 0, 0, loc)
+
+	loc := PragueLocation()
+	now, today, slot := localToday(state.Clock)
 
 	q := r.URL.Query()
 	inputDate := today
 	if d := q.Get("date"); d != "" {
-		if parsed, err := time.ParseInLocation("2006-01-02", d, loc); err == nil {
-			inputDate = parsed
+		parsed, err := parseDateParam(d, today)
+		if err != nil {
+			writeHTML(w, http.StatusBadRequest,
+				fmt.Sprintf(`<p class="my-8 text-red-600 dark:text-red-400">%s</p><p>%s</p>`,
+					html.EscapeString(err.Error()), Link("/", "Show today's prices")))
+			return
 		}
+		inputDate = parsed
 	}
-	currency := CurrencyEur
-	if cur := q.Get("cur"); cur != "" {
-		if c, err := ParseCurrency(cur); err == nil {
-			currency = c
+	currency := requestCurrency(q)
+	includeDist := q.Get("dist") == "true"
+	bothSeries := q.Get("series") == "both"
+	sortByPrice := q.Get("sort") == "price"
+	marker := ParseNowMarker(q.Get("mark"))
+	// ?exp= overlays a condition on the chart, like /optimizer does.
+	var overlay *Condition
+	var overlayErr error
+	if exp := q.Get("exp"); exp != "" {
+		c, err := ParseCondition(exp)
+		if err == nil {
+			err = c.Validate()
+		}
+		if err != nil {
+			overlayErr = err
+		} else {
+			overlay = &c
 		}
 	}
-	includeDist := q.Get("dist") == "true"
 
 	chart := DefaultChartSettings()
+	applyChartSize(&chart, q)
+	tableFormat := DefaultTableFormat()
+	if v := q.Get("precision"); v != "" {
+		if n, err := strconv.Atoi(v); err == nil {
+			n = max(0, min(4, n))
+			chart.Labels.Precision = n
+			tableFormat.Precision = n
+		}
+	}
+	if q.Get("unit") == "true" {
+		chart.Labels.Unit = currency.UnitSuffix()
+		tableFormat.Unit = currency.UnitSuffix()
+	}
+
+	dist, rate, err := parseOverrides(q, state.Distribution)
+	if err != nil {
+		writeHTML(w, http.StatusBadRequest,
+			fmt.Sprintf(`<p class="my-8 text-red-600 dark:text-red-400">%s</p>`, html.EscapeString(err.Error())))
+		return
+	}
+	chart.Labels.Rate = rate
+	tableFormat.Rate = rate
 
-	prices, ok := state.GetPrices(inputDate)
+	prices, loadErr := state.LoadPrices(inputDate)
+	ok := loadErr == nil
 
 	var sb strings.Builder
 	fmt.Fprintf(&sb, `<h1 class="text-4xl font-bold">OTE prices %s</h1>`,
@@ -120,6 +299,9 @@ func routeGetRoot(state *AppState, w http.ResponseWriter, r *http.Request) {
 	sb.WriteString(Link("/optimizer", "Optimizer"))
 	sb.WriteString(" | ")
 	sb.WriteString(Link("/consumption", "Consumption analysis"))
+	if ok && inputDate.Equal(today) {
+		sb.WriteString(prices.RenderNow(slot, &dist, currency, tableFormat))
+	}
 	sb.WriteString(`<div class="flex flex-row justify-center gap-2">`)
 	curStr := currency.String()
 	distStr := strconv.FormatBool(includeDist)
@@ -148,7 +330,7 @@ func routeGetRoot(state *AppState, w http.ResponseWriter, r *http.Request) {
 	sb.WriteString(`</div>`)
 
 	maxDate := today
-	if now.Hour() >= NextDayPricesHour {
+	if nextDayPublished(now, today) {
 		maxDate = today.AddDate(0, 0, 1)
 	}
 	monthAvgs := state.MonthAverages(inputDate.Year(), inputDate.Month(), loc, includeDist, maxDate)
@@ -157,59 +339,439 @@ func routeGetRoot(state *AppState, w http.ResponseWriter, r *http.Request) {
 	sb.WriteString(`</div>`)
 
 	status := http.StatusOK
-	if !ok {
+	if !ok && errors.Is(loadErr, ErrNoPrices) {
 		status = http.StatusNotFound
 		sb.WriteString(`<p class="my-8 text-red-600 dark:text-red-400">Error fetching data for this date. Prices may not be published yet — try another date.</p>`)
+	} else if !ok {
+		status = http.StatusServiceUnavailable
+		w.Header().Set("Retry-After", "60")
+		sb.WriteString(`<p class="my-8 text-red-600 dark:text-red-400">Prices could not be loaded from OTE right now. Please try again in a minute.</p>`)
 	} else {
-		totalPrices := prices.TotalPrices(&state.Distribution)
+		totalPrices := prices.TotalPrices(&dist)
 		var displayPrices []float32
 		if includeDist {
 			displayPrices = totalPrices
 		} else {
 			displayPrices = prices.Prices
 		}
-		cheapestIdx, minPrice := CheapestHour(displayPrices)
-		expensiveIdx, maxPrice := ExpensiveHour(displayPrices)
+		_, minPrice, _ := CheapestHour(displayPrices)
+		_, maxPrice, _ := ExpensiveHour(displayPrices)
 
 		var sum float32
 		for _, p := range displayPrices {
 			sum += p
 		}
-		avgPrice := sum / float32(len(displayPrices))
+		var avgPrice float32
+		if len(displayPrices) > 0 {
+			avgPrice = sum / float32(len(displayPrices))
+		}
 
-		distLabels := state.Distribution.ByHours()
+		distLabels := dist.ByHours()
 		labels := distLabels[:]
 
 		fmt.Fprintf(&sb, `<div class="mb-4">Min: <span class="font-bold text-green-700 dark:text-green-400">%.2f</span> | Avg: <span class="font-bold">%.2f</span> | Max: <span class="font-bold text-red-700 dark:text-red-400">%.2f</span> %s</div>`,
-			currency.Convert(minPrice),
-			currency.Convert(avgPrice),
-			currency.Convert(maxPrice),
+			tableFormat.Convert(minPrice, currency),
+			tableFormat.Convert(avgPrice, currency),
+			tableFormat.Convert(maxPrice, currency),
 			html.EscapeString(currency.ShortLabel()))
+		if prices.Pending > 0 {
+			fmt.Fprintf(&sb, `<p class="mb-4 text-orange-700 dark:text-orange-400">OTE has not published %d quarter-hours of this day yet; they are shown as pending.</p>`, prices.Pending)
+		}
 
-		fmt.Fprintf(&sb, `<div data-page-date="%s">`, inputDate.Format("2006-01-02"))
+		// data-day-start lets the page script index the current quarter by
+		// elapsed time, which stays right on DST days.
+		fmt.Fprintf(&sb, `<div data-page-date="%s" data-day-start="%d">`, inputDate.Format("2006-01-02"), inputDate.UnixMilli())
 		sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Graph</h2>`)
-		sb.WriteString(`<div class="mb-4 flex justify-center">`)
-		sb.WriteString(chart.Render(displayPrices, labels, func(index int, price float32) string {
-			if index == cheapestIdx || price < 0.0 {
-				return "fill-green-600"
-			}
-			if index == expensiveIdx {
-				return "fill-red-600"
+		if bothSeries {
+			sb.WriteString(`<p class="text-sm mb-2">Left bar: market price, right bar: including distribution</p>`)
+		}
+		// Today's current quarter is marked client-side; other days have no
+		// "now", so the optional marker stands in for it.
+		markIdx := -1
+		if !inputDate.Equal(today) && marker == MarkCheapest {
+			markIdx, _, _ = CheapestHour(displayPrices)
+		}
+		colorBy := priceBarColor
+		if q.Get("colors") == "gradient" {
+			colorBy = gradientBarColor
+		}
+		barColor := colorBy(displayPrices)
+		if bothSeries {
+			barColor = colorBy(prices.Prices)
+		}
+		if overlayErr != nil {
+			fmt.Fprintf(&sb, `<p class="mb-2 text-red-600 dark:text-red-400">Invalid expression: %s</p>`, html.EscapeString(overlayErr.Error()))
+		} else if overlay != nil {
+			if ctx, start := state.DayContext(inputDate); ctx != nil {
+				results := overlay.EvaluateAll(ctx)
+				barColor = conditionBarColor(results[start:min(start+len(prices.Prices), len(results))])
+				fmt.Fprintf(&sb, `<p class="text-sm mb-2">Green bars match <span class="font-mono">%s</span></p>`, html.EscapeString(overlay.Expression()))
 			}
-			return "fill-gray-500"
-		}, currency))
+		}
+		sb.WriteString(`<div class="mb-4 flex justify-center">`)
+		if bothSeries {
+			sb.WriteString(chart.RenderGrouped(prices.Prices, totalPrices, labels, withMarker(barColor, markIdx), "fill-blue-300", currency))
+		} else {
+			sb.WriteString(chart.Render(displayPrices, labels, withMarker(barColor, markIdx), currency))
+		}
 		sb.WriteString(`</div>`)
 
 		sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Table</h2>`)
+		sb.WriteString(NegativeBanner(prices.Prices, currency, tableFormat))
+		toggle := r.URL.Query()
+		if sortByPrice {
+			toggle.Del("sort")
+			sb.WriteString(`<p class="mb-2">` + Link("/?"+toggle.Encode(), "Sort by time") + `</p>`)
+		} else {
+			toggle.Set("sort", "price")
+			sb.WriteString(`<p class="mb-2">` + Link("/?"+toggle.Encode(), "Sort by price") + `</p>`)
+		}
+		sb.WriteString(`<div class="mb-4 flex justify-center">`)
+		if sortByPrice {
+			sb.WriteString(prices.RenderTableByPrice(&dist, currency, includeDist, tableFormat))
+		} else {
+			sb.WriteString(prices.RenderTable(&dist, currency, includeDist, tableFormat))
+		}
+		sb.WriteString(`</div>`)
 		sb.WriteString(`<div class="mb-4 flex justify-center">`)
-		sb.WriteString(prices.RenderTable(&state.Distribution, currency, includeDist))
+		sb.WriteString(prices.RenderStats(&dist, currency, tableFormat))
 		sb.WriteString(`</div>`)
 		sb.WriteString(`</div>`)
 	}
 
-	w.Header().Set("Content-Type", "text/html; charset=utf-8")
-	w.WriteHeader(status)
-	io.WriteString(w, RenderLayout(sb.String()))
+	meta := PageMeta{
+		Title: "OTE prices " + inputDate.Format("2006-01-02"),
+		Image: fmt.Sprintf("%s/card.png?date=%s&cur=%s", state.PublicURL, inputDate.Format("2006-01-02"), curStr),
+	}
+	writeHTMLMeta(w, status, sb.String(), meta)
+}
+
+type route struct {
+	path    string
+	handler http.HandlerFunc
+}
+
+// routes lists every page and endpoint. newHandler registers them and the
+// route tests walk the same list.
+func routes(state *AppState) []route {
+	withState := func(h func(*AppState, http.ResponseWriter, *http.Request)) http.HandlerFunc {
+		return func(w http.ResponseWriter, r *http.Request) { h(state, w, r) }
+	}
+	// api marks the JSON endpoints browser dashboards on other origins call.
+	api := func(h http.HandlerFunc) http.HandlerFunc {
+		return withCORS(state.CORSOrigin, h)
+	}
+	return []route{
+		{"/", func(w http.ResponseWriter, r *http.Request) {
+			if r.URL.Path != "/" {
+				http.NotFound(w, r)
+				return
+			}
+			routeGetRoot(state, w, r)
+		}},
+		{"/optimizer", withState(routeGetOptimizer)},
+		{"/opt", api(withState(routeGetOpt))},
+		{"/ha", api(withState(routeHA))},
+		{"/hours", api(withState(routeHours))},
+		{"/schedule", api(withState(routeSchedule))},
+		{"/battery", api(withState(routeBattery))},
+		{"/consumption", withState(routeConsumption)},
+		{"/compare", withState(routeCompare)},
+		{"/week", withState(routeWeek)},
+		{"/api/validate", api(routeAPIValidate)},
+		{"/normalize", api(routeNormalize)},
+		{"/chart.png", withState(routeChartPNG)},
+		{"/chart.svg", withState(routeChartSVG)},
+		{"/api/best-start", api(withState(routeAPIBestStart))},
+		{"/api/export", api(withState(routeAPIExport))},
+		{"/api/prices", api(withState(routeAPIPrices))},
+		{"/api/prices.csv", api(withState(routeAPIPricesCSV))},
+		{"/api/raw", api(withState(routeAPIRaw))},
+		{"/api/today/cheapest", api(withState(routeAPITodayHour(false)))},
+		{"/api/today/expensive", api(withState(routeAPITodayHour(true)))},
+		// /export.csv is the spreadsheet-friendly name for the same export.
+		{"/export.csv", withState(routeAPIPricesCSV)},
+		{"/card.png", withState(routeCardPNG)},
+		{"/cheap.ics", withState(routeCheapICS)},
+		{"/perf", withState(routePerf)},
+		{"/healthz", routeHealthz},
+		{"/readyz", withState(routeReadyz)},
+	}
+}
+
+// newHandler builds the mux of all routes behind the compression middleware.
+func newHandler(state *AppState) http.Handler {
+	mux := http.NewServeMux()
+	for _, rt := range routes(state) {
+		mux.HandleFunc(rt.path, rt.handler)
+	}
+	return logRequests(compressionMiddleware(mux))
+}
+
+// priceBarColor colors the cheapest bar and any negative price green, the most
+// expensive bar red, and everything else gray.
+func priceBarColor(prices []float32) func(index int, price float32) string {
+	cheapestIdx, _, _ := CheapestHour(prices)
+	expensiveIdx, _, _ := ExpensiveHour(prices)
+	return func(index int, price float32) string {
+		if index == cheapestIdx || price < 0.0 {
+			return "fill-green-600"
+		}
+		if index == expensiveIdx {
+			return "fill-red-600"
+		}
+		return "fill-gray-500"
+	}
+}
+
+// gradientClasses run from the cheapest to the most expensive bar color.
+var gradientClasses = []string{"fill-green-600", "fill-green-400", "fill-yellow-400", "fill-orange-500", "fill-red-600"}
+
+// gradientBarColor colors each bar by where its price sits between the day's
+// minimum and maximum, from green through yellow to red. A flat day is all
+// green.
+func gradientBarColor(prices []float32) func(index int, price float32) string {
+	_, lo, _ := CheapestHour(prices)
+	_, hi, _ := ExpensiveHour(prices)
+	return func(_ int, price float32) string {
+		if !(hi > lo) {
+			return gradientClasses[0]
+		}
+		step := int((price - lo) / (hi - lo) * float32(len(gradientClasses)))
+		return gradientClasses[max(0, min(step, len(gradientClasses)-1))]
+	}
+}
+
+// conditionBarColor colors the bars where a condition matched green and the
+// rest gray. matches is indexed like the chart bars.
+func conditionBarColor(matches []bool) func(index int, price float32) string {
+	return func(index int, _ float32) string {
+		if index < len(matches) && matches[index] {
+			return "fill-green-600"
+		}
+		return "fill-gray-500"
+	}
+}
+
+// NowMarker selects what the day chart highlights when the viewed day is not
+// today and so has no current quarter to mark.
+type NowMarker int
+
+const (
+	MarkNone     NowMarker = iota
+	MarkCheapest           // the cheapest quarter of the day
+)
+
+// ParseNowMarker reads ?mark=; unknown values mean MarkNone.
+func ParseNowMarker(s string) NowMarker {
+	if s == "cheapest" {
+		return MarkCheapest
+	}
+	return MarkNone
+}
+
+// withMarker colors bar idx like the live "now" marker and defers to color for
+// the rest. A negative idx marks nothing.
+func withMarker(color func(index int, price float32) string, idx int) func(index int, price float32) string {
+	return func(index int, price float32) string {
+		if index == idx {
+			return "fill-blue-600"
+		}
+		return color(index, price)
+	}
+}
+
+// parseOverrides applies the what-if query parameters ?rate=, ?high_price=
+// and ?low_price= (EUR/MWh) to a copy of dist, for the current request only.
+// A returned rate of zero means no override.
+func parseOverrides(q url.Values, dist Distribution) (Distribution, float32, error) {
+	var rate float32
+	for _, p := range []struct {
+		name string
+		dst  *float32
+	}{{"rate", &rate}, {"high_price", &dist.HighPrice}, {"low_price", &dist.LowPrice}} {
+		v := q.Get(p.name)
+		if v == "" {
+			continue
+		}
+		f, err := strconv.ParseFloat(v, 32)
+		if err != nil || math.IsInf(f, 0) || math.IsNaN(f) || f <= 0 {
+			return Distribution{}, 0, fmt.Errorf("%s must be a finite positive number, got %q", p.name, v)
+		}
+		*p.dst = float32(f)
+	}
+	return dist, rate, nil
+}
+
+// requestDate returns the Prague-local date from ?date=YYYY-MM-DD (today when
+// absent) together with today's date. A malformed or out-of-range date is an
+// error, see parseDateParam.
+func requestDate(r *http.Request, clock Clock) (date, today time.Time, err error) {
+	_, today, _ = localToday(clock)
+	date = today
+	if d := r.URL.Query().Get("date"); d != "" {
+		date, err = parseDateParam(d, today)
+	}
+	return date, today, err
+}
+
+// MaxDateYears bounds how far ?date= may lie from today, so absurd dates are
+// rejected before anything is fetched.
+const MaxDateYears = 10
+
+// parseDateParam parses a YYYY-MM-DD ?date= in today's location, rejecting
+// dates more than MaxDateYears away from today.
+func parseDateParam(s string, today time.Time) (time.Time, error) {
+	date, err := time.ParseInLocation("2006-01-02", s, today.Location())
+	if err != nil {
+		return time.Time{}, fmt.Errorf("invalid date %q: use the YYYY-MM-DD format, e.g. %s", s, today.Format("2006-01-02"))
+	}
+	if date.Before(today.AddDate(-MaxDateYears, 0, 0)) || date.After(today.AddDate(MaxDateYears, 0, 0)) {
+		return time.Time{}, fmt.Errorf("date %s is out of range: it must be within %d years of today", s, MaxDateYears)
+	}
+	return date, nil
+}
+
+// requestCurrency reads the display currency from ?currency= or its short
+// form ?cur=. Missing or unknown values mean EUR.
+func requestCurrency(q url.Values) Currency {
+	for _, key := range []string{"currency", "cur"} {
+		if v := q.Get(key); v != "" {
+			if c, err := ParseCurrency(v); err == nil {
+				return c
+			}
+		}
+	}
+	return CurrencyEur
+}
+
+// routeChartPNG serves the day chart rasterized to PNG for clients that cannot
+// display SVG. Past days never change, so their images are marked cacheable
+// and kept in memory by date and scale instead of being re-rendered.
+func routeChartPNG(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, today, err := requestDate(r, state.Clock)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	scale := 1
+	if v := r.URL.Query().Get("scale"); v != "" {
+		if n, err := strconv.Atoi(v); err == nil {
+			scale = max(1, min(MaxPNGScale, n))
+		}
+	}
+	key := pngCacheKey(date.Format("2006-01-02"), scale)
+	body, cacheable := state.pngs.get(key)
+	if !cacheable {
+		prices, ok := state.GetPrices(date)
+		if !ok {
+			http.Error(w, "No prices for this date", http.StatusNotFound)
+			return
+		}
+		body, err = DefaultChartSettings().RenderPNG(prices.Prices, priceBarColor(prices.Prices), scale)
+		if err != nil {
+			log.Printf("RenderPNG(%s) error: %v", date.Format("2006-01-02"), err)
+			http.Error(w, "Failed to render chart", http.StatusInternalServerError)
+			return
+		}
+		// A partial day is still being published, so its chart will change.
+		cacheable = date.Before(today) && prices.Pending == 0
+		if cacheable {
+			state.pngs.put(key, body)
+		}
+	}
+	w.Header().Set("Content-Type", "image/png")
+	if cacheable {
+		w.Header().Set("Cache-Control", "public, max-age=86400, immutable")
+	}
+	w.Write(body)
+}
+
+// applyChartSize overrides the chart's height and bar width from ?height= and
+// ?bar_width=, clamped to the Min/Max chart bounds. Values that are not
+// integers are ignored, like ?precision=.
+func applyChartSize(chart *ChartSettings, q url.Values) {
+	if n, err := strconv.Atoi(q.Get("height")); err == nil {
+		chart.Height = float32(max(MinChartHeight, min(MaxChartHeight, n)))
+	}
+	if n, err := strconv.Atoi(q.Get("bar_width")); err == nil {
+		chart.BarWidth = max(MinBarWidth, min(MaxBarWidth, n))
+	}
+}
+
+// routeChartSVG serves the day chart as a standalone SVG document, with the
+// distribution labels of the root page and the styles its classes need.
+// Like chart.png, complete past days are cacheable.
+func routeChartSVG(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, today, err := requestDate(r, state.Clock)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	prices, ok := state.GetPrices(date)
+	if !ok {
+		http.Error(w, "No prices for this date", http.StatusNotFound)
+		return
+	}
+	chart := DefaultChartSettings()
+	applyChartSize(&chart, r.URL.Query())
+	labels := state.Distribution.ByHours()
+	svg := chart.Render(prices.Prices, labels[:], priceBarColor(prices.Prices), CurrencyEur)
+	classes := make(map[string]struct{})
+	ExtractClassesFromHTML(svg, classes)
+	// An <img> embedding the document takes its size from width and height.
+	m := chart.calculateMetrics(prices.Prices)
+	svg = strings.Replace(svg, "<svg ", fmt.Sprintf(`<svg xmlns="http://www.w3.org/2000/svg" width="%d" height="%s" `,
+		m.svgWidth, fmtFloat(m.svgHeight)), 1)
+	svg = strings.Replace(svg, "<g>", "<style><![CDATA["+GenerateCSS(classes)+"]]></style><g>", 1)
+
+	w.Header().Set("Content-Type", "image/svg+xml")
+	// A partial day is still being published, so its chart will change.
+	if date.Before(today) && prices.Pending == 0 {
+		w.Header().Set("Cache-Control", "public, max-age=86400, immutable")
+	}
+	io.WriteString(w, `<?xml version="1.0" encoding="UTF-8"?>`+"\n"+svg)
+}
+
+// routeCardPNG serves the social preview card linked from the root page's
+// og:image. Like chart.png, complete past days are cacheable and kept in
+// memory, per currency.
+func routeCardPNG(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, today, err := requestDate(r, state.Clock)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	currency := requestCurrency(r.URL.Query())
+	key := cardCacheKey(date.Format("2006-01-02"), currency)
+	body, cacheable := state.pngs.get(key)
+	if !cacheable {
+		prices, err := state.LoadPrices(date)
+		if errors.Is(err, ErrNoPrices) {
+			http.Error(w, "No prices for this date", http.StatusNotFound)
+			return
+		} else if err != nil {
+			w.Header().Set("Retry-After", "60")
+			http.Error(w, "Prices could not be loaded from OTE", http.StatusServiceUnavailable)
+			return
+		}
+		body, err = RenderCardPNG(date, prices.Prices, currency)
+		if err != nil {
+			log.Printf("RenderCardPNG(%s) error: %v", date.Format("2006-01-02"), err)
+			http.Error(w, "Failed to render card", http.StatusInternalServerError)
+			return
+		}
+		// A partial day is still being published, so its card will change.
+		cacheable = date.Before(today) && prices.Pending == 0
+		if cacheable {
+			state.pngs.put(key, body)
+		}
+	}
+	w.Header().Set("Content-Type", "image/png")
+	if cacheable {
+		w.Header().Set("Cache-Control", "public, max-age=86400, immutable")
+	}
+	w.Write(body)
 }
 
 func parseOptQuery(q map[string][]string) (exp string, hours, from, to *uint8) {
@@ -237,6 +799,71 @@ func parseOptQuery(q map[string][]string) (exp string, hours, from, to *uint8) {
 	return
 }
 
+// backtestDay is the day of ?date= the optimizer evaluates a condition on,
+// to see how it would have behaved on past prices.
+type backtestDay struct {
+	Date  time.Time
+	Ctx   *EvaluateContext // spans the days around Date, see DayContext
+	Start int              // index of Date's first slot in Ctx
+	Slots int
+}
+
+// hourResult is a condition's result with now at the start of one hour.
+type hourResult struct {
+	Time    time.Time
+	Matched bool
+}
+
+// requestBacktest reads ?date=. It returns nil without one, so the caller
+// evaluates the live context instead; status and err describe a date that
+// cannot be evaluated.
+func requestBacktest(state *AppState, r *http.Request) (*backtestDay, int, error) {
+	v := r.URL.Query().Get("date")
+	if v == "" {
+		return nil, http.StatusOK, nil
+	}
+	_, today, _ := localToday(state.Clock)
+	date, err := parseDateParam(v, today)
+	if err != nil {
+		return nil, http.StatusBadRequest, err
+	}
+	prices, err := state.LoadPrices(date)
+	if errors.Is(err, ErrNoPrices) {
+		return nil, http.StatusNotFound, fmt.Errorf("no prices for %s", v)
+	}
+	var ctx *EvaluateContext
+	var start int
+	if err == nil {
+		ctx, start = state.DayContext(date)
+	}
+	if ctx == nil {
+		return nil, http.StatusServiceUnavailable, errors.New("prices could not be loaded from OTE")
+	}
+	return &backtestDay{Date: date, Ctx: ctx, Start: start, Slots: len(prices.Prices)}, http.StatusOK, nil
+}
+
+// Hourly evaluates c as if now were the start of each real hour of the day
+// in turn, so DST days have 23 or 25 results.
+func (d *backtestDay) Hourly(c Condition) []hourResult {
+	sph := d.Ctx.slotsPerHour()
+	var out []hourResult
+	for i := 0; i < d.Slots; i += sph {
+		out = append(out, hourResult{
+			Time:    d.Date.Add(time.Duration(i/sph) * time.Hour),
+			Matched: c.Evaluate(d.Ctx.at(d.Start + i)),
+		})
+	}
+	return out
+}
+
+// writeBacktestError reports a ?date= requestBacktest could not evaluate.
+func writeBacktestError(w http.ResponseWriter, status int, err error) {
+	if status == http.StatusServiceUnavailable {
+		w.Header().Set("Retry-After", "60")
+	}
+	http.Error(w, err.Error(), status)
+}
+
 func routeGetOptimizer(state *AppState, w http.ResponseWriter, r *http.Request) {
 	exp, hours, from, to := parseOptQuery(r.URL.Query())
 
@@ -260,17 +887,29 @@ func routeGetOptimizer(state *AppState, w http.ResponseWriter, r *http.Request)
 	} else {
 		condition = Condition{Kind: CondAnd}
 	}
+	if err := condition.Validate(); err != nil {
+		w.Header().Set("Content-Type", "text/plain; charset=utf-8")
+		w.WriteHeader(http.StatusOK)
+		fmt.Fprintf(w, "Invalid expression: %v", err)
+		return
+	}
 
-	expCtx := state.ExpressionContext()
-	if expCtx == nil {
+	day, status, err := requestBacktest(state, r)
+	if err != nil {
+		writeBacktestError(w, status, err)
+		return
+	}
+	var expCtx *EvaluateContext
+	if day != nil {
+		expCtx = day.Ctx
+	} else if expCtx = state.ExpressionContext(); expCtx == nil {
 		w.Header().Set("Content-Type", "text/plain; charset=utf-8")
 		w.WriteHeader(http.StatusOK)
 		io.WriteString(w, "Error creating expression context")
 		return
 	}
 
-	const domain = "https://ota.kalita.cz"
-	automationURL := fmt.Sprintf("%s/opt?exp=%s", domain, exp)
+	automationURL := fmt.Sprintf("%s/opt?exp=%s", state.PublicURL, exp)
 	examples := []string{`/optimizer?exp=[{"price":120},{"hours":[0,10]}]`}
 
 	var sb strings.Builder
@@ -279,18 +918,58 @@ func routeGetOptimizer(state *AppState, w http.ResponseWriter, r *http.Request)
 	sb.WriteString(Link("/", "Homepage"))
 	sb.WriteString(`<div class="text-left">`)
 	sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Condition</h2>`)
-	sb.WriteString(RenderCheapForm(cheapCondition))
+	dateParam := ""
+	if day != nil {
+		dateParam = day.Date.Format("2006-01-02")
+		fmt.Fprintf(&sb, `<p class="my-2">Evaluated on %s as if each of its hours were now. %s</p>`,
+			dateParam, Link("/optimizer?exp="+url.QueryEscape(exp), "Back to live prices"))
+	}
+	sb.WriteString(RenderCheapForm(cheapCondition, dateParam))
+	fmt.Fprintf(&sb, `<p class="font-mono my-2">%s</p>`, html.EscapeString(condition.Expression()))
 	sb.WriteString(condition.RenderHTML())
 	sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Evaluation</h2>`)
-	sb.WriteString(`<pre>`)
-	sb.WriteString(html.EscapeString(fmt.Sprintf("%v", condition.Evaluate(expCtx))))
-	sb.WriteString(`</pre>`)
+	if day != nil {
+		sb.WriteString(`<table class="font-mono"><tr><th class="px-2">Hour</th><th class="px-2">Result</th></tr>`)
+		for _, h := range day.Hourly(condition) {
+			fmt.Fprintf(&sb, `<tr><td class="px-2">%s</td><td class="px-2">%v</td></tr>`, h.Time.Format("15:04"), h.Matched)
+		}
+		sb.WriteString(`</table>`)
+	} else {
+		sb.WriteString(`<pre>`)
+		sb.WriteString(html.EscapeString(fmt.Sprintf("%v", condition.Evaluate(expCtx))))
+		sb.WriteString(`</pre>`)
+		if rank, ok := expCtx.CurrentRank(); ok {
+			sb.WriteString(RenderPriceRank(expCtx.ActualPrice(), rank))
+		}
+	}
 	fmt.Fprintf(&sb, `<a href="%s">URL for automation tools %s</a>`,
 		html.EscapeString(automationURL), html.EscapeString(automationURL))
 	sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Evaluate in Chart</h2>`)
 	sb.WriteString(`<div class="mb-4 flex justify-center">`)
-	sb.WriteString(condition.EvaluateAllInChart(expCtx))
+	sb.WriteString(condition.EvaluateAllInChart(expCtx, ParsePalette(r.URL.Query().Get("palette"))))
 	sb.WriteString(`</div>`)
+	if len(state.Favorites) > 0 {
+		sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Favorites</h2>`)
+		sb.WriteString(`<ul>`)
+		for _, f := range state.Favorites {
+			sb.WriteString(`<li>`)
+			sb.WriteString(Link(f.OptimizerURL(), f.Name))
+			if day != nil {
+				hours := day.Hourly(f.Condition)
+				matched := 0
+				for _, h := range hours {
+					if h.Matched {
+						matched++
+					}
+				}
+				fmt.Fprintf(&sb, ` <span class="font-mono">%d of %d hours</span>`, matched, len(hours))
+			} else {
+				fmt.Fprintf(&sb, ` <span class="font-mono">%v</span>`, f.Condition.Evaluate(expCtx))
+			}
+			sb.WriteString(`</li>`)
+		}
+		sb.WriteString(`</ul>`)
+	}
 	sb.WriteString(`<h2 class="text-2xl font-semibold mb-4">Examples</h2>`)
 	sb.WriteString(`<ul>`)
 	for _, ex := range examples {
@@ -301,9 +980,140 @@ func routeGetOptimizer(state *AppState, w http.ResponseWriter, r *http.Request)
 	sb.WriteString(`</ul>`)
 	sb.WriteString(`</div>`)
 
-	w.Header().Set("Content-Type", "text/html; charset=utf-8")
-	w.WriteHeader(http.StatusOK)
-	io.WriteString(w, RenderLayout(sb.String()))
+	writeHTML(w, http.StatusOK, sb.String())
+}
+
+// routeCompare shows two days side by side: ?a= defaults to today and ?b= to
+// tomorrow. Below the charts, each hour's average of b is compared with a's.
+// A missing day is noted; only both missing is a 404.
+func routeCompare(state *AppState, w http.ResponseWriter, r *http.Request) {
+	_, today, _ := localToday(state.Clock)
+	q := r.URL.Query()
+	dates := [2]time.Time{today, today.AddDate(0, 0, 1)}
+	for i, key := range []string{"a", "b"} {
+		if v := q.Get(key); v != "" {
+			d, err := parseDateParam(v, today)
+			if err != nil {
+				writeHTML(w, http.StatusBadRequest,
+					fmt.Sprintf(`<p class="my-8 text-red-600 dark:text-red-400">%s: %s</p>`, key, html.EscapeString(err.Error())))
+				return
+			}
+			dates[i] = d
+		}
+	}
+	currency := requestCurrency(q)
+	format := DefaultTableFormat()
+
+	var days [2]*DayPrices
+	for i, d := range dates {
+		if prices, ok := state.GetPrices(d); ok {
+			days[i] = prices
+		}
+	}
+
+	var sb strings.Builder
+	fmt.Fprintf(&sb, `<h1 class="text-4xl font-bold">Compare %s and %s</h1>`,
+		dates[0].Format("2006-01-02"), dates[1].Format("2006-01-02"))
+	sb.WriteString(`<p class="text-sm mb-8">` + Link("https://github.com/MichalKalita/ote", "github.com/MichalKalita/ote") + `</p>`)
+	sb.WriteString(Link("/", "Homepage"))
+
+	labels := state.Distribution.ByHours()
+	for i, d := range days {
+		fmt.Fprintf(&sb, `<h2 class="text-2xl font-semibold my-4">%s</h2>`, dates[i].Format("2006-01-02"))
+		if d == nil {
+			sb.WriteString(`<p class="mb-4 text-red-600 dark:text-red-400">No prices for this date. They may not be published yet.</p>`)
+			continue
+		}
+		sb.WriteString(`<div class="mb-4 flex justify-center">`)
+		sb.WriteString(DefaultChartSettings().Render(d.Prices, labels[:], priceBarColor(d.Prices), currency))
+		sb.WriteString(`</div>`)
+	}
+
+	status := http.StatusOK
+	switch {
+	case days[0] == nil && days[1] == nil:
+		status = http.StatusNotFound
+	case days[0] != nil && days[1] != nil:
+		avgA, hasA := days[0].ClockHourAverages()
+		avgB, hasB := days[1].ClockHourAverages()
+		sb.WriteString(`<h2 class="text-2xl font-semibold my-4">Difference</h2>`)
+		fmt.Fprintf(&sb, `<table class="mx-auto font-mono"><tr><th class="px-2">Hour</th><th class="px-2">%s</th><th class="px-2">%s</th><th class="px-2">Change</th></tr>`,
+			dates[0].Format("2006-01-02"), dates[1].Format("2006-01-02"))
+		for h := range avgA {
+			if !hasA[h] || !hasB[h] {
+				continue
+			}
+			diff := avgB[h] - avgA[h]
+			class := ""
+			switch {
+			case diff < 0:
+				class = "text-green-700 dark:text-green-400"
+			case diff > 0:
+				class = "text-red-700 dark:text-red-400"
+			}
+			fmt.Fprintf(&sb, `<tr><td class="px-2">%02d:00</td><td class="px-2 text-right">%.2f</td><td class="px-2 text-right">%.2f</td><td class="px-2 text-right %s">%+.2f</td></tr>`,
+				h, format.Convert(avgA[h], currency), format.Convert(avgB[h], currency), class, format.Convert(diff, currency))
+		}
+		fmt.Fprintf(&sb, `</table><p class="text-sm my-2">Hourly averages in %s; green got cheaper, red more expensive.</p>`,
+			html.EscapeString(currency.ShortLabel()))
+	}
+	writeHTML(w, status, sb.String())
+}
+
+// routeWeek shows seven days from ?start= (default this week's Monday) as one
+// hourly chart with each day's min, average and max. Days without prices are
+// gaps, not errors.
+func routeWeek(state *AppState, w http.ResponseWriter, r *http.Request) {
+	_, today, _ := localToday(state.Clock)
+	start := today.AddDate(0, 0, -(int(today.Weekday())+6)%7)
+	if v := r.URL.Query().Get("start"); v != "" {
+		d, err := parseDateParam(v, today)
+		if err != nil {
+			writeHTML(w, http.StatusBadRequest,
+				fmt.Sprintf(`<p class="my-8 text-red-600 dark:text-red-400">%s</p>`, html.EscapeString(err.Error())))
+			return
+		}
+		start = d
+	}
+	currency := requestCurrency(r.URL.Query())
+	format := DefaultTableFormat()
+
+	var dates []time.Time
+	var days []*DayPrices
+	for _, d := range state.PricesRange(start, start.AddDate(0, 0, 6)) {
+		dates = append(dates, d.Date)
+		days = append(days, d.Prices)
+	}
+
+	var sb strings.Builder
+	fmt.Fprintf(&sb, `<h1 class="text-4xl font-bold">Week from %s</h1>`, start.Format("2006-01-02"))
+	sb.WriteString(`<p class="text-sm mb-8">` + Link("https://github.com/MichalKalita/ote", "github.com/MichalKalita/ote") + `</p>`)
+	sb.WriteString(Link("/week?start="+start.AddDate(0, 0, -7).Format("2006-01-02"), "Previous week"))
+	sb.WriteString(" | ")
+	sb.WriteString(Link("/", "Homepage"))
+	sb.WriteString(" | ")
+	sb.WriteString(Link("/week?start="+start.AddDate(0, 0, 7).Format("2006-01-02"), "Next week"))
+	sb.WriteString(`<div class="my-4 flex justify-center">`)
+	sb.WriteString(WeekChartSettings().RenderWeek(dates, days, currency))
+	sb.WriteString(`</div>`)
+	sb.WriteString(`<table class="mx-auto font-mono"><tr><th class="px-2">Day</th><th class="px-2">Min</th><th class="px-2">Avg</th><th class="px-2">Max</th></tr>`)
+	for i, d := range days {
+		fmt.Fprintf(&sb, `<tr><td class="px-2">%s</td>`, Link("/?date="+dates[i].Format("2006-01-02"), dates[i].Format("Mon 2006-01-02")))
+		if d == nil || len(d.Prices) == 0 {
+			sb.WriteString(`<td class="px-2 text-center text-neutral-500" colspan="3">not published</td></tr>`)
+			continue
+		}
+		_, lo, _ := CheapestHour(d.Prices)
+		_, hi, _ := ExpensiveHour(d.Prices)
+		var sum float32
+		for _, p := range d.Prices {
+			sum += p
+		}
+		fmt.Fprintf(&sb, `<td class="px-2 text-right text-green-700 dark:text-green-400">%.2f</td><td class="px-2 text-right">%.2f</td><td class="px-2 text-right text-red-700 dark:text-red-400">%.2f</td></tr>`,
+			format.Convert(lo, currency), format.Convert(sum/float32(len(d.Prices)), currency), format.Convert(hi, currency))
+	}
+	fmt.Fprintf(&sb, `</table><p class="text-sm my-2">Prices in %s.</p>`, html.EscapeString(currency.ShortLabel()))
+	writeHTML(w, http.StatusOK, sb.String())
 }
 
 func routeGetOpt(state *AppState, w http.ResponseWriter, r *http.Request) {
@@ -317,11 +1127,30 @@ func routeGetOpt(state *AppState, w http.ResponseWriter, r *http.Request) {
 			fmt.Fprintf(w, "Error parsing expression: %v", err)
 			return
 		}
+		if err := parsed.Validate(); err != nil {
+			w.Header().Set("Content-Type", "text/plain; charset=utf-8")
+			fmt.Fprintf(w, "Invalid expression: %v", err)
+			return
+		}
 		condition = parsed
 	} else {
 		condition = Condition{Kind: CondAnd}
 	}
 
+	day, status, err := requestBacktest(state, r)
+	if err != nil {
+		writeBacktestError(w, status, err)
+		return
+	}
+	if day != nil {
+		// One line per hour of the day, e.g. "13:00 true".
+		w.Header().Set("Content-Type", "text/plain; charset=utf-8")
+		for _, h := range day.Hourly(condition) {
+			fmt.Fprintf(w, "%s %v\n", h.Time.Format("15:04"), h.Matched)
+		}
+		return
+	}
+
 	expCtx := state.ExpressionContext()
 	if expCtx == nil {
 		w.Header().Set("Content-Type", "text/plain; charset=utf-8")
@@ -334,13 +1163,631 @@ func routeGetOpt(state *AppState, w http.ResponseWriter, r *http.Request) {
 	fmt.Fprintf(w, "%v", result)
 }
 
-func routeConsumption(state *AppState, w http.ResponseWriter, r *http.Request) {
-	currency := CurrencyEur
-	if cur := r.URL.Query().Get("cur"); cur != "" {
-		if c, err := ParseCurrency(cur); err == nil {
-			currency = c
+type haResponse struct {
+	State        string     `json:"state"` // "on" or "off"
+	CurrentPrice float32    `json:"current_price"`
+	Matched      bool       `json:"matched"`
+	NextChange   *time.Time `json:"next_change"` // null when the result holds for all known prices
+}
+
+// routeHA is /opt for Home Assistant: a JSON sensor with the current result,
+// the price and when the result next flips.
+func routeHA(state *AppState, w http.ResponseWriter, r *http.Request) {
+	exp := r.URL.Query().Get("exp")
+	if exp == "" {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: "exp is required"})
+		return
+	}
+	condition, err := ParseCondition(exp)
+	if err == nil {
+		err = condition.Validate()
+	}
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: "invalid expression: " + err.Error()})
+		return
+	}
+
+	ctx := state.ExpressionContext()
+	if ctx == nil {
+		w.Header().Set("Retry-After", "60")
+		writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "prices are not available"})
+		return
+	}
+	resp := haResponse{
+		State:        "off",
+		CurrentPrice: ctx.ActualPrice(),
+		Matched:      condition.Evaluate(ctx),
+	}
+	if resp.Matched {
+		resp.State = "on"
+	}
+	if at, ok := condition.NextChange(ctx); ok {
+		at = pragueWallTime(at)
+		resp.NextChange = &at
+	}
+	writeJSON(w, http.StatusOK, resp)
+}
+
+type hoursResponse struct {
+	MatchedHours []int  `json:"matched_hours"` // hours of the day with at least one matched quarter
+	Results      []bool `json:"results"`       // one per quarter of today
+}
+
+// routeHours lists where ?exp= matches today, for debugging conditions.
+func routeHours(state *AppState, w http.ResponseWriter, r *http.Request) {
+	condition, err := ParseCondition(r.URL.Query().Get("exp"))
+	if err == nil {
+		err = condition.Validate()
+	}
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+
+	_, today, _ := localToday(state.Clock)
+	prices, err := state.LoadPrices(today)
+	if errors.Is(err, ErrNoPrices) {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + today.Format("2006-01-02")})
+		return
+	}
+	var ctx *EvaluateContext
+	var start int
+	if err == nil {
+		ctx, start = state.DayContext(today)
+	}
+	if ctx == nil {
+		w.Header().Set("Retry-After", "60")
+		writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "prices could not be loaded from OTE"})
+		return
+	}
+	all := condition.EvaluateAll(ctx)
+	resp := hoursResponse{
+		MatchedHours: []int{},
+		Results:      all[start:min(start+len(prices.Prices), len(all))],
+	}
+	var matched [24]bool
+	for i, ok := range resp.Results {
+		if ok {
+			matched[ctx.slotTime(start+i).Hour()] = true
+		}
+	}
+	for h, ok := range matched {
+		if ok {
+			resp.MatchedHours = append(resp.MatchedHours, h)
+		}
+	}
+	writeJSON(w, http.StatusOK, resp)
+}
+
+type scheduleEntry struct {
+	Hour  int       `json:"hour"`  // Prague wall-clock hour
+	Time  time.Time `json:"time"`  // start of the hour, Prague local
+	On    bool      `json:"on"`    // at least one quarter of the hour matched
+	Price float32   `json:"price"` // mean market price of the hour in EUR/MWh
+}
+
+// routeSchedule projects ?exp= onto the hours of ?date= (today by default)
+// as an on/off list automations can import. DST days have 23 or 25 entries.
+func routeSchedule(state *AppState, w http.ResponseWriter, r *http.Request) {
+	condition, err := ParseCondition(r.URL.Query().Get("exp"))
+	if err == nil {
+		err = condition.Validate()
+	}
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+	prices, err := state.LoadPrices(date)
+	if errors.Is(err, ErrNoPrices) {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + date.Format("2006-01-02")})
+		return
+	}
+	var ctx *EvaluateContext
+	var start int
+	if err == nil {
+		ctx, start = state.DayContext(date)
+	}
+	if ctx == nil {
+		w.Header().Set("Retry-After", "60")
+		writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "prices could not be loaded from OTE"})
+		return
+	}
+	all := condition.EvaluateAll(ctx)
+	results := all[start:min(start+len(prices.Prices), len(all))]
+	schedule := []scheduleEntry{}
+	for h, price := range prices.HourlyAverage() {
+		at := date.Add(time.Duration(h) * time.Hour)
+		on := slices.Contains(results[h*4:min(h*4+4, len(results))], true)
+		schedule = append(schedule, scheduleEntry{Hour: at.Hour(), Time: at, On: on, Price: price})
+	}
+	writeJSON(w, http.StatusOK, schedule)
+}
+
+type batteryResponse struct {
+	Date           string      `json:"date"`
+	ChargeHours    []int       `json:"charge_hours"`    // Prague wall-clock hours, ascending
+	ChargeTimes    []time.Time `json:"charge_times"`    // start of each charge hour, Prague local
+	DischargeHours []int       `json:"discharge_hours"` // Prague wall-clock hours, ascending
+	DischargeTimes []time.Time `json:"discharge_times"` // start of each discharge hour, Prague local
+	ChargePrice    float32     `json:"charge_price"`    // mean of the charge hours in EUR/MWh
+	DischargePrice float32     `json:"discharge_price"` // mean of the discharge hours in EUR/MWh
+	Arbitrage      float32     `json:"arbitrage"`       // DischargePrice - ChargePrice
+}
+
+// routeBattery plans a battery cycle for today, whatever ?date= says: the
+// ?charge_hours= cheapest hours to charge and the ?discharge_hours= most
+// expensive to discharge, ranked by hourly average like the cheap and
+// expensive conditions. The arbitrage ignores losses and the order of the
+// hours. The times tell the two 02:00 hours of the autumn DST day apart.
+func routeBattery(state *AppState, w http.ResponseWriter, r *http.Request) {
+	q := r.URL.Query()
+	charge, discharge := 4, 2
+	for _, p := range []struct {
+		name string
+		dst  *int
+	}{{"charge_hours", &charge}, {"discharge_hours", &discharge}} {
+		if v := q.Get(p.name); v != "" {
+			n, err := strconv.Atoi(v)
+			if err != nil || n < 1 || n > 24 {
+				writeJSON(w, http.StatusBadRequest, errorResponse{Error: fmt.Sprintf("%s must be between 1 and 24, got %q", p.name, v)})
+				return
+			}
+			*p.dst = n
 		}
 	}
+
+	_, today, _ := localToday(state.Clock)
+	prices, err := state.LoadPrices(today)
+	if errors.Is(err, ErrNoPrices) {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + today.Format("2006-01-02")})
+		return
+	} else if err != nil {
+		w.Header().Set("Retry-After", "60")
+		writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "prices could not be loaded from OTE"})
+		return
+	}
+	hourly := prices.HourlyAverage()
+	if charge+discharge > len(hourly) {
+		writeJSON(w, http.StatusBadRequest, errorResponse{
+			Error: fmt.Sprintf("charge_hours and discharge_hours must not exceed the %d hours of the day", len(hourly)),
+		})
+		return
+	}
+
+	pick := func(idx []int) ([]int, []time.Time, float32) {
+		slices.Sort(idx)
+		hours := make([]int, len(idx))
+		times := make([]time.Time, len(idx))
+		var sum float32
+		for i, h := range idx {
+			times[i] = today.Add(time.Duration(h) * time.Hour)
+			hours[i] = times[i].Hour()
+			sum += hourly[h]
+		}
+		return hours, times, sum / float32(len(idx))
+	}
+	chargeIdx := cheapestN(hourly, charge)
+	// Discharge only from the hours left, so flat prices cannot pick an
+	// hour twice.
+	var rest []int
+	var restPrices []float32
+	for h, p := range hourly {
+		if !slices.Contains(chargeIdx, h) {
+			rest = append(rest, h)
+			restPrices = append(restPrices, p)
+		}
+	}
+	dischargeIdx := mostExpensiveN(restPrices, discharge)
+	for i, j := range dischargeIdx {
+		dischargeIdx[i] = rest[j]
+	}
+
+	resp := batteryResponse{Date: today.Format("2006-01-02")}
+	resp.ChargeHours, resp.ChargeTimes, resp.ChargePrice = pick(chargeIdx)
+	resp.DischargeHours, resp.DischargeTimes, resp.DischargePrice = pick(dischargeIdx)
+	resp.Arbitrage = resp.DischargePrice - resp.ChargePrice
+	writeJSON(w, http.StatusOK, resp)
+}
+
+type todayHourResponse struct {
+	Hour       int       `json:"hour"`        // Prague wall-clock hour
+	Time       time.Time `json:"time"`        // start of the hour, unambiguous on DST days
+	Price      float32   `json:"price"`       // mean market price of the hour in EUR/MWh
+	TotalPrice float32   `json:"total_price"` // including distribution
+}
+
+// routeAPITodayHour returns a handler serving today's cheapest hour, or the
+// most expensive one with expensive set, ranked by hourly average market
+// price. The answer only changes with the day, so clients may cache it
+// briefly.
+func routeAPITodayHour(expensive bool) func(*AppState, http.ResponseWriter, *http.Request) {
+	pick := CheapestHour
+	if expensive {
+		pick = ExpensiveHour
+	}
+	return func(state *AppState, w http.ResponseWriter, r *http.Request) {
+		_, today, _ := localToday(state.Clock)
+		prices, err := state.LoadPrices(today)
+		if errors.Is(err, ErrNoPrices) {
+			writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + today.Format("2006-01-02")})
+			return
+		} else if err != nil {
+			w.Header().Set("Retry-After", "60")
+			writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "prices could not be loaded from OTE"})
+			return
+		}
+		totals := (&DayPrices{Prices: prices.TotalPrices(&state.Distribution)}).HourlyAverage()
+		h, price, ok := pick(prices.HourlyAverage())
+		if !ok {
+			writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + today.Format("2006-01-02")})
+			return
+		}
+		// The hour is an offset from midnight, so the repeated hour of the
+		// autumn switch reports 2 twice; Time tells them apart.
+		start := today.Add(time.Duration(h) * time.Hour)
+		w.Header().Set("Cache-Control", "public, max-age=300")
+		writeJSON(w, http.StatusOK, todayHourResponse{
+			Hour:       start.Hour(),
+			Time:       start,
+			Price:      price,
+			TotalPrice: totals[h],
+		})
+	}
+}
+
+// pragueWallTime turns a UTC-labelled wall-clock time, as EvaluateContext
+// uses, into the Prague time it names.
+func pragueWallTime(t time.Time) time.Time {
+	loc := PragueLocation()
+	return time.Date(t.Year(), t.Month(), t.Day(), t.Hour(), t.Minute(), t.Second(), 0, loc)
+}
+
+// writeHTML wraps content in the page layout and writes it with the given
+// status. Every HTML page goes through here so none misses its Content-Type.
+func writeHTML(w http.ResponseWriter, status int, content string) {
+	writeHTMLMeta(w, status, content, PageMeta{})
+}
+
+// writeHTMLMeta is writeHTML with link-preview tags.
+func writeHTMLMeta(w http.ResponseWriter, status int, content string, meta PageMeta) {
+	w.Header().Set("Content-Type", "text/html; charset=utf-8")
+	w.WriteHeader(status)
+	io.WriteString(w, RenderLayoutMeta(content, meta))
+}
+
+// writeJSON encodes v as the response body with the given status.
+func writeJSON(w http.ResponseWriter, status int, v any) {
+	w.Header().Set("Content-Type", "application/json")
+	w.WriteHeader(status)
+	if err := json.NewEncoder(w).Encode(v); err != nil {
+		log.Printf("writeJSON: %v", err)
+	}
+}
+
+type validateResponse struct {
+	Valid    bool   `json:"valid"`
+	Error    string `json:"error,omitempty"`
+	Position *int64 `json:"position,omitempty"`
+}
+
+// routeAPIValidate parses and validates ?exp= without building an
+// EvaluateContext, so it never touches the DB or OTE.
+func routeAPIValidate(w http.ResponseWriter, r *http.Request) {
+	exp, _, _, _ := parseOptQuery(r.URL.Query())
+	condition, err := ParseCondition(exp)
+	if err == nil {
+		err = condition.Validate()
+	}
+	if err != nil {
+		resp := validateResponse{Valid: false, Error: err.Error()}
+		if pos, ok := ParseErrorPosition(err); ok {
+			resp.Position = &pos
+		}
+		writeJSON(w, http.StatusOK, resp)
+		return
+	}
+	writeJSON(w, http.StatusOK, validateResponse{Valid: true})
+}
+
+type normalizeResponse struct {
+	Display    string `json:"display"`    // Condition.Format
+	Expression string `json:"expression"` // Condition.Expression
+	Exp        string `json:"exp"`        // canonical ?exp= value
+	URL        string `json:"url"`        // optimizer link with Exp
+}
+
+// routeNormalize parses and validates ?exp= and answers its canonical form,
+// for building shareable URLs from hand-written JSON5.
+func routeNormalize(w http.ResponseWriter, r *http.Request) {
+	condition, err := ParseCondition(r.URL.Query().Get("exp"))
+	if err == nil {
+		err = condition.Validate()
+	}
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+	exp, err := condition.ExpParam()
+	if err != nil {
+		writeJSON(w, http.StatusInternalServerError, errorResponse{Error: err.Error()})
+		return
+	}
+	writeJSON(w, http.StatusOK, normalizeResponse{
+		Display:    condition.Format(),
+		Expression: condition.Expression(),
+		Exp:        exp,
+		URL:        "/optimizer?exp=" + url.QueryEscape(exp),
+	})
+}
+
+type healthResponse struct {
+	Status string `json:"status"`
+}
+
+// routeHealthz is the liveness probe. It touches neither the DB nor OTE.
+func routeHealthz(w http.ResponseWriter, r *http.Request) {
+	writeJSON(w, http.StatusOK, healthResponse{Status: "ok"})
+}
+
+// routeReadyz is the readiness probe: ready once today's prices are in the
+// DB, which the prefetcher ensures shortly after startup. It does no network
+// I/O.
+func routeReadyz(state *AppState, w http.ResponseWriter, r *http.Request) {
+	today := state.db.PragueDate(state.Clock.Now())
+	has, err := state.db.HasDay(today)
+	if err != nil || !has {
+		writeJSON(w, http.StatusServiceUnavailable, healthResponse{Status: "today's prices are not cached yet"})
+		return
+	}
+	writeJSON(w, http.StatusOK, healthResponse{Status: "ready"})
+}
+
+type perfResponse struct {
+	UptimeSeconds float64  `json:"uptime_seconds"`
+	CachedDays    int      `json:"cached_days"`
+	LastFetchMs   *float64 `json:"last_fetch_ms"` // null until the first OTE fetch
+}
+
+// routePerf reports process metrics. It only reads counters and one COUNT
+// query, so it is safe to poll.
+func routePerf(state *AppState, w http.ResponseWriter, r *http.Request) {
+	days, err := state.db.CountDays()
+	if err != nil {
+		writeJSON(w, http.StatusInternalServerError, errorResponse{Error: err.Error()})
+		return
+	}
+	resp := perfResponse{
+		UptimeSeconds: state.Clock.Now().Sub(state.startedAt).Seconds(),
+		CachedDays:    days,
+	}
+	if ns := state.lastFetch.Load(); ns > 0 {
+		ms := float64(ns) / float64(time.Millisecond)
+		resp.LastFetchMs = &ms
+	}
+	writeJSON(w, http.StatusOK, resp)
+}
+
+type errorResponse struct {
+	Error string `json:"error"`
+}
+
+type bestStartResponse struct {
+	Date       string    `json:"date"`
+	Duration   uint8     `json:"duration"`
+	Start      time.Time `json:"start"`
+	StartIndex int       `json:"start_index"`
+	AvgPrice   float32   `json:"avg_price"` // EUR/MWh over the block
+	Cost       float32   `json:"cost"`      // EUR for a constant 1 kW load over the block
+}
+
+// routeAPIBestStart finds the start of the cheapest run of ?duration= hours
+// inside the [from, to) hour window of ?date=. The search steps by quarter
+// hour, so the recommended start may fall on :15, :30 or :45.
+func routeAPIBestStart(state *AppState, w http.ResponseWriter, r *http.Request) {
+	q := r.URL.Query()
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+	duration, from, to := uint8(1), uint8(0), uint8(24)
+	for _, p := range []struct {
+		name string
+		dst  *uint8
+	}{{"duration", &duration}, {"from", &from}, {"to", &to}} {
+		if v := q.Get(p.name); v != "" {
+			n, err := strconv.ParseUint(v, 10, 8)
+			if err != nil {
+				writeJSON(w, http.StatusBadRequest, errorResponse{Error: fmt.Sprintf("invalid %s: %q", p.name, v)})
+				return
+			}
+			*p.dst = uint8(n)
+		}
+	}
+	if to > 24 || from >= to {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: "window must satisfy 0 <= from < to <= 24"})
+		return
+	}
+	if duration == 0 || duration > to-from {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: fmt.Sprintf("duration must be between 1 and %d hours for this window", to-from)})
+		return
+	}
+
+	prices, ok := state.GetPrices(date)
+	if !ok {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + date.Format("2006-01-02")})
+		return
+	}
+	// The window is in wall-clock hours; map it to slots like the conditions
+	// do so it does not shift by an hour after a DST switch.
+	slots := PricesContext{DayLens: []int{prices.Slots()}}
+	lo := min(slots.slotAt(int(from)*4), len(prices.Prices))
+	hi := min(slots.slotAt(int(to)*4), len(prices.Prices))
+	n := int(duration) * 4
+	idx, sum, ok := CheapestBlock(prices.Prices[lo:hi], n)
+	if !ok {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: "window is shorter than duration on this day"})
+		return
+	}
+	avg := sum / float32(n)
+	writeJSON(w, http.StatusOK, bestStartResponse{
+		Date:       date.Format("2006-01-02"),
+		Duration:   duration,
+		Start:      date.Add(time.Duration(lo+idx) * 15 * time.Minute),
+		StartIndex: lo + idx,
+		AvgPrice:   avg,
+		Cost:       avg * float32(duration) / 1000.0,
+	})
+}
+
+// MaxExportDays bounds the ?from..?to range of /api/export.
+const MaxExportDays = 366
+
+type exportLine struct {
+	Date   string    `json:"date"`
+	Prices []float32 `json:"prices,omitempty"`
+	Error  string    `json:"error,omitempty"`
+}
+
+// routeAPIExport streams one NDJSON line per day in [from, to], flushing as
+// each day resolves so memory stays flat for year-long ranges. A day that
+// cannot be loaded yields an error line instead of aborting the stream.
+func routeAPIExport(state *AppState, w http.ResponseWriter, r *http.Request) {
+	loc := PragueLocation()
+	q := r.URL.Query()
+	from, errFrom := time.ParseInLocation("2006-01-02", q.Get("from"), loc)
+	to, errTo := time.ParseInLocation("2006-01-02", q.Get("to"), loc)
+	if errFrom != nil || errTo != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: "from and to must be dates in YYYY-MM-DD format"})
+		return
+	}
+	if to.Before(from) || to.After(from.AddDate(0, 0, MaxExportDays-1)) {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: fmt.Sprintf("range must be 1 to %d days with from <= to", MaxExportDays)})
+		return
+	}
+
+	w.Header().Set("Content-Type", "application/x-ndjson")
+	w.WriteHeader(http.StatusOK)
+	flusher, _ := w.(http.Flusher)
+	enc := json.NewEncoder(w)
+	for d := from; !d.After(to); d = d.AddDate(0, 0, 1) {
+		line := exportLine{Date: d.Format("2006-01-02")}
+		if prices, ok := state.GetPrices(d); ok {
+			line.Prices = prices.Prices
+		} else {
+			line.Error = "prices unavailable"
+		}
+		if err := enc.Encode(line); err != nil {
+			log.Printf("export stream aborted at %s: %v", line.Date, err)
+			return
+		}
+		if flusher != nil {
+			flusher.Flush()
+		}
+	}
+}
+
+type pricesResponse struct {
+	Date        string    `json:"date"`
+	Prices      []float32 `json:"prices"`       // EUR/MWh per quarter hour
+	TotalPrices []float32 `json:"total_prices"` // including distribution
+	Cheapest    int       `json:"cheapest_index"`
+	Expensive   int       `json:"expensive_index"`
+}
+
+// routeAPIPrices serves one day's market prices and the totals including
+// distribution as JSON. The cheapest and most expensive indexes refer to the
+// market prices.
+func routeAPIPrices(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+	prices, ok := state.GetPrices(date)
+	if !ok {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no prices for " + date.Format("2006-01-02")})
+		return
+	}
+	cheapest, _, _ := CheapestHour(prices.Prices)
+	expensive, _, _ := ExpensiveHour(prices.Prices)
+	writeJSON(w, http.StatusOK, pricesResponse{
+		Date:        date.Format("2006-01-02"),
+		Prices:      prices.Prices,
+		TotalPrices: prices.TotalPrices(&state.Distribution),
+		Cheapest:    cheapest,
+		Expensive:   expensive,
+	})
+}
+
+type rawResponse struct {
+	Date  string               `json:"date"`
+	Lines map[string][]float32 `json:"lines"` // every OTE series by title
+}
+
+// routeAPIRaw passes through all series of the OTE chart data for ?date=,
+// not just the 15-minute prices, fetched fresh on every request.
+func routeAPIRaw(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		writeJSON(w, http.StatusBadRequest, errorResponse{Error: err.Error()})
+		return
+	}
+	lines, err := state.DataLines(date)
+	if notPublished(err) {
+		writeJSON(w, http.StatusNotFound, errorResponse{Error: "no data for " + date.Format("2006-01-02")})
+		return
+	} else if err != nil {
+		w.Header().Set("Retry-After", "60")
+		writeJSON(w, http.StatusServiceUnavailable, errorResponse{Error: "data could not be loaded from OTE"})
+		return
+	}
+	writeJSON(w, http.StatusOK, rawResponse{Date: date.Format("2006-01-02"), Lines: lines})
+}
+
+// routeAPIPricesCSV serves one day as CSV, one row per quarter hour, with the
+// market price and the price including distribution surcharges.
+func routeAPIPricesCSV(state *AppState, w http.ResponseWriter, r *http.Request) {
+	date, _, err := requestDate(r, state.Clock)
+	if err != nil {
+		http.Error(w, err.Error(), http.StatusBadRequest)
+		return
+	}
+	prices, ok := state.GetPrices(date)
+	if !ok {
+		http.Error(w, "No prices for this date", http.StatusNotFound)
+		return
+	}
+	totalPrices := prices.TotalPrices(&state.Distribution)
+
+	w.Header().Set("Content-Type", "text/csv; charset=utf-8")
+	w.Header().Set("Content-Disposition",
+		fmt.Sprintf(`attachment; filename="ote-prices-%s.csv"`, date.Format("2006-01-02")))
+	cw := csv.NewWriter(w)
+	_ = cw.Write([]string{"time", "market_eur_mwh", "total_eur_mwh"})
+	for i, price := range prices.Prices {
+		// RFC 3339 keeps the repeated hour of the autumn DST switch unambiguous.
+		start := date.Add(time.Duration(i) * 15 * time.Minute)
+		_ = cw.Write([]string{
+			start.Format(time.RFC3339),
+			strconv.FormatFloat(float64(price), 'f', 2, 32),
+			strconv.FormatFloat(float64(totalPrices[i]), 'f', 2, 32),
+		})
+	}
+	cw.Flush()
+	if err := cw.Error(); err != nil {
+		log.Printf("prices.csv: %v", err)
+	}
+}
+
+func routeConsumption(state *AppState, w http.ResponseWriter, r *http.Request) {
+	currency := requestCurrency(r.URL.Query())
 	curStr := currency.String()
 
 	var sb strings.Builder
@@ -360,16 +1807,16 @@ func routeConsumption(state *AppState, w http.ResponseWriter, r *http.Request) {
 		const maxUpload = 10 << 20 // 10 MiB
 		r.Body = http.MaxBytesReader(w, r.Body, maxUpload)
 		if err := r.ParseMultipartForm(maxUpload); err != nil {
-			renderConsumptionPage(w, sb.String()+
+			writeHTML(w, http.StatusBadRequest, sb.String()+
 				`<p class="text-red-600 my-4">Upload too large or invalid form.</p>`+
-				renderConsumptionForm(curStr), http.StatusBadRequest)
+				renderConsumptionForm(curStr))
 			return
 		}
 		file, _, err := r.FormFile("csv")
 		if err != nil {
-			renderConsumptionPage(w, sb.String()+
+			writeHTML(w, http.StatusBadRequest, sb.String()+
 				`<p class="text-red-600 my-4">Missing CSV file.</p>`+
-				renderConsumptionForm(curStr), http.StatusBadRequest)
+				renderConsumptionForm(curStr))
 			return
 		}
 		defer file.Close()
@@ -379,38 +1826,33 @@ func routeConsumption(state *AppState, w http.ResponseWriter, r *http.Request) {
 			body := sb.String() +
 				fmt.Sprintf(`<p class="text-red-600 my-4">%s</p>`, html.EscapeString(err.Error())) +
 				renderConsumptionForm(curStr)
-			renderConsumptionPage(w, body, http.StatusBadRequest)
+			writeHTML(w, http.StatusBadRequest, body)
 			return
 		}
 		if len(quarters) == 0 {
-			renderConsumptionPage(w, sb.String()+
+			writeHTML(w, http.StatusBadRequest, sb.String()+
 				`<p class="text-red-600 my-4">No data rows found in CSV.</p>`+
-				renderConsumptionForm(curStr), http.StatusBadRequest)
+				renderConsumptionForm(curStr))
 			return
 		}
 
-		analysis, err := state.AnalyzeConsumption(quarters, time.Now())
+		analysis, err := state.AnalyzeConsumption(quarters, state.Clock.Now())
 		if err != nil {
 			body := sb.String() +
 				fmt.Sprintf(`<p class="text-red-600 my-4">%s</p>`, html.EscapeString(err.Error())) +
 				renderConsumptionForm(curStr)
-			renderConsumptionPage(w, body, http.StatusBadRequest)
+			writeHTML(w, http.StatusBadRequest, body)
 			return
 		}
 		sb.WriteString(renderConsumptionResults(analysis, currency))
 		sb.WriteString(`<div class="my-8 text-sm">Upload another file:</div>`)
 		sb.WriteString(renderConsumptionForm(curStr))
-		renderConsumptionPage(w, sb.String(), http.StatusOK)
+		writeHTML(w, http.StatusOK, sb.String())
 		return
 	}
 
 	sb.WriteString(`<p class="my-4">Upload a CSV exported from ČEZ "Profilová náměřená data" (PND export). The file is processed in memory and is not stored on the server.</p>`)
 	sb.WriteString(renderConsumptionForm(curStr))
-	renderConsumptionPage(w, sb.String(), http.StatusOK)
+	writeHTML(w, http.StatusOK, sb.String())
 }
 
-func renderConsumptionPage(w http.ResponseWriter, body string, status int) {
-	w.Header().Set("Content-Type", "text/html; charset=utf-8")
-	w.WriteHeader(status)
-	io.WriteString(w, RenderLayout(body))
-}